@@ -3,6 +3,7 @@ use window_vibrancy::{NSVisualEffectMaterial, apply_vibrancy};
 
 pub(crate) fn setup(app: &mut tauri::App) -> Result<(), Box<dyn std::error::Error>> {
     let handle = app.handle().clone();
+    crate::tools::ffprobe::cache::init_probe_cache(&handle);
     create_main_window(handle);
     Ok(())
 }
@@ -1,15 +1,27 @@
 pub(crate) use crate::tools::data::rsext as data;
+pub(crate) use crate::tools::ffmpeg::attachments as ffmpeg_attachments;
 pub(crate) use crate::tools::ffmpeg::cancel as ffmpeg_cancel;
+pub(crate) use crate::tools::ffmpeg::captions as ffmpeg_captions;
+pub(crate) use crate::tools::ffmpeg::chapters as ffmpeg_chapters;
 pub(crate) use crate::tools::ffmpeg::download as ffmpeg_download;
 pub(crate) use crate::tools::ffmpeg::extract as ffmpeg_extract;
 pub(crate) use crate::tools::ffmpeg::version as ffmpeg_version;
+pub(crate) use crate::tools::ffprobe::bitrate as ffprobe_bitrate;
+pub(crate) use crate::tools::ffprobe::cache as ffprobe_cache;
+pub(crate) use crate::tools::ffprobe::keyframes as ffprobe_keyframes;
 pub(crate) use crate::tools::ffprobe::probe as ffprobe;
+pub(crate) use crate::tools::ffprobe::scan as ffprobe_scan;
 pub(crate) use crate::tools::fs::cancel as fs_cancel;
 pub(crate) use crate::tools::fs::file_ops as fs_file_ops;
 pub(crate) use crate::tools::fs::metadata as fs_metadata;
 pub(crate) use crate::tools::fs::open_folder as fs_open_folder;
+pub(crate) use crate::tools::merge::batch as merge_batch;
 pub(crate) use crate::tools::merge::cancel as merge_cancel;
+pub(crate) use crate::tools::merge::job as merge_job;
+pub(crate) use crate::tools::merge::matching as merge_matching;
 pub(crate) use crate::tools::merge::merge;
+pub(crate) use crate::tools::merge::plan as merge_plan;
+pub(crate) use crate::tools::merge::remux as merge_remux;
 pub(crate) use crate::tools::ocr::cancel as ocr_cancel;
 pub(crate) use crate::tools::ocr::export as ocr_export;
 pub(crate) use crate::tools::ocr::models as ocr_models;
@@ -17,9 +17,20 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_store::Builder::new().build())
+        .manage(tools::merge::batch::MergeBatchRegistry::default())
         .invoke_handler(tauri::generate_handler![
             commands::ffprobe::probe_file,
+            commands::ffprobe_bitrate::analyze_bitrate,
+            commands::ffprobe_bitrate::cancel_analyze_bitrate,
+            commands::ffprobe_cache::clear_probe_cache,
+            commands::ffprobe_keyframes::resolve_clip_cut,
+            commands::ffprobe_scan::scan_directory,
+            commands::ffprobe_scan::cancel_scan_directory,
             commands::ffmpeg_extract::extract_track,
+            commands::ffmpeg_extract::extract_tracks,
+            commands::ffmpeg_chapters::extract_chapters,
+            commands::ffmpeg_attachments::extract_attachments,
+            commands::ffmpeg_captions::extract_closed_captions,
             commands::ffmpeg_cancel::cancel_extract,
             commands::ffmpeg_cancel::cancel_extract_file,
             commands::fs_open_folder::open_folder,
@@ -27,6 +38,13 @@ pub fn run() {
             commands::ffmpeg_version::get_ffmpeg_version,
             commands::ffmpeg_download::download_ffmpeg,
             commands::merge::merge_tracks,
+            commands::merge_job::validate_merge_job,
+            commands::merge_matching::match_files_to_videos,
+            commands::merge_plan::plan_merge,
+            commands::merge_remux::remux_tracks,
+            commands::merge_batch::start_merge_batch,
+            commands::merge_batch::get_merge_batch_status,
+            commands::merge_batch::cancel_merge_batch,
             commands::merge_cancel::cancel_merge,
             commands::merge_cancel::cancel_merge_file,
             commands::fs_file_ops::rename_file,
@@ -0,0 +1,480 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A chapter marker with millisecond timestamps
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct Chapter {
+    pub(crate) start_ms: u64,
+    #[serde(default)]
+    pub(crate) end_ms: Option<u64>,
+    #[serde(default)]
+    pub(crate) title: Option<String>,
+}
+
+/// Parse "HH:MM:SS", "HH:MM:SS.fff" or "HH:MM:SS.fffffffff" into milliseconds
+pub(crate) fn parse_chapter_timestamp(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let (clock, fraction) = match value.split_once('.') {
+        Some((clock, fraction)) => (clock, fraction),
+        None => (value, ""),
+    };
+
+    let parts: Vec<&str> = clock.split(':').collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let hours: u64 = parts[0].parse().ok()?;
+    let minutes: u64 = parts[1].parse().ok()?;
+    let seconds: u64 = parts[2].parse().ok()?;
+    if minutes >= 60 || seconds >= 60 {
+        return None;
+    }
+
+    if !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let millis = format!("{:0<3}", &fraction[..fraction.len().min(3)])
+        .parse::<u64>()
+        .ok()?;
+
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + millis)
+}
+
+/// Format milliseconds as "HH:MM:SS.fff"
+pub(crate) fn format_chapter_timestamp(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// Parse an OGM chapter file (`CHAPTER01=00:00:00.000` / `CHAPTER01NAME=Intro`)
+pub(crate) fn parse_ogm_chapters(content: &str) -> Result<Vec<Chapter>, String> {
+    let mut chapters: Vec<(String, Chapter)> = Vec::new();
+
+    for (line_no, line) in content.lines().enumerate() {
+        let line = line.trim().trim_start_matches('\u{feff}');
+        if line.is_empty() {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            return Err(format!(
+                "Invalid OGM chapter line {}: {}",
+                line_no + 1,
+                line
+            ));
+        };
+        let key = key.trim().to_uppercase();
+        if !key.starts_with("CHAPTER") {
+            return Err(format!(
+                "Invalid OGM chapter line {}: {}",
+                line_no + 1,
+                line
+            ));
+        }
+
+        if let Some(id) = key.strip_suffix("NAME") {
+            let title = value.trim().to_string();
+            match chapters.iter_mut().find(|(chapter_id, _)| chapter_id == id) {
+                Some((_, chapter)) => chapter.title = Some(title).filter(|t| !t.is_empty()),
+                None => {
+                    return Err(format!(
+                        "Chapter name on line {} has no timestamp",
+                        line_no + 1
+                    ));
+                }
+            }
+        } else {
+            let start_ms = parse_chapter_timestamp(value).ok_or_else(|| {
+                format!(
+                    "Invalid chapter timestamp on line {}: {}",
+                    line_no + 1,
+                    value
+                )
+            })?;
+            chapters.push((
+                key,
+                Chapter {
+                    start_ms,
+                    end_ms: None,
+                    title: None,
+                },
+            ));
+        }
+    }
+
+    Ok(chapters.into_iter().map(|(_, chapter)| chapter).collect())
+}
+
+fn xml_unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn xml_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+fn xml_element<'a>(content: &'a str, name: &str) -> Option<&'a str> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = content.find(&open)? + open.len();
+    let end = content[start..].find(&close)? + start;
+    Some(content[start..end].trim())
+}
+
+/// Parse the chapter atoms of a Matroska chapter XML file
+pub(crate) fn parse_matroska_chapters_xml(content: &str) -> Result<Vec<Chapter>, String> {
+    let mut chapters = Vec::new();
+
+    // Each atom's own fields come before any nested atom or its closing tag
+    for atom in content.split("<ChapterAtom>").skip(1) {
+        let own = atom.split("</ChapterAtom>").next().unwrap_or("");
+
+        let start = xml_element(own, "ChapterTimeStart")
+            .ok_or_else(|| "Chapter atom without ChapterTimeStart".to_string())?;
+        let start_ms = parse_chapter_timestamp(start)
+            .ok_or_else(|| format!("Invalid chapter timestamp: {}", start))?;
+        let end_ms = match xml_element(own, "ChapterTimeEnd") {
+            Some(end) => Some(
+                parse_chapter_timestamp(end)
+                    .ok_or_else(|| format!("Invalid chapter timestamp: {}", end))?,
+            ),
+            None => None,
+        };
+        let title = xml_element(own, "ChapterString")
+            .map(xml_unescape)
+            .filter(|t| !t.is_empty());
+
+        chapters.push(Chapter {
+            start_ms,
+            end_ms,
+            title,
+        });
+    }
+
+    if chapters.is_empty() {
+        return Err("No chapters found in Matroska chapter XML".to_string());
+    }
+
+    Ok(chapters)
+}
+
+/// Read the chapters of an ffprobe result produced with `-show_chapters`
+pub(crate) fn chapters_from_probe(probe: &Value) -> Vec<Chapter> {
+    let seconds_to_ms = |value: Option<&Value>| {
+        value
+            .and_then(|v| v.as_str())
+            .and_then(|v| v.parse::<f64>().ok())
+            .map(|secs| (secs.max(0.0) * 1000.0).round() as u64)
+    };
+
+    probe
+        .get("chapters")
+        .and_then(|c| c.as_array())
+        .map(|chapters| {
+            chapters
+                .iter()
+                .filter_map(|chapter| {
+                    Some(Chapter {
+                        start_ms: seconds_to_ms(chapter.get("start_time"))?,
+                        end_ms: seconds_to_ms(chapter.get("end_time")),
+                        title: chapter
+                            .get("tags")
+                            .and_then(|tags| tags.get("title"))
+                            .and_then(|v| v.as_str())
+                            .filter(|t| !t.is_empty())
+                            .map(str::to_string),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Sort chapters and fill missing ends from the next start or the total duration
+pub(crate) fn normalize_chapters(
+    mut chapters: Vec<Chapter>,
+    total_ms: Option<u64>,
+) -> Vec<Chapter> {
+    chapters.sort_by_key(|chapter| chapter.start_ms);
+
+    let next_starts: Vec<Option<u64>> = chapters
+        .iter()
+        .skip(1)
+        .map(|chapter| Some(chapter.start_ms))
+        .chain(std::iter::once(total_ms))
+        .collect();
+
+    for (chapter, next_start) in chapters.iter_mut().zip(next_starts) {
+        if chapter.end_ms.is_none_or(|end| end <= chapter.start_ms) {
+            chapter.end_ms = Some(
+                next_start
+                    .filter(|next| *next > chapter.start_ms)
+                    .unwrap_or(chapter.start_ms + 1),
+            );
+        }
+    }
+
+    chapters
+}
+
+fn escape_ffmetadata(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, '=' | ';' | '#' | '\\' | '\n') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Render chapters as an FFMETADATA file with a millisecond timebase
+pub(crate) fn to_ffmetadata(chapters: &[Chapter]) -> String {
+    let mut content = String::from(";FFMETADATA1\n");
+    for chapter in chapters {
+        content.push_str("\n[CHAPTER]\nTIMEBASE=1/1000\n");
+        content.push_str(&format!("START={}\n", chapter.start_ms));
+        content.push_str(&format!(
+            "END={}\n",
+            chapter.end_ms.unwrap_or(chapter.start_ms)
+        ));
+        if let Some(title) = chapter.title.as_deref() {
+            content.push_str(&format!("title={}\n", escape_ffmetadata(title)));
+        }
+    }
+    content
+}
+
+/// Render chapters as an OGM simple chapter file
+pub(crate) fn to_ogm_chapters(chapters: &[Chapter]) -> String {
+    let mut content = String::new();
+    for (i, chapter) in chapters.iter().enumerate() {
+        content.push_str(&format!(
+            "CHAPTER{:02}={}\n",
+            i + 1,
+            format_chapter_timestamp(chapter.start_ms)
+        ));
+        content.push_str(&format!(
+            "CHAPTER{:02}NAME={}\n",
+            i + 1,
+            chapter.title.as_deref().unwrap_or("")
+        ));
+    }
+    content
+}
+
+/// Render chapters as a Matroska chapter XML file with a single edition
+pub(crate) fn to_matroska_chapters_xml(chapters: &[Chapter]) -> String {
+    // Matroska timestamps carry nanosecond precision
+    let timestamp = |ms: u64| format!("{}000000", format_chapter_timestamp(ms));
+
+    let mut content = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE Chapters SYSTEM \"matroskachapters.dtd\">\n<Chapters>\n  <EditionEntry>\n",
+    );
+    for chapter in chapters {
+        content.push_str("    <ChapterAtom>\n");
+        content.push_str(&format!(
+            "      <ChapterTimeStart>{}</ChapterTimeStart>\n",
+            timestamp(chapter.start_ms)
+        ));
+        if let Some(end_ms) = chapter.end_ms {
+            content.push_str(&format!(
+                "      <ChapterTimeEnd>{}</ChapterTimeEnd>\n",
+                timestamp(end_ms)
+            ));
+        }
+        if let Some(title) = chapter.title.as_deref() {
+            content.push_str(&format!(
+                "      <ChapterDisplay>\n        <ChapterString>{}</ChapterString>\n        <ChapterLanguage>und</ChapterLanguage>\n      </ChapterDisplay>\n",
+                xml_escape(title)
+            ));
+        }
+        content.push_str("    </ChapterAtom>\n");
+    }
+    content.push_str("  </EditionEntry>\n</Chapters>\n");
+    content
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Render chapters as CSV with one row per chapter
+pub(crate) fn to_chapters_csv(chapters: &[Chapter]) -> String {
+    let mut content = String::from("chapter,start,end,title\n");
+    for (i, chapter) in chapters.iter().enumerate() {
+        content.push_str(&format!(
+            "{},{},{},{}\n",
+            i + 1,
+            format_chapter_timestamp(chapter.start_ms),
+            chapter
+                .end_ms
+                .map(format_chapter_timestamp)
+                .unwrap_or_default(),
+            csv_field(chapter.title.as_deref().unwrap_or(""))
+        ));
+    }
+    content
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        Chapter, chapters_from_probe, format_chapter_timestamp, normalize_chapters,
+        parse_chapter_timestamp, parse_matroska_chapters_xml, parse_ogm_chapters, to_chapters_csv,
+        to_ffmetadata, to_matroska_chapters_xml, to_ogm_chapters,
+    };
+
+    #[test]
+    fn parse_chapter_timestamp_accepts_common_precisions() {
+        assert_eq!(parse_chapter_timestamp("00:01:02"), Some(62_000));
+        assert_eq!(parse_chapter_timestamp("01:00:00.5"), Some(3_600_500));
+        assert_eq!(parse_chapter_timestamp("00:00:01.234567890"), Some(1_234));
+        assert_eq!(parse_chapter_timestamp("00:61:00.000"), None);
+        assert_eq!(parse_chapter_timestamp("bogus"), None);
+    }
+
+    #[test]
+    fn parse_ogm_chapters_pairs_names_with_timestamps() {
+        let chapters = parse_ogm_chapters(
+            "CHAPTER01=00:00:00.000\nCHAPTER01NAME=Intro\nCHAPTER02=00:01:30.500\nCHAPTER02NAME=Part A\n",
+        )
+        .expect("ogm chapters should parse");
+
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[1].start_ms, 90_500);
+        assert_eq!(chapters[1].title.as_deref(), Some("Part A"));
+        assert!(parse_ogm_chapters("CHAPTER01NAME=Orphan").is_err());
+    }
+
+    #[test]
+    fn parse_matroska_chapters_xml_reads_atoms() {
+        let xml = r#"<?xml version="1.0"?>
+<Chapters><EditionEntry>
+  <ChapterAtom>
+    <ChapterTimeStart>00:00:00.000000000</ChapterTimeStart>
+    <ChapterDisplay><ChapterString>Opening &amp; Titles</ChapterString></ChapterDisplay>
+  </ChapterAtom>
+  <ChapterAtom>
+    <ChapterTimeStart>00:02:00.000000000</ChapterTimeStart>
+    <ChapterTimeEnd>00:20:00.000000000</ChapterTimeEnd>
+    <ChapterDisplay><ChapterString>Episode</ChapterString></ChapterDisplay>
+  </ChapterAtom>
+</EditionEntry></Chapters>"#;
+
+        let chapters = parse_matroska_chapters_xml(xml).expect("xml chapters should parse");
+
+        assert_eq!(chapters.len(), 2);
+        assert_eq!(chapters[0].title.as_deref(), Some("Opening & Titles"));
+        assert_eq!(chapters[1].start_ms, 120_000);
+        assert_eq!(chapters[1].end_ms, Some(1_200_000));
+    }
+
+    #[test]
+    fn chapters_from_probe_converts_seconds() {
+        let probe = json!({"chapters": [
+            {"start_time": "0.000000", "end_time": "61.500000", "tags": {"title": "One"}},
+            {"start_time": "61.500000", "end_time": "120.000000"}
+        ]});
+
+        let chapters = chapters_from_probe(&probe);
+
+        assert_eq!(chapters[0].end_ms, Some(61_500));
+        assert_eq!(chapters[1].title, None);
+    }
+
+    #[test]
+    fn normalize_chapters_fills_ends_and_ffmetadata_escapes_titles() {
+        let chapters = normalize_chapters(
+            vec![
+                Chapter {
+                    start_ms: 5_000,
+                    end_ms: None,
+                    title: Some("A=B; #1".to_string()),
+                },
+                Chapter {
+                    start_ms: 0,
+                    end_ms: None,
+                    title: None,
+                },
+            ],
+            Some(10_000),
+        );
+
+        assert_eq!(chapters[0].end_ms, Some(5_000));
+        assert_eq!(chapters[1].end_ms, Some(10_000));
+        let metadata = to_ffmetadata(&chapters);
+        assert!(metadata.starts_with(";FFMETADATA1\n"));
+        assert!(metadata.contains("START=5000\nEND=10000\ntitle=A\\=B\\; \\#1\n"));
+    }
+
+    fn sample_chapters() -> Vec<Chapter> {
+        vec![
+            Chapter {
+                start_ms: 0,
+                end_ms: Some(90_500),
+                title: Some("Opening & \"Titles\"".to_string()),
+            },
+            Chapter {
+                start_ms: 90_500,
+                end_ms: Some(3_723_004),
+                title: Some("Part A, B".to_string()),
+            },
+        ]
+    }
+
+    #[test]
+    fn format_chapter_timestamp_pads_every_field() {
+        assert_eq!(format_chapter_timestamp(0), "00:00:00.000");
+        assert_eq!(format_chapter_timestamp(3_723_004), "01:02:03.004");
+    }
+
+    #[test]
+    fn ogm_and_matroska_xml_exports_parse_back() {
+        let chapters = sample_chapters();
+
+        let ogm = to_ogm_chapters(&chapters);
+        assert!(ogm.starts_with("CHAPTER01=00:00:00.000\nCHAPTER01NAME=Opening"));
+        let from_ogm = parse_ogm_chapters(&ogm).expect("exported ogm should parse");
+        assert_eq!(from_ogm[1].start_ms, 90_500);
+        assert_eq!(from_ogm[1].title.as_deref(), Some("Part A, B"));
+
+        let xml = to_matroska_chapters_xml(&chapters);
+        assert!(xml.contains("<ChapterTimeEnd>01:02:03.004000000</ChapterTimeEnd>"));
+        let from_xml = parse_matroska_chapters_xml(&xml).expect("exported xml should parse");
+        assert_eq!(from_xml, chapters);
+    }
+
+    #[test]
+    fn to_chapters_csv_quotes_titles_with_separators() {
+        let csv = to_chapters_csv(&sample_chapters());
+
+        assert_eq!(
+            csv,
+            "chapter,start,end,title\n\
+             1,00:00:00.000,00:01:30.500,\"Opening & \"\"Titles\"\"\"\n\
+             2,00:01:30.500,01:02:03.004,\"Part A, B\"\n"
+        );
+    }
+}
@@ -0,0 +1,253 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// Video encoder settings used when a clip is cut in accurate mode
+pub(crate) const ACCURATE_VIDEO_ENCODE_ARGS: &[&str] =
+    &["-c:v", "libx264", "-crf", "18", "-preset", "medium"];
+
+/// How a clip's boundaries are cut
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum ClipMode {
+    /// Stream copy, with the boundaries moved to the surrounding keyframes
+    #[default]
+    Keyframe,
+    /// Cut at the requested times and re-encode the video
+    Accurate,
+}
+
+/// Requested time range; an open end runs to the end of the input
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct ClipRange {
+    #[serde(default)]
+    pub(crate) start_ms: u64,
+    #[serde(default)]
+    pub(crate) end_ms: Option<u64>,
+    #[serde(default)]
+    pub(crate) mode: ClipMode,
+}
+
+impl ClipRange {
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        match self.end_ms {
+            Some(end_ms) if end_ms <= self.start_ms => Err(format!(
+                "Clip end ({} ms) must be after its start ({} ms)",
+                end_ms, self.start_ms
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Cut points a clip is made with, next to the ones that were requested
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ClipCut {
+    pub(crate) requested_start_ms: u64,
+    pub(crate) requested_end_ms: Option<u64>,
+    pub(crate) start_ms: u64,
+    pub(crate) end_ms: Option<u64>,
+    pub(crate) mode: ClipMode,
+}
+
+fn format_secs(ms: u64) -> String {
+    format!("{:.3}", ms as f64 / 1000.0)
+}
+
+impl ClipCut {
+    /// Cut exactly at the requested times
+    pub(crate) fn exact(range: &ClipRange) -> Self {
+        Self {
+            requested_start_ms: range.start_ms,
+            requested_end_ms: range.end_ms,
+            start_ms: range.start_ms,
+            end_ms: range.end_ms,
+            mode: range.mode,
+        }
+    }
+
+    pub(crate) fn reencodes_video(&self) -> bool {
+        self.mode == ClipMode::Accurate
+    }
+
+    pub(crate) fn duration_ms(&self) -> Option<u64> {
+        self.end_ms
+            .map(|end_ms| end_ms.saturating_sub(self.start_ms))
+    }
+
+    /// `-ss` and `-itsoffset` for an input shifted by `delay_ms`, so that the input stays
+    /// aligned with the clipped source; seeking never goes before the start of the input
+    pub(crate) fn input_timing(&self, delay_ms: i64) -> (u64, i64) {
+        let seek_ms = (self.start_ms as i64).saturating_sub(delay_ms).max(0);
+        let offset_ms = delay_ms - self.start_ms as i64 + seek_ms;
+        (seek_ms as u64, offset_ms)
+    }
+
+    /// Input option placed before `-i`
+    pub(crate) fn seek_args(&self) -> Vec<String> {
+        if self.start_ms == 0 {
+            return Vec::new();
+        }
+        vec!["-ss".to_string(), format_secs(self.start_ms)]
+    }
+
+    /// Output option placed before each output path
+    pub(crate) fn duration_args(&self) -> Vec<String> {
+        self.duration_ms()
+            .map(|ms| vec!["-t".to_string(), format_secs(ms)])
+            .unwrap_or_default()
+    }
+
+    /// Length of the clip taken from an input of `full_secs`
+    pub(crate) fn clipped_duration_secs(&self, full_secs: Option<f64>) -> Option<f64> {
+        let start = self.start_ms as f64 / 1000.0;
+        let end = match (self.end_ms, full_secs) {
+            (Some(end_ms), Some(full)) => (end_ms as f64 / 1000.0).min(full),
+            (Some(end_ms), None) => end_ms as f64 / 1000.0,
+            (None, Some(full)) => full,
+            (None, None) => return None,
+        };
+        Some((end - start).max(0.0))
+    }
+
+    /// Progress total for the clipped output, from the input duration when known
+    pub(crate) fn clipped_duration_us(&self, full_us: Option<u64>) -> Option<u64> {
+        self.clipped_duration_secs(full_us.map(|us| us as f64 / 1_000_000.0))
+            .map(|secs| (secs * 1_000_000.0).round() as u64)
+    }
+}
+
+/// Keyframe timestamps (ms) from `ffprobe -show_entries packet=pts_time,flags` output
+///
+/// Times are counted from `start_time_secs`, the file's start time, which is what ffmpeg's
+/// `-ss` is relative to.
+pub(crate) fn keyframes_from_packets(probe: &Value, start_time_secs: f64) -> Vec<u64> {
+    let mut keyframes: Vec<u64> = probe
+        .get("packets")
+        .and_then(|packets| packets.as_array())
+        .map(|packets| {
+            packets
+                .iter()
+                .filter(|packet| {
+                    packet
+                        .get("flags")
+                        .and_then(|flags| flags.as_str())
+                        .is_some_and(|flags| flags.starts_with('K'))
+                })
+                .filter_map(|packet| packet.get("pts_time")?.as_str()?.parse::<f64>().ok())
+                .map(|secs| secs - start_time_secs)
+                .filter(|secs| *secs >= 0.0)
+                .map(|secs| (secs * 1000.0).round() as u64)
+                .collect()
+        })
+        .unwrap_or_default();
+    keyframes.sort_unstable();
+    keyframes.dedup();
+    keyframes
+}
+
+/// Widen a range to the keyframe at or before its start and the first one at or after its end
+///
+/// A boundary with no keyframe on the right side of it keeps the requested time
+pub(crate) fn snap_to_keyframes(range: &ClipRange, keyframes: &[u64]) -> ClipCut {
+    let start_ms = keyframes
+        .iter()
+        .rev()
+        .find(|keyframe| **keyframe <= range.start_ms)
+        .copied()
+        .unwrap_or(range.start_ms);
+    let end_ms = range.end_ms.map(|end_ms| {
+        keyframes
+            .iter()
+            .find(|keyframe| **keyframe >= end_ms)
+            .copied()
+            .unwrap_or(end_ms)
+    });
+
+    ClipCut {
+        start_ms,
+        end_ms,
+        ..ClipCut::exact(range)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ClipCut, ClipMode, ClipRange, keyframes_from_packets, snap_to_keyframes};
+    use serde_json::json;
+
+    fn range(start_ms: u64, end_ms: Option<u64>) -> ClipRange {
+        ClipRange {
+            start_ms,
+            end_ms,
+            mode: ClipMode::Keyframe,
+        }
+    }
+
+    #[test]
+    fn snap_to_keyframes_widens_range_to_surrounding_keyframes() {
+        let probe = json!({
+            "packets": [
+                {"pts_time": "8.008000", "flags": "K__"},
+                {"pts_time": "8.050000", "flags": "___"},
+                {"pts_time": "12.012000", "flags": "K_"},
+                {"pts_time": "20.020000", "flags": "K_"},
+                {"pts_time": "N/A", "flags": "K_"}
+            ]
+        });
+        let keyframes = keyframes_from_packets(&probe, 0.0);
+        assert_eq!(keyframes, vec![8_008, 12_012, 20_020]);
+
+        let cut = snap_to_keyframes(&range(10_000, Some(15_000)), &keyframes);
+        assert_eq!((cut.start_ms, cut.end_ms), (8_008, Some(20_020)));
+        assert_eq!(
+            (cut.requested_start_ms, cut.requested_end_ms),
+            (10_000, Some(15_000))
+        );
+
+        // Exact keyframes stay put and missing keyframes keep the requested time
+        let cut = snap_to_keyframes(&range(12_012, Some(30_000)), &keyframes);
+        assert_eq!((cut.start_ms, cut.end_ms), (12_012, Some(30_000)));
+        let cut = snap_to_keyframes(&range(5_000, None), &keyframes);
+        assert_eq!((cut.start_ms, cut.end_ms), (5_000, None));
+    }
+
+    #[test]
+    fn keyframes_from_packets_are_relative_to_the_start_time() {
+        // MPEG-TS captures commonly start well after zero
+        let probe = json!({
+            "packets": [
+                {"pts_time": "1.400000", "flags": "K_"},
+                {"pts_time": "9.408000", "flags": "K_"},
+                {"pts_time": "13.412000", "flags": "K_"}
+            ]
+        });
+        let keyframes = keyframes_from_packets(&probe, 1.4);
+        assert_eq!(keyframes, vec![0, 8_008, 12_012]);
+
+        let cut = snap_to_keyframes(&range(10_000, Some(11_000)), &keyframes);
+        assert_eq!((cut.start_ms, cut.end_ms), (8_008, Some(12_012)));
+    }
+
+    #[test]
+    fn clip_cut_aligns_delayed_inputs_and_clipped_duration() {
+        let cut = ClipCut::exact(&range(10_000, Some(25_000)));
+
+        assert_eq!(cut.input_timing(0), (10_000, 0));
+        assert_eq!(cut.input_timing(2_000), (8_000, 0));
+        assert_eq!(cut.input_timing(-3_000), (13_000, 0));
+        assert_eq!(cut.input_timing(12_000), (0, 2_000));
+        assert_eq!(cut.seek_args(), vec!["-ss", "10.000"]);
+        assert_eq!(cut.duration_args(), vec!["-t", "15.000"]);
+        assert_eq!(cut.clipped_duration_secs(Some(20.0)), Some(10.0));
+        assert_eq!(cut.clipped_duration_us(None), Some(15_000_000));
+    }
+
+    #[test]
+    fn clip_range_rejects_end_before_start() {
+        assert!(range(5_000, Some(5_000)).validate().is_err());
+        assert!(range(5_000, Some(5_001)).validate().is_ok());
+        assert!(range(5_000, None).validate().is_ok());
+    }
+}
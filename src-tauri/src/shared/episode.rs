@@ -0,0 +1,510 @@
+use serde::Serialize;
+
+/// Resolutions that show up as bare numbers in release names
+const RESOLUTION_NUMBERS: [u32; 5] = [480, 576, 720, 1080, 2160];
+
+/// Longest run accepted for ranges such as `S01E01-E03` or `- 01-02`
+const MAX_EPISODE_RANGE: u32 = 10;
+
+/// Naming convention an episode number was read from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum EpisodePattern {
+    /// `S01E02`, `s1.e2`, `S01E01E02`
+    SeasonEpisode,
+    /// `1x02`
+    Cross,
+    /// `Season 1 Episode 2`, `Episode 12`, `Ep.12`, `E12`
+    Labeled,
+    /// `第12話`
+    Japanese,
+    /// Bare absolute number, as in `[Group] Show - 12 [1080p]`
+    Absolute,
+}
+
+/// Season and episode numbers read from a file name
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EpisodeInfo {
+    pub(crate) season: Option<u32>,
+    /// Sorted episode numbers; multi-episode files list each of them
+    pub(crate) episodes: Vec<u32>,
+    pub(crate) pattern: EpisodePattern,
+    /// Lowercase series title found before the episode marker
+    pub(crate) title: String,
+    pub(crate) release_group: Option<String>,
+}
+
+struct EpisodeMarker {
+    start: usize,
+    season: Option<u32>,
+    episodes: Vec<u32>,
+    pattern: EpisodePattern,
+}
+
+/// Strip the extension and the `.fre.track2` suffix the extractor appends
+fn analysis_name(file_name: &str) -> &str {
+    let name = file_name.rsplit(['/', '\\']).next().unwrap_or(file_name);
+    let name = match name.rsplit_once('.') {
+        Some((stem, ext))
+            if !stem.is_empty()
+                && (1..=4).contains(&ext.len())
+                && ext.chars().all(|c| c.is_ascii_alphanumeric()) =>
+        {
+            stem
+        }
+        _ => name,
+    };
+
+    let Some((stem, track)) = name.rsplit_once('.') else {
+        return name;
+    };
+    let is_track_suffix = track.len() > 5
+        && track[..5].eq_ignore_ascii_case("track")
+        && track[5..].chars().all(|c| c.is_ascii_digit());
+    if !is_track_suffix {
+        return name;
+    }
+    match stem.rsplit_once('.') {
+        Some((rest, language))
+            if (2..=8).contains(&language.len())
+                && language.chars().all(|c| c.is_ascii_alphabetic()) =>
+        {
+            rest
+        }
+        _ => stem,
+    }
+}
+
+fn is_word_start(chars: &[char], i: usize) -> bool {
+    i == 0 || !chars[i - 1].is_alphanumeric()
+}
+
+fn is_word_end(chars: &[char], i: usize) -> bool {
+    chars.get(i).is_none_or(|c| !c.is_alphanumeric())
+}
+
+fn is_separator(c: char) -> bool {
+    matches!(c, ' ' | '.' | '_' | '-')
+}
+
+/// Read a run of at most `max_digits` ASCII digits starting at `start`
+fn read_number(chars: &[char], start: usize, max_digits: usize) -> Option<(u32, usize)> {
+    let len = chars
+        .get(start..)?
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    if len == 0 || len > max_digits {
+        return None;
+    }
+    let value = chars[start..start + len].iter().collect::<String>();
+    Some((value.parse().ok()?, start + len))
+}
+
+/// Skip a release version suffix such as `v2`
+fn skip_version(chars: &[char], i: usize) -> usize {
+    if chars.get(i).is_some_and(|c| c.eq_ignore_ascii_case(&'v'))
+        && let Some((_, end)) = read_number(chars, i + 1, 2)
+    {
+        return end;
+    }
+    i
+}
+
+fn starts_with_ignore_case(chars: &[char], i: usize, word: &str) -> bool {
+    let len = word.chars().count();
+    chars.len() >= i + len
+        && chars[i..i + len]
+            .iter()
+            .zip(word.chars())
+            .all(|(a, b)| a.to_lowercase().eq(b.to_lowercase()))
+}
+
+/// Episodes from `first` to `last`, or `None` for a reversed or implausibly long range
+fn episode_range(first: u32, last: u32) -> Option<Vec<u32>> {
+    (last > first && last - first < MAX_EPISODE_RANGE).then(|| (first..=last).collect())
+}
+
+fn is_resolution_or_year(value: u32, digits: usize) -> bool {
+    RESOLUTION_NUMBERS.contains(&value) || (digits == 4 && (1900..=2099).contains(&value))
+}
+
+/// Read `E02`, `-E03` or `-03` continuations after a first episode number
+fn read_episode_continuation(
+    chars: &[char],
+    mut i: usize,
+    first: u32,
+    markers: &[char],
+) -> (Vec<u32>, usize) {
+    let mut episodes = vec![first];
+    loop {
+        let dashed = chars.get(i) == Some(&'-');
+        let mut j = if dashed { i + 1 } else { i };
+        let marked = chars
+            .get(j)
+            .is_some_and(|c| markers.iter().any(|m| c.eq_ignore_ascii_case(m)));
+        if marked {
+            j += 1;
+        }
+        if !dashed && !marked {
+            break;
+        }
+        let Some((episode, end)) = read_number(chars, j, 4) else {
+            break;
+        };
+        let end = skip_version(chars, end);
+        if !is_word_end(chars, end) && !matches!(chars.get(end), Some('-' | 'e' | 'E' | 'x')) {
+            break;
+        }
+        let last = *episodes.last().unwrap_or(&first);
+        if dashed {
+            match episode_range(last, episode) {
+                Some(range) => episodes.extend(range.into_iter().skip(1)),
+                None => break,
+            }
+        } else if episode > last {
+            episodes.push(episode);
+        } else {
+            break;
+        }
+        i = end;
+    }
+    (episodes, i)
+}
+
+/// `S01E02`, `S01.E02`, `S01xE02` and multi-episode `S01E01E02` / `S01E01-E03`
+fn find_season_episode(chars: &[char]) -> Option<EpisodeMarker> {
+    (0..chars.len()).find_map(|i| {
+        if !chars[i].eq_ignore_ascii_case(&'s') || !is_word_start(chars, i) {
+            return None;
+        }
+        let (season, mut j) = read_number(chars, i + 1, 4)?;
+        if chars.get(j).copied().is_some_and(is_separator) {
+            j += 1;
+        }
+        if !chars
+            .get(j)
+            .is_some_and(|c| matches!(c, 'e' | 'E' | 'x' | 'X'))
+        {
+            return None;
+        }
+        let (first, end) = read_number(chars, j + 1, 4)?;
+        let end = skip_version(chars, end);
+        let (episodes, end) = read_episode_continuation(chars, end, first, &['e']);
+        is_word_end(chars, end).then_some(EpisodeMarker {
+            start: i,
+            season: Some(season),
+            episodes,
+            pattern: EpisodePattern::SeasonEpisode,
+        })
+    })
+}
+
+/// `1x02` and `1x02x03` / `1x02-03`
+fn find_cross(chars: &[char]) -> Option<EpisodeMarker> {
+    (0..chars.len()).find_map(|i| {
+        if !chars[i].is_ascii_digit() || !is_word_start(chars, i) {
+            return None;
+        }
+        let (season, j) = read_number(chars, i, 2)?;
+        if !chars.get(j).is_some_and(|c| c.eq_ignore_ascii_case(&'x')) {
+            return None;
+        }
+        let (first, end) = read_number(chars, j + 1, 3)?;
+        let end = skip_version(chars, end);
+        let (episodes, end) = read_episode_continuation(chars, end, first, &['x']);
+        is_word_end(chars, end).then_some(EpisodeMarker {
+            start: i,
+            season: Some(season),
+            episodes,
+            pattern: EpisodePattern::Cross,
+        })
+    })
+}
+
+/// Number following a keyword such as `Episode`, `Ep.` or `Season`
+fn find_keyword_number(
+    chars: &[char],
+    from: usize,
+    keywords: &[&str],
+) -> Option<(usize, u32, usize)> {
+    (from..chars.len()).find_map(|i| {
+        if !is_word_start(chars, i) {
+            return None;
+        }
+        let keyword = keywords
+            .iter()
+            .find(|k| starts_with_ignore_case(chars, i, k))?;
+        let mut j = i + keyword.chars().count();
+        if chars.get(j).is_some_and(|c| c.is_alphabetic()) {
+            return None;
+        }
+        while chars.get(j).copied().is_some_and(is_separator) {
+            j += 1;
+        }
+        let (value, end) = read_number(chars, j, 4)?;
+        Some((i, value, end))
+    })
+}
+
+/// `Season 1 Episode 2`, `Episode 12`, `Ep.12-13`, `E12`
+fn find_labeled(chars: &[char]) -> Option<EpisodeMarker> {
+    let season = find_keyword_number(chars, 0, &["season"]);
+    let from = season.map_or(0, |(_, _, end)| end);
+    let (start, first, end) = find_keyword_number(chars, from, &["episode", "ep", "e"])?;
+    let end = skip_version(chars, end);
+
+    let (episodes, end) = match chars.get(end) {
+        Some('-' | '~') => match read_number(chars, end + 1, 4)
+            .and_then(|(last, last_end)| Some((episode_range(first, last)?, last_end)))
+        {
+            Some((range, last_end)) if is_word_end(chars, last_end) => (range, last_end),
+            _ => (vec![first], end),
+        },
+        _ => (vec![first], end),
+    };
+    if !is_word_end(chars, end) {
+        return None;
+    }
+
+    Some(EpisodeMarker {
+        start: season.map_or(start, |(season_start, _, _)| season_start),
+        season: season.map(|(_, value, _)| value),
+        episodes,
+        pattern: EpisodePattern::Labeled,
+    })
+}
+
+/// `第12話`
+fn find_japanese(chars: &[char]) -> Option<EpisodeMarker> {
+    (0..chars.len()).find_map(|i| {
+        if chars[i] != '第' {
+            return None;
+        }
+        let mut j = i + 1;
+        while chars.get(j) == Some(&' ') {
+            j += 1;
+        }
+        let (episode, mut end) = read_number(chars, j, 4)?;
+        while chars.get(end) == Some(&' ') {
+            end += 1;
+        }
+        (chars.get(end) == Some(&'話')).then_some(EpisodeMarker {
+            start: i,
+            season: None,
+            episodes: vec![episode],
+            pattern: EpisodePattern::Japanese,
+        })
+    })
+}
+
+/// Absolute number with an optional `-02` / `~02` range, rejecting resolutions and years
+fn read_absolute(chars: &[char], start: usize) -> Option<(Vec<u32>, usize)> {
+    let (first, end) = read_number(chars, start, 4)?;
+    if end - start < 2 || is_resolution_or_year(first, end - start) {
+        return None;
+    }
+    let end = skip_version(chars, end);
+    if matches!(chars.get(end), Some('-' | '~'))
+        && let Some((last, last_end)) = read_number(chars, end + 1, 4)
+        && let Some(range) = episode_range(first, last)
+    {
+        let last_end = skip_version(chars, last_end);
+        if is_word_end(chars, last_end) {
+            return Some((range, last_end));
+        }
+    }
+    Some((vec![first], end))
+}
+
+/// `Show - 01`, `Show [01]` or a trailing `Show 01 [1080p]`
+fn find_absolute(chars: &[char]) -> Option<EpisodeMarker> {
+    let absolute = |start: usize, episodes: Vec<u32>| EpisodeMarker {
+        start,
+        season: None,
+        episodes,
+        pattern: EpisodePattern::Absolute,
+    };
+
+    // Separator style: "Show - 01", "Show -01v2 [720p]", "Show _01_"
+    let separated = (1..chars.len()).find_map(|i| {
+        if !matches!(chars[i], '-' | '_') || !matches!(chars[i - 1], ' ' | '[' | '(') {
+            return None;
+        }
+        let mut j = i + 1;
+        while chars.get(j) == Some(&' ') {
+            j += 1;
+        }
+        let (episodes, end) = read_absolute(chars, j)?;
+        chars
+            .get(end)
+            .is_none_or(|c| matches!(c, ' ' | ']' | ')' | '.' | '_' | '['))
+            .then(|| absolute(i, episodes))
+    });
+    if separated.is_some() {
+        return separated;
+    }
+
+    // Bracket style used by fansub releases: "[Group] Show [01][1080p]"
+    let bracketed = (0..chars.len()).find_map(|i| {
+        if chars[i] != '[' {
+            return None;
+        }
+        let (episodes, end) = read_absolute(chars, i + 1)?;
+        (chars.get(end) == Some(&']')).then(|| absolute(i, episodes))
+    });
+    if bracketed.is_some() {
+        return bracketed;
+    }
+
+    // Trailing number once bracket groups are removed: "Show 01 [1080p]"
+    let mut end = chars.len();
+    loop {
+        while end > 0 && chars[end - 1] == ' ' {
+            end -= 1;
+        }
+        let close = match end.checked_sub(1).map(|i| chars[i]) {
+            Some(']') => '[',
+            Some(')') => '(',
+            _ => break,
+        };
+        match chars[..end].iter().rposition(|c| *c == close) {
+            Some(open) => end = open,
+            None => break,
+        }
+    }
+    let start = chars[..end]
+        .iter()
+        .rposition(|c| !c.is_ascii_digit())
+        .map_or(0, |i| i + 1);
+    if start == 0 || chars[start - 1] != ' ' {
+        return None;
+    }
+    let (episodes, number_end) = read_absolute(chars, start)?;
+    (number_end == end).then(|| absolute(start, episodes))
+}
+
+/// Leading `[Group]` tag of a release name
+fn release_group(chars: &[char]) -> Option<String> {
+    if chars.first() != Some(&'[') {
+        return None;
+    }
+    let close = chars.iter().position(|c| *c == ']')?;
+    let group: String = chars[1..close]
+        .iter()
+        .collect::<String>()
+        .trim()
+        .to_string();
+    (!group.is_empty()).then_some(group)
+}
+
+/// Lowercase words before the episode marker, without bracket groups or separators
+fn series_title(chars: &[char]) -> String {
+    let mut title = String::new();
+    let mut depth = 0usize;
+    for c in chars {
+        match c {
+            '[' | '(' => depth += 1,
+            ']' | ')' => depth = depth.saturating_sub(1),
+            c if depth == 0 => {
+                title.push(if matches!(c, '.' | '_' | '-') {
+                    ' '
+                } else {
+                    *c
+                });
+            }
+            _ => {}
+        }
+    }
+    title
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// Read season and episode numbers from a file name or path
+pub(crate) fn parse_episode(file_name: &str) -> Option<EpisodeInfo> {
+    let chars: Vec<char> = analysis_name(file_name).chars().collect();
+
+    let marker = find_season_episode(&chars)
+        .or_else(|| find_cross(&chars))
+        .or_else(|| find_labeled(&chars))
+        .or_else(|| find_japanese(&chars))
+        .or_else(|| find_absolute(&chars))?;
+
+    Some(EpisodeInfo {
+        season: marker.season,
+        episodes: marker.episodes,
+        pattern: marker.pattern,
+        title: series_title(&chars[..marker.start]),
+        release_group: release_group(&chars),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EpisodePattern, parse_episode};
+
+    fn parsed(name: &str) -> (Option<u32>, Vec<u32>, EpisodePattern) {
+        let info = parse_episode(name).unwrap_or_else(|| panic!("{} should parse", name));
+        (info.season, info.episodes, info.pattern)
+    }
+
+    #[test]
+    fn parse_episode_reads_season_episode_forms() {
+        use EpisodePattern::{Cross, Labeled, SeasonEpisode};
+
+        assert_eq!(
+            parsed("Show.Name.S01E02.1080p.WEB.mkv"),
+            (Some(1), vec![2], SeasonEpisode)
+        );
+        assert_eq!(
+            parsed("show s2.e10 final.ass"),
+            (Some(2), vec![10], SeasonEpisode)
+        );
+        assert_eq!(parsed("Show - 1x02 - Title.srt"), (Some(1), vec![2], Cross));
+        assert_eq!(
+            parsed("Show Season 3 Episode 4.mkv"),
+            (Some(3), vec![4], Labeled)
+        );
+        assert_eq!(parsed("Show Episode 12.mkv"), (None, vec![12], Labeled));
+        assert_eq!(parsed("Show EP11_Title.mkv"), (None, vec![11], Labeled));
+    }
+
+    #[test]
+    fn parse_episode_reads_multi_episode_files() {
+        assert_eq!(parsed("Show.S01E01E02.mkv").1, vec![1, 2]);
+        assert_eq!(parsed("Show.S01E01-E03.mkv").1, vec![1, 2, 3]);
+        assert_eq!(parsed("Show 1x05-06.mkv").1, vec![5, 6]);
+        assert_eq!(parsed("Show Episode 12-13.mkv").1, vec![12, 13]);
+        assert_eq!(parsed("[Group] Show - 24-25 [1080p].mkv").1, vec![24, 25]);
+    }
+
+    #[test]
+    fn parse_episode_reads_absolute_anime_numbering() {
+        let info = parse_episode("[SubsPlease] Sousou no Frieren - 12v2 (1080p) [ABCD1234].mkv")
+            .expect("absolute episode should parse");
+
+        assert_eq!(info.season, None);
+        assert_eq!(info.episodes, vec![12]);
+        assert_eq!(info.pattern, EpisodePattern::Absolute);
+        assert_eq!(info.title, "sousou no frieren");
+        assert_eq!(info.release_group.as_deref(), Some("SubsPlease"));
+
+        assert_eq!(parsed("[Group] Show [07][1080p].mkv").1, vec![7]);
+        assert_eq!(parsed("High School DxD 01 [BD].mkv").1, vec![1]);
+        assert_eq!(parsed("One Piece - 1071.mkv").1, vec![1071]);
+        assert_eq!(parsed("Show 第05話.mkv").1, vec![5]);
+    }
+
+    #[test]
+    fn parse_episode_ignores_resolutions_years_and_track_suffixes() {
+        assert_eq!(parse_episode("Movie (2019) [1080p].mkv"), None);
+        assert_eq!(parse_episode("Some Movie 1920x1080.mkv"), None);
+        assert_eq!(parse_episode("Movie - 720.mkv"), None);
+        assert_eq!(parsed("Show 02.fre.track2.ass").1, vec![2]);
+        assert_eq!(parsed("/media/Show/Show.S01E03.track1.srt").1, vec![3]);
+    }
+}
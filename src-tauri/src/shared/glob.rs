@@ -0,0 +1,62 @@
+/// Match a path against a glob, ignoring case
+///
+/// `*` and `?` stay within one path component and `**` spans any number of them. A pattern
+/// without `/` is matched against the file name only, so `*.mkv` works at any depth.
+/// `path` uses `/` separators.
+pub(crate) fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<char> = pattern.to_lowercase().chars().collect();
+    let target = if pattern.contains(&'/') {
+        path
+    } else {
+        path.rsplit('/').next().unwrap_or(path)
+    };
+    let target: Vec<char> = target.to_lowercase().chars().collect();
+    matches(&pattern, &target)
+}
+
+fn matches(pattern: &[char], path: &[char]) -> bool {
+    match pattern {
+        [] => path.is_empty(),
+        ['*', '*', rest @ ..] => {
+            // `**/` also matches no directory at all
+            let without_slash = rest.strip_prefix(&['/']).unwrap_or(rest);
+            matches(without_slash, path) || (0..=path.len()).any(|i| matches(rest, &path[i..]))
+        }
+        ['*', rest @ ..] => {
+            for i in 0..=path.len() {
+                if matches(rest, &path[i..]) {
+                    return true;
+                }
+                if path.get(i) == Some(&'/') {
+                    break;
+                }
+            }
+            false
+        }
+        ['?', rest @ ..] => path.first().is_some_and(|c| *c != '/') && matches(rest, &path[1..]),
+        [c, rest @ ..] => path.first() == Some(c) && matches(rest, &path[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::glob_matches;
+
+    #[test]
+    fn glob_matches_file_names_at_any_depth_without_slash() {
+        assert!(glob_matches("*.mkv", "Season 1/Show.S01E01.MKV"));
+        assert!(glob_matches("*sample*", "extras/Show-Sample.mp4"));
+        assert!(glob_matches("E0?.mkv", "E01.mkv"));
+        assert!(!glob_matches("*.mkv", "Show.S01E01.mp4"));
+    }
+
+    #[test]
+    fn glob_matches_paths_with_single_and_double_stars() {
+        assert!(glob_matches("Season */*.mkv", "Season 1/E01.mkv"));
+        assert!(!glob_matches("Season */*.mkv", "Season 1/extras/E01.mkv"));
+        assert!(glob_matches("**/extras/**", "Season 1/extras/bts.mkv"));
+        assert!(glob_matches("**/extras/**", "extras/bts.mkv"));
+        assert!(glob_matches("Season 1/**/*.mkv", "Season 1/E01.mkv"));
+        assert!(!glob_matches("Season ?/*.mkv", "Season 10/E01.mkv"));
+    }
+}
@@ -0,0 +1,269 @@
+/// ISO 639-1 codes with their ISO 639-2/B and 639-2/T equivalents
+const ISO639_CODES: &[(&str, &str, &str)] = &[
+    ("aa", "aar", "aar"),
+    ("ab", "abk", "abk"),
+    ("ae", "ave", "ave"),
+    ("af", "afr", "afr"),
+    ("ak", "aka", "aka"),
+    ("am", "amh", "amh"),
+    ("an", "arg", "arg"),
+    ("ar", "ara", "ara"),
+    ("as", "asm", "asm"),
+    ("av", "ava", "ava"),
+    ("ay", "aym", "aym"),
+    ("az", "aze", "aze"),
+    ("ba", "bak", "bak"),
+    ("be", "bel", "bel"),
+    ("bg", "bul", "bul"),
+    ("bi", "bis", "bis"),
+    ("bm", "bam", "bam"),
+    ("bn", "ben", "ben"),
+    ("bo", "tib", "bod"),
+    ("br", "bre", "bre"),
+    ("bs", "bos", "bos"),
+    ("ca", "cat", "cat"),
+    ("ce", "che", "che"),
+    ("ch", "cha", "cha"),
+    ("co", "cos", "cos"),
+    ("cr", "cre", "cre"),
+    ("cs", "cze", "ces"),
+    ("cu", "chu", "chu"),
+    ("cv", "chv", "chv"),
+    ("cy", "wel", "cym"),
+    ("da", "dan", "dan"),
+    ("de", "ger", "deu"),
+    ("dv", "div", "div"),
+    ("dz", "dzo", "dzo"),
+    ("ee", "ewe", "ewe"),
+    ("el", "gre", "ell"),
+    ("en", "eng", "eng"),
+    ("eo", "epo", "epo"),
+    ("es", "spa", "spa"),
+    ("et", "est", "est"),
+    ("eu", "baq", "eus"),
+    ("fa", "per", "fas"),
+    ("ff", "ful", "ful"),
+    ("fi", "fin", "fin"),
+    ("fj", "fij", "fij"),
+    ("fo", "fao", "fao"),
+    ("fr", "fre", "fra"),
+    ("fy", "fry", "fry"),
+    ("ga", "gle", "gle"),
+    ("gd", "gla", "gla"),
+    ("gl", "glg", "glg"),
+    ("gn", "grn", "grn"),
+    ("gu", "guj", "guj"),
+    ("gv", "glv", "glv"),
+    ("ha", "hau", "hau"),
+    ("he", "heb", "heb"),
+    ("hi", "hin", "hin"),
+    ("ho", "hmo", "hmo"),
+    ("hr", "hrv", "hrv"),
+    ("ht", "hat", "hat"),
+    ("hu", "hun", "hun"),
+    ("hy", "arm", "hye"),
+    ("hz", "her", "her"),
+    ("ia", "ina", "ina"),
+    ("id", "ind", "ind"),
+    ("ie", "ile", "ile"),
+    ("ig", "ibo", "ibo"),
+    ("ii", "iii", "iii"),
+    ("ik", "ipk", "ipk"),
+    ("io", "ido", "ido"),
+    ("is", "ice", "isl"),
+    ("it", "ita", "ita"),
+    ("iu", "iku", "iku"),
+    ("ja", "jpn", "jpn"),
+    ("jv", "jav", "jav"),
+    ("ka", "geo", "kat"),
+    ("kg", "kon", "kon"),
+    ("ki", "kik", "kik"),
+    ("kj", "kua", "kua"),
+    ("kk", "kaz", "kaz"),
+    ("kl", "kal", "kal"),
+    ("km", "khm", "khm"),
+    ("kn", "kan", "kan"),
+    ("ko", "kor", "kor"),
+    ("kr", "kau", "kau"),
+    ("ks", "kas", "kas"),
+    ("ku", "kur", "kur"),
+    ("kv", "kom", "kom"),
+    ("kw", "cor", "cor"),
+    ("ky", "kir", "kir"),
+    ("la", "lat", "lat"),
+    ("lb", "ltz", "ltz"),
+    ("lg", "lug", "lug"),
+    ("li", "lim", "lim"),
+    ("ln", "lin", "lin"),
+    ("lo", "lao", "lao"),
+    ("lt", "lit", "lit"),
+    ("lu", "lub", "lub"),
+    ("lv", "lav", "lav"),
+    ("mg", "mlg", "mlg"),
+    ("mh", "mah", "mah"),
+    ("mi", "mao", "mri"),
+    ("mk", "mac", "mkd"),
+    ("ml", "mal", "mal"),
+    ("mn", "mon", "mon"),
+    ("mr", "mar", "mar"),
+    ("ms", "may", "msa"),
+    ("mt", "mlt", "mlt"),
+    ("my", "bur", "mya"),
+    ("na", "nau", "nau"),
+    ("nb", "nob", "nob"),
+    ("nd", "nde", "nde"),
+    ("ne", "nep", "nep"),
+    ("ng", "ndo", "ndo"),
+    ("nl", "dut", "nld"),
+    ("nn", "nno", "nno"),
+    ("no", "nor", "nor"),
+    ("nr", "nbl", "nbl"),
+    ("nv", "nav", "nav"),
+    ("ny", "nya", "nya"),
+    ("oc", "oci", "oci"),
+    ("oj", "oji", "oji"),
+    ("om", "orm", "orm"),
+    ("or", "ori", "ori"),
+    ("os", "oss", "oss"),
+    ("pa", "pan", "pan"),
+    ("pi", "pli", "pli"),
+    ("pl", "pol", "pol"),
+    ("ps", "pus", "pus"),
+    ("pt", "por", "por"),
+    ("qu", "que", "que"),
+    ("rm", "roh", "roh"),
+    ("rn", "run", "run"),
+    ("ro", "rum", "ron"),
+    ("ru", "rus", "rus"),
+    ("rw", "kin", "kin"),
+    ("sa", "san", "san"),
+    ("sc", "srd", "srd"),
+    ("sd", "snd", "snd"),
+    ("se", "sme", "sme"),
+    ("sg", "sag", "sag"),
+    ("si", "sin", "sin"),
+    ("sk", "slo", "slk"),
+    ("sl", "slv", "slv"),
+    ("sm", "smo", "smo"),
+    ("sn", "sna", "sna"),
+    ("so", "som", "som"),
+    ("sq", "alb", "sqi"),
+    ("sr", "srp", "srp"),
+    ("ss", "ssw", "ssw"),
+    ("st", "sot", "sot"),
+    ("su", "sun", "sun"),
+    ("sv", "swe", "swe"),
+    ("sw", "swa", "swa"),
+    ("ta", "tam", "tam"),
+    ("te", "tel", "tel"),
+    ("tg", "tgk", "tgk"),
+    ("th", "tha", "tha"),
+    ("ti", "tir", "tir"),
+    ("tk", "tuk", "tuk"),
+    ("tl", "tgl", "tgl"),
+    ("tn", "tsn", "tsn"),
+    ("to", "ton", "ton"),
+    ("tr", "tur", "tur"),
+    ("ts", "tso", "tso"),
+    ("tt", "tat", "tat"),
+    ("tw", "twi", "twi"),
+    ("ty", "tah", "tah"),
+    ("ug", "uig", "uig"),
+    ("uk", "ukr", "ukr"),
+    ("ur", "urd", "urd"),
+    ("uz", "uzb", "uzb"),
+    ("ve", "ven", "ven"),
+    ("vi", "vie", "vie"),
+    ("vo", "vol", "vol"),
+    ("wa", "wln", "wln"),
+    ("wo", "wol", "wol"),
+    ("xh", "xho", "xho"),
+    ("yi", "yid", "yid"),
+    ("yo", "yor", "yor"),
+    ("za", "zha", "zha"),
+    ("zh", "chi", "zho"),
+    ("zu", "zul", "zul"),
+];
+
+/// ISO 639-2 codes for undetermined, multiple, no linguistic content and uncoded languages
+const SPECIAL_ISO639_CODES: &[&str] = &["und", "mul", "zxx", "mis"];
+
+/// Three-letter ISO 639-2 code for a language tag, as written to container metadata
+///
+/// Accepts ISO 639-2/B and 639-2/T codes in any case, ISO 639-1 codes (mapped to their
+/// 639-2/B form) and BCP 47 tags such as "en-US", which keep their primary subtag. `None`
+/// when the tag is not a known language code.
+pub(crate) fn normalize_language_code(code: &str) -> Option<String> {
+    let code = code.trim().to_ascii_lowercase();
+    let primary = code.split(['-', '_']).next().unwrap_or_default();
+    if !primary.chars().all(|c| c.is_ascii_lowercase()) {
+        return None;
+    }
+
+    match primary.len() {
+        3 => (SPECIAL_ISO639_CODES.contains(&primary)
+            || ISO639_CODES
+                .iter()
+                .any(|(_, iso639_2b, iso639_2t)| *iso639_2b == primary || *iso639_2t == primary))
+        .then(|| primary.to_string()),
+        2 => ISO639_CODES
+            .iter()
+            .find(|(iso639_1, _, _)| *iso639_1 == primary)
+            .map(|(_, iso639_2b, _)| iso639_2b.to_string()),
+        _ => None,
+    }
+}
+
+/// Two-letter ISO 639-1 code for an ISO 639-2/B or 639-2/T code
+pub(crate) fn iso639_1_code(code: &str) -> Option<&'static str> {
+    ISO639_CODES
+        .iter()
+        .find(|(_, iso639_2b, iso639_2t)| {
+            iso639_2b.eq_ignore_ascii_case(code) || iso639_2t.eq_ignore_ascii_case(code)
+        })
+        .map(|(iso639_1, _, _)| *iso639_1)
+}
+
+/// Whether two language tags name the same language, e.g. "en", "ENG" and "en-GB"
+pub(crate) fn same_language(a: &str, b: &str) -> bool {
+    match (normalize_language_code(a), normalize_language_code(b)) {
+        (Some(a), Some(b)) => {
+            a == b || iso639_1_code(&a).is_some_and(|a| iso639_1_code(&b) == Some(a))
+        }
+        _ => a.eq_ignore_ascii_case(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{iso639_1_code, normalize_language_code, same_language};
+
+    #[test]
+    fn normalize_language_code_accepts_probed_tags() {
+        assert_eq!(normalize_language_code("eng").as_deref(), Some("eng"));
+        assert_eq!(normalize_language_code("ENG").as_deref(), Some("eng"));
+        assert_eq!(normalize_language_code("und").as_deref(), Some("und"));
+        assert_eq!(normalize_language_code("en").as_deref(), Some("eng"));
+        assert_eq!(normalize_language_code("fr").as_deref(), Some("fre"));
+        assert_eq!(normalize_language_code("pt-BR").as_deref(), Some("por"));
+        assert_eq!(normalize_language_code(" deu ").as_deref(), Some("deu"));
+        assert_eq!(normalize_language_code("english"), None);
+        assert_eq!(normalize_language_code("e1"), None);
+        assert_eq!(normalize_language_code("xx"), None);
+        assert_eq!(normalize_language_code("zzz"), None);
+        assert_eq!(normalize_language_code("abc"), None);
+        assert_eq!(normalize_language_code("fra").as_deref(), Some("fra"));
+        assert_eq!(normalize_language_code("mul").as_deref(), Some("mul"));
+        assert_eq!(normalize_language_code(""), None);
+    }
+
+    #[test]
+    fn iso639_1_code_maps_both_639_2_forms() {
+        assert_eq!(iso639_1_code("fre"), Some("fr"));
+        assert_eq!(iso639_1_code("FRA"), Some("fr"));
+        assert_eq!(iso639_1_code("und"), None);
+        assert!(same_language("en-GB", "ENG"));
+        assert!(same_language("ger", "deu"));
+        assert!(!same_language("eng", "fre"));
+    }
+}
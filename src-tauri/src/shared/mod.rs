@@ -1,6 +1,12 @@
+pub(crate) mod chapters;
+pub(crate) mod clip;
 pub(crate) mod copy_progress;
+pub(crate) mod episode;
 pub(crate) mod ffmpeg_progress;
+pub(crate) mod glob;
 pub(crate) mod hash;
+pub(crate) mod language;
+pub(crate) mod output;
 pub(crate) mod process;
 pub(crate) mod sleep_inhibit;
 pub(crate) mod store;
@@ -0,0 +1,243 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Highest numeric suffix tried before giving up on auto-suffix
+const MAX_AUTO_SUFFIX: u32 = 9999;
+
+static TEMP_OUTPUT_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// What to do when the requested output path already exists
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum OverwritePolicy {
+    /// Refuse to run
+    Fail,
+    /// Replace the existing file once the new one is complete
+    #[default]
+    Overwrite,
+    /// Write to "name (1).ext", "name (2).ext", ... instead
+    AutoSuffix,
+}
+
+fn suffixed_path(path: &Path, n: u32) -> PathBuf {
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let file_name = match path.extension() {
+        Some(ext) => format!("{} ({}).{}", stem, n, ext.to_string_lossy()),
+        None => format!("{} ({})", stem, n),
+    };
+    path.with_file_name(file_name)
+}
+
+/// Resolve the path the finished output will be renamed to
+pub(crate) fn resolve_output_path(path: &str, policy: OverwritePolicy) -> Result<String, String> {
+    let requested = Path::new(path);
+    if !requested.exists() {
+        return Ok(path.to_string());
+    }
+    if requested.is_dir() {
+        return Err(format!("Output path is a directory: {}", path));
+    }
+
+    match policy {
+        OverwritePolicy::Overwrite => Ok(path.to_string()),
+        OverwritePolicy::Fail => Err(format!("Output file already exists: {}", path)),
+        OverwritePolicy::AutoSuffix => (1..=MAX_AUTO_SUFFIX)
+            .map(|n| suffixed_path(requested, n))
+            .find(|candidate| !candidate.exists())
+            .map(|candidate| candidate.to_string_lossy().to_string())
+            .ok_or_else(|| format!("No free output name found for: {}", path)),
+    }
+}
+
+/// Resolve outputs written together, such as an .idx/.sub pair, so they share one suffix
+pub(crate) fn resolve_output_paths(
+    paths: &[&str],
+    policy: OverwritePolicy,
+) -> Result<Vec<String>, String> {
+    let requested: Vec<&Path> = paths.iter().map(Path::new).collect();
+    let Some(existing) = requested.iter().find(|path| path.exists()) else {
+        return Ok(paths.iter().map(|path| path.to_string()).collect());
+    };
+    if let Some(dir) = requested.iter().find(|path| path.is_dir()) {
+        return Err(format!("Output path is a directory: {}", dir.display()));
+    }
+
+    match policy {
+        OverwritePolicy::Overwrite => Ok(paths.iter().map(|path| path.to_string()).collect()),
+        OverwritePolicy::Fail => Err(format!(
+            "Output file already exists: {}",
+            existing.display()
+        )),
+        OverwritePolicy::AutoSuffix => (1..=MAX_AUTO_SUFFIX)
+            .find(|n| {
+                requested
+                    .iter()
+                    .all(|path| !suffixed_path(path, *n).exists())
+            })
+            .map(|n| {
+                requested
+                    .iter()
+                    .map(|path| suffixed_path(path, n).to_string_lossy().to_string())
+                    .collect()
+            })
+            .ok_or_else(|| format!("No free output name found for: {}", paths.join(", "))),
+    }
+}
+
+/// Hidden sibling of the final path that keeps its extension for ffmpeg format detection
+fn temp_output_path(final_path: &str) -> String {
+    let path = Path::new(final_path);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let token = format!(
+        "{}-{}",
+        std::process::id(),
+        TEMP_OUTPUT_COUNTER.fetch_add(1, Ordering::Relaxed)
+    );
+    let file_name = match path.extension() {
+        Some(ext) => format!(".{}.{}.part.{}", stem, token, ext.to_string_lossy()),
+        None => format!(".{}.{}.part", stem, token),
+    };
+    path.with_file_name(file_name).to_string_lossy().to_string()
+}
+
+/// An output written to a temp file and renamed into place on success
+#[derive(Debug, Clone)]
+pub(crate) struct StagedOutput {
+    final_path: String,
+    temp_path: String,
+    policy: OverwritePolicy,
+}
+
+impl StagedOutput {
+    pub(crate) fn prepare(path: &str, policy: OverwritePolicy) -> Result<Self, String> {
+        let final_path = resolve_output_path(path, policy)?;
+        let temp_path = temp_output_path(&final_path);
+        Ok(Self {
+            final_path,
+            temp_path,
+            policy,
+        })
+    }
+
+    pub(crate) fn final_path(&self) -> &str {
+        &self.final_path
+    }
+
+    /// Path the producing process should write to
+    pub(crate) fn temp_path(&self) -> &str {
+        &self.temp_path
+    }
+
+    /// Move the finished temp file onto the final path
+    pub(crate) fn commit(&self) -> Result<String, String> {
+        if !Path::new(&self.temp_path).is_file() {
+            return Err(format!("Output file was not created: {}", self.final_path));
+        }
+        // Another job may have claimed the name while this one was running
+        if self.policy != OverwritePolicy::Overwrite && Path::new(&self.final_path).exists() {
+            self.discard();
+            return Err(format!("Output file already exists: {}", self.final_path));
+        }
+
+        std::fs::rename(&self.temp_path, &self.final_path).map_err(|e| {
+            self.discard();
+            format!("Failed to move output into place: {}", e)
+        })?;
+        Ok(self.final_path.clone())
+    }
+
+    /// Remove the temp file, leaving any existing final file untouched
+    pub(crate) fn discard(&self) {
+        let _ = std::fs::remove_file(&self.temp_path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{OverwritePolicy, StagedOutput, resolve_output_path, resolve_output_paths};
+
+    #[test]
+    fn resolve_output_path_applies_policy_to_existing_file() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let existing = dir.path().join("movie.mkv");
+        std::fs::write(&existing, b"good").expect("failed to create existing output");
+        std::fs::write(dir.path().join("movie (1).mkv"), b"taken")
+            .expect("failed to create suffixed output");
+        let path = existing.to_string_lossy().to_string();
+
+        assert_eq!(
+            resolve_output_path(&path, OverwritePolicy::Overwrite).as_deref(),
+            Ok(path.as_str())
+        );
+        let error = resolve_output_path(&path, OverwritePolicy::Fail)
+            .expect_err("existing output should fail");
+        assert!(error.contains("already exists"));
+        let suffixed = resolve_output_path(&path, OverwritePolicy::AutoSuffix)
+            .expect("auto suffix should find a free name");
+        assert!(suffixed.ends_with("movie (2).mkv"));
+    }
+
+    #[test]
+    fn resolve_output_paths_suffixes_every_path_of_a_group() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let idx = dir.path().join("subs.idx").to_string_lossy().to_string();
+        let sub = dir.path().join("subs.sub").to_string_lossy().to_string();
+        std::fs::write(&sub, b"taken").expect("failed to create existing output");
+        std::fs::write(dir.path().join("subs (1).idx"), b"taken")
+            .expect("failed to create suffixed output");
+
+        let resolved = resolve_output_paths(&[&idx, &sub], OverwritePolicy::AutoSuffix)
+            .expect("auto suffix should find a free pair");
+        assert!(resolved[0].ends_with("subs (2).idx"));
+        assert!(resolved[1].ends_with("subs (2).sub"));
+        assert!(resolve_output_paths(&[&idx, &sub], OverwritePolicy::Fail).is_err());
+        assert_eq!(
+            resolve_output_paths(&[&idx, &sub], OverwritePolicy::Overwrite),
+            Ok(vec![idx.clone(), sub.clone()])
+        );
+    }
+
+    #[test]
+    fn staged_output_keeps_existing_file_until_commit() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let output = dir.path().join("movie.mkv");
+        std::fs::write(&output, b"good").expect("failed to create existing output");
+
+        let staged = StagedOutput::prepare(
+            output.to_string_lossy().as_ref(),
+            OverwritePolicy::Overwrite,
+        )
+        .expect("staging should succeed");
+        assert!(staged.temp_path().ends_with(".part.mkv"));
+        std::fs::write(staged.temp_path(), b"partial").expect("failed to write temp output");
+
+        staged.discard();
+        assert_eq!(std::fs::read(&output).expect("read output"), b"good");
+
+        std::fs::write(staged.temp_path(), b"new").expect("failed to write temp output");
+        let committed = staged.commit().expect("commit should succeed");
+        assert_eq!(committed, output.to_string_lossy());
+        assert_eq!(std::fs::read(&output).expect("read output"), b"new");
+        assert!(!std::path::Path::new(staged.temp_path()).exists());
+    }
+
+    #[test]
+    fn staged_output_commit_fails_when_nothing_was_written() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let output = dir.path().join("out.srt");
+
+        let staged =
+            StagedOutput::prepare(output.to_string_lossy().as_ref(), OverwritePolicy::Fail)
+                .expect("staging should succeed");
+
+        assert!(staged.commit().is_err());
+        assert!(!output.exists());
+    }
+}
@@ -8,6 +8,9 @@ pub(crate) const SETTINGS_STORE_FILE: &str = "settings.json";
 pub(crate) const FFMPEG_PATH_KEY: &str = "ffmpegPath";
 pub(crate) const FFPROBE_PATH_KEY: &str = "ffprobePath";
 
+/// Store key enabling the on-disk probe cache
+pub(crate) const PROBE_CACHE_PERSIST_KEY: &str = "probeCachePersist";
+
 fn read_store_path(app: &tauri::AppHandle, key: &str) -> Result<Option<String>, String> {
     let store = app
         .store(SETTINGS_STORE_FILE)
@@ -57,6 +60,15 @@ fn resolve_binary_path_from_custom(
     Ok(path.to_string_lossy().to_string())
 }
 
+/// Whether probe results should also be kept on disk across launches
+pub(crate) fn probe_cache_persist_enabled(app: &tauri::AppHandle) -> bool {
+    app.store(SETTINGS_STORE_FILE)
+        .ok()
+        .and_then(|store| store.get(PROBE_CACHE_PERSIST_KEY))
+        .and_then(|value| value.as_bool())
+        .unwrap_or(false)
+}
+
 pub(crate) fn resolve_ffmpeg_path(app: &tauri::AppHandle) -> Result<String, String> {
     resolve_binary_path(app, FFMPEG_PATH_KEY, "ffmpeg", "FFmpeg")
 }
@@ -3,9 +3,18 @@ use std::path::Path;
 /// Allowed media file extensions
 pub(crate) const ALLOWED_MEDIA_EXTENSIONS: &[&str] = &[
     "mkv", "mp4", "avi", "mov", "webm", "m4v", "mks", "mka", "m4a", "mp3", "flac", "wav", "ogg",
-    "aac", "ac3", "dts", "srt", "ass", "ssa", "vtt", "sub", "sup", "opus", "wma",
+    "aac", "ac3", "dts", "srt", "ass", "ssa", "vtt", "sub", "sup", "opus", "wma", "ts", "m2ts",
+    "idx",
 ];
 
+/// Digits with an optional k/M suffix, e.g. "192k"
+pub(crate) fn is_valid_bitrate(bitrate: &str) -> bool {
+    let digits = bitrate.trim_end_matches(['k', 'K', 'M']);
+    !digits.is_empty()
+        && digits.len() + 1 >= bitrate.len()
+        && digits.chars().all(|c| c.is_ascii_digit())
+}
+
 /// Validate that a path exists and is a file with an allowed extension
 pub(crate) fn validate_media_path(path: &str) -> Result<(), String> {
     let path = Path::new(path);
@@ -21,19 +30,25 @@ pub(crate) fn validate_media_path(path: &str) -> Result<(), String> {
     }
 
     // Check extension
-    let ext = path
-        .extension()
-        .and_then(|e| e.to_str())
-        .unwrap_or("")
-        .to_lowercase();
-
-    if !ALLOWED_MEDIA_EXTENSIONS.contains(&ext.as_str()) {
-        return Err(format!("Unsupported file type: .{}", ext));
+    if !has_media_extension(path) {
+        return Err(format!("Unsupported file type: .{}", media_extension(path)));
     }
 
     Ok(())
 }
 
+fn media_extension(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+/// Whether a path has one of `ALLOWED_MEDIA_EXTENSIONS`, in any case
+pub(crate) fn has_media_extension(path: &Path) -> bool {
+    ALLOWED_MEDIA_EXTENSIONS.contains(&media_extension(path).as_str())
+}
+
 /// Validate that a path is safe (no path traversal) and parent directory exists
 pub(crate) fn validate_output_path(path: &str) -> Result<(), String> {
     let path = Path::new(path);
@@ -110,6 +125,28 @@ mod tests {
             .expect("webm media path should be valid");
     }
 
+    #[test]
+    fn validate_media_path_accepts_transport_streams() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let file = dir.path().join("capture.ts");
+        std::fs::write(&file, b"data").expect("failed to create media file");
+
+        validate_media_path(file.to_string_lossy().as_ref())
+            .expect("transport stream path should be valid");
+    }
+
+    #[test]
+    fn validate_media_path_accepts_vobsub_pair() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        for name in ["movie.idx", "movie.sub"] {
+            let file = dir.path().join(name);
+            std::fs::write(&file, b"data").expect("failed to create media file");
+
+            validate_media_path(file.to_string_lossy().as_ref())
+                .expect("vobsub path should be valid");
+        }
+    }
+
     #[test]
     fn validate_media_path_rejects_unsupported_extension() {
         let dir = tempfile::tempdir().expect("failed to create tempdir");
@@ -0,0 +1,283 @@
+use crate::shared::output::{OverwritePolicy, StagedOutput};
+use crate::shared::process::terminate_process;
+use crate::shared::store::{resolve_ffmpeg_path, resolve_ffprobe_path};
+use crate::shared::validation::{validate_directory_path, validate_media_path};
+use crate::tools::ffmpeg::extract::{FFMPEG_EXTRACT_TIMEOUT, clear_extract_registration};
+use crate::tools::ffprobe::media_info::MediaAttachment;
+use crate::tools::ffprobe::probe::probe_file_with_ffprobe;
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::Stdio;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+/// File name for an attachment, keeping only the last component of the stored name
+fn attachment_file_name(attachment: &MediaAttachment) -> String {
+    attachment
+        .filename
+        .as_deref()
+        .and_then(|name| name.rsplit(['/', '\\']).next())
+        .map(str::trim)
+        .filter(|name| !name.is_empty() && *name != "." && *name != "..")
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("attachment_{}.bin", attachment.index))
+}
+
+/// Output file names, prefixed with the stream index when two attachments share a name
+fn unique_file_names(attachments: &[MediaAttachment]) -> Vec<String> {
+    let names: Vec<String> = attachments.iter().map(attachment_file_name).collect();
+    let mut seen = HashSet::new();
+    let duplicated: HashSet<&str> = names
+        .iter()
+        .filter(|name| !seen.insert(name.to_lowercase()))
+        .map(String::as_str)
+        .collect();
+
+    attachments
+        .iter()
+        .zip(&names)
+        .map(|(attachment, name)| {
+            if duplicated.iter().any(|dup| dup.eq_ignore_ascii_case(name)) {
+                format!("{}_{}", attachment.index, name)
+            } else {
+                name.clone()
+            }
+        })
+        .collect()
+}
+
+/// Dump attachments before opening the input; the null output keeps ffmpeg from
+/// failing with "at least one output file must be specified"
+fn build_dump_attachment_args(input_path: &str, outputs: &[(usize, &str)]) -> Vec<String> {
+    let mut args = vec!["-y".to_string()];
+    for (index, output_path) in outputs {
+        args.push(format!("-dump_attachment:{}", index));
+        args.push(output_path.to_string());
+    }
+    args.extend(
+        ["-i", input_path, "-t", "0", "-f", "null", "-"]
+            .iter()
+            .map(|arg| arg.to_string()),
+    );
+    args
+}
+
+/// Attachments to extract: every one when `indices` is None, otherwise the listed streams
+fn select_attachments(
+    attachments: Vec<MediaAttachment>,
+    indices: Option<&[usize]>,
+) -> Result<Vec<MediaAttachment>, String> {
+    let Some(indices) = indices else {
+        return Ok(attachments);
+    };
+
+    if let Some(missing) = indices
+        .iter()
+        .find(|index| !attachments.iter().any(|a| a.index == **index))
+    {
+        return Err(format!("Stream {} is not an attachment", missing));
+    }
+    Ok(attachments
+        .into_iter()
+        .filter(|a| indices.contains(&a.index))
+        .collect())
+}
+
+fn discard_all(staged: &[StagedOutput]) {
+    for output in staged {
+        output.discard();
+    }
+}
+
+pub(super) async fn extract_attachments_with_bins(
+    ffprobe_path: &str,
+    ffmpeg_path: &str,
+    input_path: &str,
+    output_dir: &str,
+    attachment_indices: Option<&[usize]>,
+    overwrite_policy: OverwritePolicy,
+) -> Result<Vec<String>, String> {
+    validate_media_path(input_path)?;
+    validate_directory_path(output_dir)?;
+
+    let info = probe_file_with_ffprobe(ffprobe_path, input_path).await?;
+    let attachments = select_attachments(info.attachments, attachment_indices)?;
+    if attachments.is_empty() {
+        return Err(format!("No attachments found in {}", input_path));
+    }
+
+    let mut staged = Vec::with_capacity(attachments.len());
+    for name in unique_file_names(&attachments) {
+        let path = Path::new(output_dir).join(name);
+        match StagedOutput::prepare(&path.to_string_lossy(), overwrite_policy) {
+            Ok(output) => staged.push(output),
+            Err(e) => {
+                discard_all(&staged);
+                return Err(e);
+            }
+        }
+    }
+    let outputs: Vec<(usize, &str)> = attachments
+        .iter()
+        .zip(&staged)
+        .map(|(attachment, output)| (attachment.index, output.temp_path()))
+        .collect();
+    let args = build_dump_attachment_args(input_path, &outputs);
+
+    let child = Command::new(ffmpeg_path)
+        .args(&args)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            format!(
+                "Failed to execute ffmpeg: {}. Make sure FFmpeg is installed.",
+                e
+            )
+        })?;
+
+    // Registered by input path so cancel_extract_file stops it like a track extraction
+    if let Some(pid) = child.id()
+        && let Ok(mut guard) = super::state::EXTRACT_PROCESS_IDS.lock()
+    {
+        guard.insert(input_path.to_string(), pid);
+    }
+
+    let output = timeout(FFMPEG_EXTRACT_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| {
+            let (pid, _) = clear_extract_registration(input_path);
+            if let Some(pid) = pid {
+                terminate_process(pid);
+            }
+            discard_all(&staged);
+            format!(
+                "FFmpeg attachment extraction timeout after {} seconds",
+                FFMPEG_EXTRACT_TIMEOUT.as_secs()
+            )
+        })?
+        .map_err(|e| {
+            clear_extract_registration(input_path);
+            discard_all(&staged);
+            format!("Failed to execute ffmpeg: {}", e)
+        })?;
+
+    clear_extract_registration(input_path);
+
+    if !output.status.success() {
+        discard_all(&staged);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffmpeg attachment extraction failed: {}", stderr));
+    }
+
+    let mut output_paths = Vec::with_capacity(staged.len());
+    for (index, output) in staged.iter().enumerate() {
+        match output.commit() {
+            Ok(path) => output_paths.push(path),
+            Err(e) => {
+                discard_all(&staged[index..]);
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(output_paths)
+}
+
+/// Write the fonts and images embedded in a file to a folder
+/// Returns the final output paths; `attachment_indices` selects streams, all by default
+#[tauri::command]
+pub(crate) async fn extract_attachments(
+    app: tauri::AppHandle,
+    input_path: String,
+    output_dir: String,
+    attachment_indices: Option<Vec<usize>>,
+    overwrite_policy: Option<OverwritePolicy>,
+) -> Result<Vec<String>, String> {
+    let ffprobe_path = resolve_ffprobe_path(&app)?;
+    let ffmpeg_path = resolve_ffmpeg_path(&app)?;
+    extract_attachments_with_bins(
+        &ffprobe_path,
+        &ffmpeg_path,
+        &input_path,
+        &output_dir,
+        attachment_indices.as_deref(),
+        overwrite_policy.unwrap_or_default(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{build_dump_attachment_args, select_attachments, unique_file_names};
+    use crate::tools::ffprobe::media_info::MediaAttachment;
+
+    fn attachment(index: usize, filename: Option<&str>) -> MediaAttachment {
+        MediaAttachment {
+            index,
+            filename: filename.map(str::to_string),
+            mimetype: Some("application/x-truetype-font".to_string()),
+            size_bytes: None,
+        }
+    }
+
+    #[test]
+    fn unique_file_names_sanitizes_and_disambiguates() {
+        let attachments = vec![
+            attachment(3, Some("fonts/Arial.ttf")),
+            attachment(4, Some("arial.TTF")),
+            attachment(5, Some("..")),
+            attachment(6, Some("Cover.jpg")),
+        ];
+
+        assert_eq!(
+            unique_file_names(&attachments),
+            vec![
+                "3_Arial.ttf",
+                "4_arial.TTF",
+                "attachment_5.bin",
+                "Cover.jpg"
+            ]
+        );
+    }
+
+    #[test]
+    fn build_dump_attachment_args_dumps_before_input_with_null_output() {
+        let args = build_dump_attachment_args(
+            "/tmp/input.mkv",
+            &[(3, "/tmp/out/a.ttf"), (5, "/tmp/out/b.otf")],
+        );
+
+        assert_eq!(
+            args,
+            vec![
+                "-y",
+                "-dump_attachment:3",
+                "/tmp/out/a.ttf",
+                "-dump_attachment:5",
+                "/tmp/out/b.otf",
+                "-i",
+                "/tmp/input.mkv",
+                "-t",
+                "0",
+                "-f",
+                "null",
+                "-",
+            ]
+        );
+    }
+
+    #[test]
+    fn select_attachments_rejects_non_attachment_streams() {
+        let attachments = vec![attachment(3, Some("a.ttf")), attachment(4, Some("b.ttf"))];
+
+        let selected =
+            select_attachments(attachments.clone(), Some(&[4])).expect("stream 4 is attached");
+        assert_eq!(selected, vec![attachment(4, Some("b.ttf"))]);
+        assert_eq!(
+            select_attachments(attachments.clone(), None).map(|a| a.len()),
+            Ok(2)
+        );
+        assert!(select_attachments(attachments, Some(&[1])).is_err());
+    }
+}
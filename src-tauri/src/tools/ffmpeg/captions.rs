@@ -0,0 +1,241 @@
+use crate::shared::output::{OverwritePolicy, StagedOutput};
+use crate::shared::sleep_inhibit::SleepInhibitGuard;
+use crate::shared::store::resolve_ffmpeg_path;
+use crate::shared::validation::{validate_media_path, validate_output_path};
+use crate::tools::ffmpeg::extract::{StagedExtract, commit_staged_extract, run_staged_ffmpeg};
+use tokio::time::Duration;
+
+/// Captions are read by decoding the whole video, which takes longer than copying a track
+const FFMPEG_CAPTION_TIMEOUT: Duration = Duration::from_secs(1800);
+
+/// Escape a path for the `movie` filter: once as an option value, once for the filtergraph
+fn escape_movie_path(path: &str) -> String {
+    let escape = |value: &str, special: &[char]| {
+        let mut escaped = String::with_capacity(value.len());
+        for c in value.chars() {
+            if c == '\\' || special.contains(&c) {
+                escaped.push('\\');
+            }
+            escaped.push(c);
+        }
+        escaped
+    };
+
+    escape(&escape(path, &['\'', ':']), &['\'', '[', ']', ',', ';'])
+}
+
+/// Read the input through lavfi so the decoder exposes its captions as a subtitle stream
+fn build_caption_extract_args(
+    input_path: &str,
+    output_path: &str,
+    video_stream_index: Option<usize>,
+) -> Vec<String> {
+    let mut source = format!("movie={}", escape_movie_path(input_path));
+    if let Some(index) = video_stream_index {
+        source.push_str(&format!(":si={}", index));
+    }
+    source.push_str("[out+subcc]");
+
+    [
+        "-y",
+        "-f",
+        "lavfi",
+        "-i",
+        &source,
+        "-map",
+        "0:s",
+        "-c:s",
+        "srt",
+        "-progress",
+        "pipe:1",
+        output_path,
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect()
+}
+
+pub(super) async fn extract_closed_captions_with_ffmpeg_and_progress(
+    app: Option<&tauri::AppHandle>,
+    ffmpeg_path: &str,
+    input_path: &str,
+    output_path: &str,
+    video_stream_index: Option<usize>,
+    duration_us: Option<u64>,
+    overwrite_policy: OverwritePolicy,
+) -> Result<String, String> {
+    validate_media_path(input_path)?;
+    validate_output_path(output_path)?;
+    if !output_path.to_lowercase().ends_with(".srt") {
+        return Err("Closed captions can only be extracted to .srt".to_string());
+    }
+
+    let staged = StagedOutput::prepare(output_path, overwrite_policy)?;
+    let args = build_caption_extract_args(input_path, staged.temp_path(), video_stream_index);
+    let extract = StagedExtract {
+        input_path,
+        staged,
+        args,
+        track_index: video_stream_index.unwrap_or(0) as i32,
+        duration_us,
+        time_limit: FFMPEG_CAPTION_TIMEOUT,
+    };
+    run_staged_ffmpeg(app, ffmpeg_path, &extract).await?;
+
+    // ffmpeg still writes an empty file when the video carries no captions, which must not
+    // replace an existing output
+    let is_empty =
+        std::fs::metadata(extract.staged.temp_path()).is_ok_and(|metadata| metadata.len() == 0);
+    if is_empty {
+        extract.staged.discard();
+        return Err(format!("No closed captions found in {}", input_path));
+    }
+
+    commit_staged_extract(app, &extract)
+}
+
+/// Extract EIA-608/708 captions embedded in a video stream to an SRT file
+/// `video_stream_index` picks the stream listed in the probe's `closedCaptions`, the best
+/// video stream by default
+/// Returns the final output path, which differs from the request under auto-suffix
+#[tauri::command]
+pub(crate) async fn extract_closed_captions(
+    app: tauri::AppHandle,
+    input_path: String,
+    output_path: String,
+    video_stream_index: Option<usize>,
+    duration_us: Option<u64>,
+    overwrite_policy: Option<OverwritePolicy>,
+) -> Result<String, String> {
+    let _sleep_guard = SleepInhibitGuard::try_acquire("FFmpeg caption extraction").ok();
+    let ffmpeg_path = resolve_ffmpeg_path(&app)?;
+    extract_closed_captions_with_ffmpeg_and_progress(
+        Some(&app),
+        &ffmpeg_path,
+        &input_path,
+        &output_path,
+        video_stream_index,
+        duration_us,
+        overwrite_policy.unwrap_or_default(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_caption_extract_args, escape_movie_path,
+        extract_closed_captions_with_ffmpeg_and_progress,
+    };
+    use crate::shared::output::OverwritePolicy;
+
+    #[test]
+    fn escape_movie_path_escapes_option_and_filtergraph_characters() {
+        assert_eq!(escape_movie_path("/tv/show.ts"), "/tv/show.ts");
+        assert_eq!(
+            escape_movie_path(r"C:\tv\it's [live].ts"),
+            r"C\\:\\\\tv\\\\it\\\'s \[live\].ts"
+        );
+    }
+
+    #[test]
+    fn build_caption_extract_args_maps_subcc_stream_to_srt() {
+        let args = build_caption_extract_args("/tv/show.ts", "/tmp/show.srt", Some(2));
+
+        assert_eq!(
+            args[..5],
+            [
+                "-y",
+                "-f",
+                "lavfi",
+                "-i",
+                "movie=/tv/show.ts:si=2[out+subcc]"
+            ]
+        );
+        assert!(args.windows(2).any(|w| w == ["-map", "0:s"]));
+        assert!(args.windows(2).any(|w| w == ["-c:s", "srt"]));
+        assert_eq!(args.last().map(String::as_str), Some("/tmp/show.srt"));
+        assert_eq!(
+            build_caption_extract_args("/tv/show.ts", "/tmp/show.srt", None)[4],
+            "movie=/tv/show.ts[out+subcc]"
+        );
+    }
+
+    #[tokio::test]
+    async fn extract_closed_captions_requires_srt_output_and_reports_missing_ffmpeg() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let input = temp.path().join("capture.mkv");
+        std::fs::write(&input, b"media").expect("failed to write input");
+        let input = input.to_string_lossy();
+
+        let error = extract_closed_captions_with_ffmpeg_and_progress(
+            None,
+            "/tmp/definitely-not-a-real-ffmpeg-binary",
+            &input,
+            &temp.path().join("captions.ass").to_string_lossy(),
+            None,
+            None,
+            OverwritePolicy::Overwrite,
+        )
+        .await
+        .expect_err("non-srt output should fail");
+        assert!(error.contains(".srt"));
+
+        let output = temp.path().join("captions.srt");
+        let error = extract_closed_captions_with_ffmpeg_and_progress(
+            None,
+            "/tmp/definitely-not-a-real-ffmpeg-binary",
+            &input,
+            &output.to_string_lossy(),
+            Some(0),
+            None,
+            OverwritePolicy::Overwrite,
+        )
+        .await
+        .expect_err("missing ffmpeg binary should fail");
+        assert!(error.contains("Failed to execute ffmpeg"));
+        assert!(!output.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn extract_closed_captions_keeps_existing_output_when_no_captions_are_found() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let input = temp.path().join("capture.ts");
+        std::fs::write(&input, b"media").expect("failed to write input");
+        let output = temp.path().join("captions.srt");
+        let existing = "1\n00:00:01,000 --> 00:00:02,000\nHello\n";
+        std::fs::write(&output, existing).expect("failed to write existing output");
+
+        // Stands in for ffmpeg on a video without captions: an empty output and success
+        let ffmpeg = temp.path().join("ffmpeg");
+        std::fs::write(&ffmpeg, "#!/bin/sh\nfor last; do :; done\n: > \"$last\"\n")
+            .expect("failed to write fake ffmpeg");
+        std::fs::set_permissions(&ffmpeg, std::fs::Permissions::from_mode(0o755))
+            .expect("failed to make fake ffmpeg executable");
+
+        let error = extract_closed_captions_with_ffmpeg_and_progress(
+            None,
+            &ffmpeg.to_string_lossy(),
+            &input.to_string_lossy(),
+            &output.to_string_lossy(),
+            None,
+            None,
+            OverwritePolicy::Overwrite,
+        )
+        .await
+        .expect_err("an empty caption track should fail");
+
+        assert!(error.contains("No closed captions"));
+        assert_eq!(
+            std::fs::read_to_string(&output).expect("existing output should remain"),
+            existing
+        );
+        let leftovers = std::fs::read_dir(temp.path())
+            .expect("failed to list tempdir")
+            .count();
+        assert_eq!(leftovers, 3, "the empty temp output should be removed");
+    }
+}
@@ -0,0 +1,201 @@
+use crate::shared::chapters::{
+    Chapter, chapters_from_probe, normalize_chapters, to_chapters_csv, to_ffmetadata,
+    to_matroska_chapters_xml, to_ogm_chapters,
+};
+use crate::shared::output::{OverwritePolicy, StagedOutput};
+use crate::shared::store::resolve_ffprobe_path;
+use crate::shared::validation::{validate_media_path, validate_output_path};
+use crate::tools::ffprobe::FFPROBE_TIMEOUT;
+use serde::Deserialize;
+use serde_json::Value;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+/// File format written by `extract_chapters`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum ChapterExportFormat {
+    /// OGM simple chapters (`CHAPTER01=00:00:00.000`)
+    Ogm,
+    MatroskaXml,
+    Ffmetadata,
+    Csv,
+}
+
+impl ChapterExportFormat {
+    fn render(self, chapters: &[Chapter]) -> String {
+        match self {
+            Self::Ogm => to_ogm_chapters(chapters),
+            Self::MatroskaXml => to_matroska_chapters_xml(chapters),
+            Self::Ffmetadata => to_ffmetadata(chapters),
+            Self::Csv => to_chapters_csv(chapters),
+        }
+    }
+}
+
+/// Read the chapters of a media file, with ends filled from the file duration
+pub(super) async fn probe_chapters(ffprobe_path: &str, path: &str) -> Result<Vec<Chapter>, String> {
+    let probe_future = async move {
+        Command::new(ffprobe_path)
+            .args([
+                "-v",
+                "quiet",
+                "-print_format",
+                "json",
+                "-show_chapters",
+                "-show_format",
+                path,
+            ])
+            .output()
+            .await
+    };
+
+    let output = timeout(FFPROBE_TIMEOUT, probe_future)
+        .await
+        .map_err(|_| {
+            format!(
+                "FFprobe timeout after {} seconds",
+                FFPROBE_TIMEOUT.as_secs()
+            )
+        })?
+        .map_err(|e| {
+            format!(
+                "Failed to execute ffprobe: {}. Make sure FFmpeg is installed.",
+                e
+            )
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffprobe failed: {}", stderr));
+    }
+
+    let probe: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse probe output: {}", e))?;
+    let total_ms = probe
+        .get("format")
+        .and_then(|f| f.get("duration"))
+        .and_then(|v| v.as_str())
+        .and_then(|v| v.parse::<f64>().ok())
+        .map(|secs| (secs * 1000.0).round() as u64);
+
+    Ok(normalize_chapters(chapters_from_probe(&probe), total_ms))
+}
+
+/// Write chapters to `output_path` and return the final path
+fn write_chapters(
+    chapters: &[Chapter],
+    output_path: &str,
+    format: ChapterExportFormat,
+    overwrite_policy: OverwritePolicy,
+) -> Result<String, String> {
+    let staged = StagedOutput::prepare(output_path, overwrite_policy)?;
+    if let Err(e) = std::fs::write(staged.temp_path(), format.render(chapters)) {
+        staged.discard();
+        return Err(format!("Failed to write chapters: {}", e));
+    }
+    staged.commit()
+}
+
+pub(super) async fn extract_chapters_with_ffprobe(
+    ffprobe_path: &str,
+    input_path: &str,
+    output_path: &str,
+    format: ChapterExportFormat,
+    overwrite_policy: OverwritePolicy,
+) -> Result<String, String> {
+    validate_media_path(input_path)?;
+    validate_output_path(output_path)?;
+
+    let chapters = probe_chapters(ffprobe_path, input_path).await?;
+    if chapters.is_empty() {
+        return Err(format!("No chapters found in {}", input_path));
+    }
+
+    write_chapters(&chapters, output_path, format, overwrite_policy)
+}
+
+/// Export the chapters of a media file as OGM, Matroska XML, FFMETADATA or CSV
+/// Returns the final output path, which differs from the request under auto-suffix
+#[tauri::command]
+pub(crate) async fn extract_chapters(
+    app: tauri::AppHandle,
+    input_path: String,
+    output_path: String,
+    format: ChapterExportFormat,
+    overwrite_policy: Option<OverwritePolicy>,
+) -> Result<String, String> {
+    let ffprobe_path = resolve_ffprobe_path(&app)?;
+    extract_chapters_with_ffprobe(
+        &ffprobe_path,
+        &input_path,
+        &output_path,
+        format,
+        overwrite_policy.unwrap_or_default(),
+    )
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ChapterExportFormat, extract_chapters_with_ffprobe, write_chapters};
+    use crate::shared::chapters::Chapter;
+    use crate::shared::output::OverwritePolicy;
+
+    #[test]
+    fn write_chapters_renders_requested_format_under_overwrite_policy() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let output = temp.path().join("chapters.txt");
+        std::fs::write(&output, b"existing").expect("failed to write output");
+        let chapters = vec![Chapter {
+            start_ms: 0,
+            end_ms: Some(1_000),
+            title: Some("Intro".to_string()),
+        }];
+
+        let written = write_chapters(
+            &chapters,
+            output.to_string_lossy().as_ref(),
+            ChapterExportFormat::Ogm,
+            OverwritePolicy::AutoSuffix,
+        )
+        .expect("chapters should be written");
+
+        assert_ne!(written, output.to_string_lossy());
+        assert_eq!(
+            std::fs::read_to_string(&written).expect("read chapters"),
+            "CHAPTER01=00:00:00.000\nCHAPTER01NAME=Intro\n"
+        );
+        assert_eq!(std::fs::read(&output).expect("read output"), b"existing");
+        assert!(
+            write_chapters(
+                &chapters,
+                output.to_string_lossy().as_ref(),
+                ChapterExportFormat::Csv,
+                OverwritePolicy::Fail,
+            )
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn extract_chapters_reports_error_when_ffprobe_binary_is_missing() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let input = temp.path().join("input.mkv");
+        std::fs::write(&input, b"media").expect("failed to write input");
+        let output = temp.path().join("chapters.xml");
+
+        let error = extract_chapters_with_ffprobe(
+            "/tmp/definitely-not-a-real-ffprobe-binary",
+            input.to_string_lossy().as_ref(),
+            output.to_string_lossy().as_ref(),
+            ChapterExportFormat::MatroskaXml,
+            OverwritePolicy::Overwrite,
+        )
+        .await
+        .expect_err("missing ffprobe binary should fail");
+
+        assert!(error.contains("Failed to execute ffprobe"));
+        assert!(!output.exists());
+    }
+}
@@ -1,21 +1,30 @@
+use crate::shared::clip::{ACCURATE_VIDEO_ENCODE_ARGS, ClipCut, ClipRange};
 use crate::shared::ffmpeg_progress::FfmpegProgressTracker;
+use crate::shared::output::{OverwritePolicy, StagedOutput};
 use crate::shared::process::terminate_process;
 use crate::shared::sleep_inhibit::SleepInhibitGuard;
-use crate::shared::store::resolve_ffmpeg_path;
-use crate::shared::validation::{validate_media_path, validate_output_path};
+use crate::shared::store::{resolve_ffmpeg_path, resolve_ffprobe_path};
+use crate::shared::validation::{is_valid_bitrate, validate_media_path, validate_output_path};
+use crate::tools::ffmpeg::vobsub::{extract_vobsub_with_bins, is_vobsub_output};
+use crate::tools::ffprobe::keyframes::resolve_clip;
+use serde::Deserialize;
+use std::collections::HashSet;
 use std::process::Stdio;
 use tauri::Emitter;
 use tokio::process::Command;
 use tokio::time::{Duration, timeout};
 
 /// Timeout for FFmpeg extraction operations (5 minutes)
-const FFMPEG_EXTRACT_TIMEOUT: Duration = Duration::from_secs(300);
+pub(super) const FFMPEG_EXTRACT_TIMEOUT: Duration = Duration::from_secs(300);
 
-fn remove_partial_output(path: &str) {
-    let _ = std::fs::remove_file(path);
+/// Time allowed for one ffmpeg pass: five minutes per output, or the input's playback length
+/// when that is longer, since a pass over a large remux reads the whole file
+pub(super) fn extract_timeout(duration_us: Option<u64>, output_count: usize) -> Duration {
+    let per_output = FFMPEG_EXTRACT_TIMEOUT.saturating_mul(output_count.max(1) as u32);
+    per_output.max(Duration::from_micros(duration_us.unwrap_or(0)))
 }
 
-fn clear_extract_registration(input_path: &str) -> (Option<u32>, Option<String>) {
+pub(super) fn clear_extract_registration(input_path: &str) -> (Option<u32>, Option<String>) {
     let pid = super::state::EXTRACT_PROCESS_IDS
         .lock()
         .ok()
@@ -90,22 +99,184 @@ const KNOWN_EXTENSIONS: &[&str] = &[
     ".sup",
 ];
 
-fn build_extract_args(
-    input_path: &str,
+// ============================================================================
+// FORMAT CONVERSION
+// Targets an extracted audio or subtitle track can be re-encoded to
+// ============================================================================
+
+/// Subtitle targets: (name, encoder, accepted output extensions)
+const SUBTITLE_CONVERSIONS: &[(&str, &str, &[&str])] = &[
+    ("srt", "srt", &[".srt"]),
+    ("ass", "ass", &[".ass", ".ssa"]),
+    ("webvtt", "webvtt", &[".vtt"]),
+];
+
+/// Audio targets: (name, encoder, accepted output extensions, takes a bitrate)
+const AUDIO_CONVERSIONS: &[(&str, &str, &[&str], bool)] = &[
+    ("flac", "flac", &[".flac"], false),
+    ("aac", "aac", &[".m4a", ".aac"], true),
+    ("opus", "libopus", &[".opus", ".ogg"], true),
+    ("mp3", "libmp3lame", &[".mp3"], true),
+    ("ac3", "ac3", &[".ac3"], true),
+];
+
+/// Text subtitle codecs ffmpeg can decode into another text format
+const TEXT_SUBTITLE_CODECS: &[&str] =
+    &["subrip", "srt", "ass", "ssa", "mov_text", "webvtt", "text"];
+
+/// Re-encode settings for an extracted track; without them the track is copied
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct ExtractConversion {
+    /// Target format: srt, ass or webvtt for subtitles; flac, aac, opus, mp3 or ac3 for audio
+    pub(crate) codec: String,
+    #[serde(default)]
+    pub(crate) bitrate: Option<String>,
+}
+
+impl ExtractConversion {
+    fn encoder(&self, track_type: &str) -> Option<&'static str> {
+        match track_type {
+            "subtitle" => SUBTITLE_CONVERSIONS
+                .iter()
+                .find(|(name, _, _)| *name == self.codec)
+                .map(|(_, encoder, _)| *encoder),
+            "audio" => AUDIO_CONVERSIONS
+                .iter()
+                .find(|(name, _, _, _)| *name == self.codec)
+                .map(|(_, encoder, _, _)| *encoder),
+            _ => None,
+        }
+    }
+}
+
+/// Reject conversions ffmpeg cannot perform or that do not fit the output extension
+fn validate_extract_conversion(
+    output_path: &str,
+    track_type: &str,
+    codec: &str,
+    conversion: &ExtractConversion,
+) -> Result<(), String> {
+    let (extensions, takes_bitrate): (&[&str], bool) = match track_type {
+        "subtitle" => {
+            let (_, _, extensions) = SUBTITLE_CONVERSIONS
+                .iter()
+                .find(|(name, _, _)| *name == conversion.codec)
+                .ok_or_else(|| format!("Unsupported subtitle format: {}", conversion.codec))?;
+            if !TEXT_SUBTITLE_CODECS.contains(&codec) {
+                return Err(format!(
+                    "Cannot convert {} subtitles to {}: only text subtitles can be converted",
+                    codec, conversion.codec
+                ));
+            }
+            (extensions, false)
+        }
+        "audio" => {
+            let (_, _, extensions, takes_bitrate) = AUDIO_CONVERSIONS
+                .iter()
+                .find(|(name, _, _, _)| *name == conversion.codec)
+                .ok_or_else(|| format!("Unsupported audio format: {}", conversion.codec))?;
+            (extensions, *takes_bitrate)
+        }
+        _ => {
+            return Err(format!(
+                "Only audio and subtitle tracks can be converted, not {}",
+                track_type
+            ));
+        }
+    };
+
+    let output_lower = output_path.to_lowercase();
+    if !has_recognized_extension(output_path)
+        || !extensions.iter().any(|ext| output_lower.ends_with(ext))
+    {
+        return Err(format!(
+            "Output for {} must end with {}",
+            conversion.codec,
+            extensions.join(" or ")
+        ));
+    }
+
+    match conversion.bitrate.as_deref() {
+        Some(_) if !takes_bitrate => Err(format!("{} does not take a bitrate", conversion.codec)),
+        Some(bitrate) if !is_valid_bitrate(bitrate) => Err(format!(
+            "Invalid bitrate \"{}\" (expected e.g. \"192k\")",
+            bitrate
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// One track of a multi-track extraction
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct ExtractTrackRequest {
+    pub(crate) track_index: i32,
+    pub(crate) track_type: String,
+    pub(crate) codec: String,
+    pub(crate) output_path: String,
+    #[serde(default)]
+    pub(crate) conversion: Option<ExtractConversion>,
+}
+
+/// Settings shared by every track of one extraction
+#[derive(Debug, Clone, Default)]
+pub(super) struct ExtractOptions {
+    pub(super) clip: Option<ClipCut>,
+    pub(super) duration_us: Option<u64>,
+    pub(super) overwrite_policy: OverwritePolicy,
+}
+
+impl ExtractOptions {
+    /// Length of what is written, for progress and the timeout
+    pub(super) fn output_duration_us(&self) -> Option<u64> {
+        self.clip.as_ref().map_or(self.duration_us, |clip| {
+            clip.clipped_duration_us(self.duration_us)
+        })
+    }
+}
+
+/// One ffmpeg pass writing a single staged output
+pub(super) struct StagedExtract<'a> {
+    pub(super) input_path: &'a str,
+    pub(super) staged: StagedOutput,
+    pub(super) args: Vec<String>,
+    pub(super) track_index: i32,
+    pub(super) duration_us: Option<u64>,
+    pub(super) time_limit: Duration,
+}
+
+/// Push the `-map`, codec, format and clip length options of one output, without the output path
+fn push_track_output_args(
+    args: &mut Vec<String>,
     output_path: &str,
     track_index: i32,
     track_type: &str,
     codec: &str,
-) -> Vec<String> {
-    let map_arg = format!("0:{}", track_index);
+    conversion: Option<&ExtractConversion>,
+    clip: Option<&ClipCut>,
+) {
+    args.push("-map".to_string());
+    args.push(format!("0:{}", track_index));
+    if let Some(clip) = clip {
+        args.extend(clip.duration_args());
+    }
 
-    let mut args = vec![
-        "-y".to_string(),
-        "-i".to_string(),
-        input_path.to_string(),
-        "-map".to_string(),
-        map_arg,
-    ];
+    // Validated conversions pick their muxer from the output extension
+    if let Some(conversion) = conversion
+        && let Some(encoder) = conversion.encoder(track_type)
+    {
+        if track_type == "subtitle" {
+            args.extend(["-c:s".to_string(), encoder.to_string()]);
+        } else {
+            args.extend(["-c:a".to_string(), encoder.to_string()]);
+            if let Some(bitrate) = conversion.bitrate.as_deref() {
+                args.extend(["-b:a".to_string(), bitrate.to_string()]);
+            }
+            args.push("-vn".to_string());
+        }
+        return;
+    }
 
     let needs_explicit_format = match track_type {
         "subtitle" => {
@@ -118,6 +289,10 @@ fn build_extract_args(
                 }
                 _ => args.extend(["-c:s".to_string(), "copy".to_string()]),
             }
+            // A single-track .mks is Matroska, which ffmpeg doesn't guess from the extension
+            if output_path.to_lowercase().ends_with(".mks") {
+                args.extend(["-f".to_string(), "matroska".to_string()]);
+            }
             false
         }
         "audio" => {
@@ -126,7 +301,11 @@ fn build_extract_args(
             get_ffmpeg_format_for_codec(codec).is_some() || !has_recognized_extension(output_path)
         }
         "video" => {
-            args.extend(["-c:v".to_string(), "copy".to_string()]);
+            if clip.is_some_and(ClipCut::reencodes_video) {
+                args.extend(ACCURATE_VIDEO_ENCODE_ARGS.iter().map(|arg| arg.to_string()));
+            } else {
+                args.extend(["-c:v".to_string(), "copy".to_string()]);
+            }
             args.extend(["-an".to_string()]);
             args.extend(["-sn".to_string()]);
             false
@@ -143,6 +322,31 @@ fn build_extract_args(
             args.push(format.to_string());
         }
     }
+}
+
+fn build_extract_args(
+    input_path: &str,
+    output_path: &str,
+    track_index: i32,
+    track_type: &str,
+    codec: &str,
+    conversion: Option<&ExtractConversion>,
+    clip: Option<&ClipCut>,
+) -> Vec<String> {
+    let mut args = vec!["-y".to_string()];
+    if let Some(clip) = clip {
+        args.extend(clip.seek_args());
+    }
+    args.extend(["-i".to_string(), input_path.to_string()]);
+    push_track_output_args(
+        &mut args,
+        output_path,
+        track_index,
+        track_type,
+        codec,
+        conversion,
+        clip,
+    );
 
     args.push("-progress".to_string());
     args.push("pipe:1".to_string());
@@ -150,6 +354,36 @@ fn build_extract_args(
     args
 }
 
+/// Read the input once and write every track to its own output
+fn build_multi_extract_args(
+    input_path: &str,
+    outputs: &[(&str, &ExtractTrackRequest)],
+    clip: Option<&ClipCut>,
+) -> Vec<String> {
+    let mut args = vec!["-y".to_string()];
+    if let Some(clip) = clip {
+        args.extend(clip.seek_args());
+    }
+    args.extend(
+        ["-i", input_path, "-progress", "pipe:1"]
+            .iter()
+            .map(|arg| arg.to_string()),
+    );
+    for (output_path, track) in outputs {
+        push_track_output_args(
+            &mut args,
+            output_path,
+            track.track_index,
+            &track.track_type,
+            &track.codec,
+            track.conversion.as_ref(),
+            clip,
+        );
+        args.push(output_path.to_string());
+    }
+    args
+}
+
 fn emit_extract_progress(
     app: &tauri::AppHandle,
     input_path: &str,
@@ -170,45 +404,155 @@ fn emit_extract_progress(
     );
 }
 
+/// `extract-progress` for every output of a pass, which all share ffmpeg's one position
+fn emit_outputs_progress(
+    app: &tauri::AppHandle,
+    input_path: &str,
+    outputs: &[(String, i32)],
+    progress: i32,
+    speed_bytes_per_sec: Option<f64>,
+) {
+    for (output_path, track_index) in outputs {
+        emit_extract_progress(
+            app,
+            input_path,
+            output_path,
+            *track_index,
+            progress,
+            speed_bytes_per_sec,
+        );
+    }
+}
+
 async fn extract_track_with_ffmpeg_and_progress(
     app: Option<&tauri::AppHandle>,
     ffmpeg_path: &str,
     input_path: &str,
-    output_path: &str,
-    track_index: i32,
-    track_type: &str,
-    codec: &str,
-    duration_us: Option<u64>,
-) -> Result<(), String> {
+    track: &ExtractTrackRequest,
+    options: &ExtractOptions,
+) -> Result<String, String> {
     // Validate paths
     validate_media_path(input_path)?;
-    validate_output_path(output_path)?;
+    validate_output_path(&track.output_path)?;
+    if let Some(conversion) = track.conversion.as_ref() {
+        validate_extract_conversion(
+            &track.output_path,
+            &track.track_type,
+            &track.codec,
+            conversion,
+        )?;
+    }
 
-    let args = build_extract_args(input_path, output_path, track_index, track_type, codec);
+    // ffmpeg writes to a hidden sibling that only replaces the output once complete
+    let staged = StagedOutput::prepare(&track.output_path, options.overwrite_policy)?;
+    let args = build_extract_args(
+        input_path,
+        staged.temp_path(),
+        track.track_index,
+        &track.track_type,
+        &track.codec,
+        track.conversion.as_ref(),
+        options.clip.as_ref(),
+    );
+    let duration_us = options.output_duration_us();
 
-    let mut child = Command::new(ffmpeg_path)
-        .args(&args)
+    run_staged_extract(
+        app,
+        ffmpeg_path,
+        StagedExtract {
+            input_path,
+            staged,
+            args,
+            track_index: track.track_index,
+            duration_us,
+            time_limit: extract_timeout(duration_us, 1),
+        },
+    )
+    .await
+}
+
+/// Run an ffmpeg pass writing one staged output and move it into place
+pub(super) async fn run_staged_extract(
+    app: Option<&tauri::AppHandle>,
+    ffmpeg_path: &str,
+    extract: StagedExtract<'_>,
+) -> Result<String, String> {
+    run_staged_ffmpeg(app, ffmpeg_path, &extract).await?;
+    commit_staged_extract(app, &extract)
+}
+
+/// Run an ffmpeg pass writing one staged output, with progress events and cancel support
+/// The output is left at its temp path so the caller can check it before committing
+pub(super) async fn run_staged_ffmpeg(
+    app: Option<&tauri::AppHandle>,
+    ffmpeg_path: &str,
+    extract: &StagedExtract<'_>,
+) -> Result<(), String> {
+    run_ffmpeg_pass(
+        app,
+        ffmpeg_path,
+        extract.input_path,
+        &extract.args,
+        &[(&extract.staged, extract.track_index)],
+        extract.duration_us,
+        extract.time_limit,
+    )
+    .await
+}
+
+/// Run one ffmpeg pass writing staged outputs, each with the index of the track it holds
+/// Reports progress per output and can be cancelled by input path; every output is
+/// discarded when the pass fails and left at its temp path otherwise
+async fn run_ffmpeg_pass(
+    app: Option<&tauri::AppHandle>,
+    ffmpeg_path: &str,
+    input_path: &str,
+    args: &[String],
+    outputs: &[(&StagedOutput, i32)],
+    duration_us: Option<u64>,
+    time_limit: Duration,
+) -> Result<(), String> {
+    let discard_outputs = || {
+        for (output, _) in outputs {
+            output.discard();
+        }
+    };
+
+    let mut child = match Command::new(ffmpeg_path)
+        .args(args)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
-        .map_err(|e| {
-            format!(
+    {
+        Ok(child) => child,
+        Err(e) => {
+            discard_outputs();
+            return Err(format!(
                 "Failed to execute ffmpeg: {}. Make sure FFmpeg is installed.",
                 e
-            )
-        })?;
-
-    if let Some(pid) = child.id() {
-        if let Ok(mut guard) = super::state::EXTRACT_PROCESS_IDS.lock() {
-            guard.insert(input_path.to_string(), pid);
+            ));
         }
+    };
+
+    if let Some(pid) = child.id()
+        && let Ok(mut guard) = super::state::EXTRACT_PROCESS_IDS.lock()
+    {
+        guard.insert(input_path.to_string(), pid);
     }
-    if let Ok(mut guard) = super::state::EXTRACT_OUTPUT_PATHS.lock() {
-        guard.insert(input_path.to_string(), output_path.to_string());
+    // Cancelling removes a lone output at once; any others are discarded when ffmpeg exits
+    if let [(output, _)] = outputs
+        && let Ok(mut guard) = super::state::EXTRACT_OUTPUT_PATHS.lock()
+    {
+        guard.insert(input_path.to_string(), output.temp_path().to_string());
     }
 
+    let progress_outputs: Vec<(String, i32)> = outputs
+        .iter()
+        .map(|(output, track_index)| (output.final_path().to_string(), *track_index))
+        .collect();
+
     if let Some(app_handle) = app {
-        emit_extract_progress(app_handle, input_path, output_path, track_index, 0, None);
+        emit_outputs_progress(app_handle, input_path, &progress_outputs, 0, None);
     }
 
     if let Some(stdout) = child.stdout.take() {
@@ -216,7 +560,6 @@ async fn extract_track_with_ffmpeg_and_progress(
 
         let app_for_progress = app.cloned();
         let input_path_for_progress = input_path.to_string();
-        let output_path_for_progress = output_path.to_string();
 
         tokio::spawn(async move {
             let mut tracker = FfmpegProgressTracker::new(duration_us);
@@ -231,11 +574,10 @@ async fn extract_track_with_ffmpeg_and_progress(
                     }
 
                     if let Some(app_handle) = app_for_progress.as_ref() {
-                        emit_extract_progress(
+                        emit_outputs_progress(
                             app_handle,
                             &input_path_for_progress,
-                            &output_path_for_progress,
-                            track_index,
+                            &progress_outputs,
                             last_progress,
                             update.speed_bytes_per_sec,
                         );
@@ -251,45 +593,168 @@ async fn extract_track_with_ffmpeg_and_progress(
 
     let extract_future = async { child.wait_with_output().await };
 
-    let output = timeout(FFMPEG_EXTRACT_TIMEOUT, extract_future)
+    let output = timeout(time_limit, extract_future)
         .await
         .map_err(|_| {
-            let (pid, registered_output) = clear_extract_registration(input_path);
+            let (pid, _) = clear_extract_registration(input_path);
             if let Some(pid) = pid {
                 terminate_process(pid);
             }
-            if let Some(path) = registered_output {
-                remove_partial_output(&path);
-            }
+            discard_outputs();
 
             format!(
                 "FFmpeg extraction timeout after {} seconds",
-                FFMPEG_EXTRACT_TIMEOUT.as_secs()
+                time_limit.as_secs()
             )
         })?
         .map_err(|e| {
-            let (_pid, registered_output) = clear_extract_registration(input_path);
-            if let Some(path) = registered_output {
-                remove_partial_output(&path);
-            }
+            clear_extract_registration(input_path);
+            discard_outputs();
             format!("Failed to execute ffmpeg: {}", e)
         })?;
 
     clear_extract_registration(input_path);
 
     if !output.status.success() {
-        remove_partial_output(output_path);
+        discard_outputs();
         let stderr = String::from_utf8_lossy(&output.stderr);
         return Err(format!("ffmpeg extraction failed: {}", stderr));
     }
 
+    Ok(())
+}
+
+/// Move the output of a finished pass into place and report it complete
+pub(super) fn commit_staged_extract(
+    app: Option<&tauri::AppHandle>,
+    extract: &StagedExtract<'_>,
+) -> Result<String, String> {
+    let output_path = extract.staged.commit()?;
+
     if let Some(app_handle) = app {
-        emit_extract_progress(app_handle, input_path, output_path, track_index, 100, None);
+        emit_extract_progress(
+            app_handle,
+            extract.input_path,
+            &output_path,
+            extract.track_index,
+            100,
+            None,
+        );
+    }
+
+    Ok(output_path)
+}
+
+fn validate_extract_tracks(input_path: &str, tracks: &[ExtractTrackRequest]) -> Result<(), String> {
+    validate_media_path(input_path)?;
+    if tracks.is_empty() {
+        return Err("No tracks to extract".to_string());
+    }
+
+    let mut output_paths = HashSet::new();
+    for track in tracks {
+        validate_output_path(&track.output_path)?;
+        // Only image DVD subtitles become an .idx/.sub pair, text can be written to .sub
+        if track.codec == "dvd_subtitle"
+            && track.conversion.is_none()
+            && is_vobsub_output(&track.output_path)
+        {
+            return Err(format!(
+                "Output {} is a VobSub pair, which is extracted on its own",
+                track.output_path
+            ));
+        }
+        if let Some(conversion) = track.conversion.as_ref() {
+            validate_extract_conversion(
+                &track.output_path,
+                &track.track_type,
+                &track.codec,
+                conversion,
+            )?;
+        }
+        if !output_paths.insert(track.output_path.as_str()) {
+            return Err(format!(
+                "Output {} is used by more than one track",
+                track.output_path
+            ));
+        }
     }
 
     Ok(())
 }
 
+fn discard_all(staged: &[StagedOutput]) {
+    for output in staged {
+        output.discard();
+    }
+}
+
+async fn extract_tracks_with_ffmpeg_and_progress(
+    app: Option<&tauri::AppHandle>,
+    ffmpeg_path: &str,
+    input_path: &str,
+    tracks: &[ExtractTrackRequest],
+    options: &ExtractOptions,
+) -> Result<Vec<String>, String> {
+    validate_extract_tracks(input_path, tracks)?;
+
+    let mut staged = Vec::with_capacity(tracks.len());
+    for track in tracks {
+        match StagedOutput::prepare(&track.output_path, options.overwrite_policy) {
+            Ok(output) => staged.push(output),
+            Err(e) => {
+                discard_all(&staged);
+                return Err(e);
+            }
+        }
+    }
+    let outputs: Vec<(&str, &ExtractTrackRequest)> = staged
+        .iter()
+        .map(StagedOutput::temp_path)
+        .zip(tracks)
+        .collect();
+    let args = build_multi_extract_args(input_path, &outputs, options.clip.as_ref());
+    let duration_us = options.output_duration_us();
+
+    let pass_outputs: Vec<(&StagedOutput, i32)> = staged
+        .iter()
+        .zip(tracks)
+        .map(|(output, track)| (output, track.track_index))
+        .collect();
+    run_ffmpeg_pass(
+        app,
+        ffmpeg_path,
+        input_path,
+        &args,
+        &pass_outputs,
+        duration_us,
+        extract_timeout(duration_us, tracks.len()),
+    )
+    .await?;
+
+    let mut output_paths = Vec::with_capacity(staged.len());
+    for (index, output) in staged.iter().enumerate() {
+        match output.commit() {
+            Ok(path) => output_paths.push(path),
+            Err(e) => {
+                discard_all(&staged[index..]);
+                return Err(e);
+            }
+        }
+    }
+
+    if let Some(app_handle) = app {
+        let committed: Vec<(String, i32)> = output_paths
+            .iter()
+            .cloned()
+            .zip(tracks.iter().map(|track| track.track_index))
+            .collect();
+        emit_outputs_progress(app_handle, input_path, &committed, 100, None);
+    }
+
+    Ok(output_paths)
+}
+
 #[cfg_attr(not(test), allow(dead_code))]
 pub(super) async fn extract_track_with_ffmpeg(
     ffmpeg_path: &str,
@@ -298,44 +763,129 @@ pub(super) async fn extract_track_with_ffmpeg(
     track_index: i32,
     track_type: &str,
     codec: &str,
-) -> Result<(), String> {
+) -> Result<String, String> {
+    let track = ExtractTrackRequest {
+        track_index,
+        track_type: track_type.to_string(),
+        codec: codec.to_string(),
+        output_path: output_path.to_string(),
+        conversion: None,
+    };
     extract_track_with_ffmpeg_and_progress(
         None,
         ffmpeg_path,
         input_path,
-        output_path,
-        track_index,
-        track_type,
-        codec,
-        None,
+        &track,
+        &ExtractOptions::default(),
     )
     .await
 }
 
+fn emit_extract_clip(app: &tauri::AppHandle, input_path: &str, clip: &ClipCut) {
+    let _ = app.emit(
+        "extract-clip",
+        serde_json::json!({
+            "inputPath": input_path,
+            "clip": clip
+        }),
+    );
+}
+
+/// Resolve the cut points of a clip against `stream_specifier` and report them
+async fn resolve_extract_clip(
+    app: &tauri::AppHandle,
+    input_path: &str,
+    stream_specifier: &str,
+    range: Option<ClipRange>,
+) -> Result<Option<ClipCut>, String> {
+    let Some(range) = range else {
+        return Ok(None);
+    };
+
+    validate_media_path(input_path)?;
+    let ffprobe_path = resolve_ffprobe_path(app)?;
+    let clip = resolve_clip(&ffprobe_path, input_path, stream_specifier, &range).await?;
+    emit_extract_clip(app, input_path, &clip);
+    Ok(Some(clip))
+}
+
 /// Extract a track from a video file using ffmpeg
 /// Uses async tokio::process::Command with timeout
 /// Automatically adds -f flag when codec requires explicit format specification
+/// Re-encodes to the requested format when a conversion is given
+/// Writes DVD subtitles to an .idx/.sub pair when either extension is requested and returns
+/// the .idx path
+/// Keeps only the clip's time range when one is given, snapped to the track's keyframes
+/// in keyframe mode
+/// Returns the final output path, which differs from the request under auto-suffix
 #[tauri::command]
 pub(crate) async fn extract_track(
     app: tauri::AppHandle,
     input_path: String,
-    output_path: String,
-    track_index: i32,
-    track_type: String,
-    codec: String,
+    track: ExtractTrackRequest,
     duration_us: Option<u64>,
-) -> Result<(), String> {
+    overwrite_policy: Option<OverwritePolicy>,
+    clip: Option<ClipRange>,
+) -> Result<String, String> {
     let _sleep_guard = SleepInhibitGuard::try_acquire("FFmpeg extraction").ok();
     let ffmpeg_path = resolve_ffmpeg_path(&app)?;
-    extract_track_with_ffmpeg_and_progress(
+    let clip =
+        resolve_extract_clip(&app, &input_path, &track.track_index.to_string(), clip).await?;
+    let options = ExtractOptions {
+        clip,
+        duration_us,
+        overwrite_policy: overwrite_policy.unwrap_or_default(),
+    };
+    if track.codec == "dvd_subtitle"
+        && track.conversion.is_none()
+        && is_vobsub_output(&track.output_path)
+    {
+        let ffprobe_path = resolve_ffprobe_path(&app)?;
+        return extract_vobsub_with_bins(
+            Some(&app),
+            &ffprobe_path,
+            &ffmpeg_path,
+            &input_path,
+            &track,
+            &options,
+        )
+        .await;
+    }
+    extract_track_with_ffmpeg_and_progress(Some(&app), &ffmpeg_path, &input_path, &track, &options)
+        .await
+}
+
+/// Extract several tracks of one file in a single ffmpeg pass
+/// A clip applies to every track and snaps to the keyframes of the first video track
+/// Returns the final output paths in the order the tracks were given
+#[tauri::command]
+pub(crate) async fn extract_tracks(
+    app: tauri::AppHandle,
+    input_path: String,
+    tracks: Vec<ExtractTrackRequest>,
+    duration_us: Option<u64>,
+    overwrite_policy: Option<OverwritePolicy>,
+    clip: Option<ClipRange>,
+) -> Result<Vec<String>, String> {
+    let _sleep_guard = SleepInhibitGuard::try_acquire("FFmpeg extraction").ok();
+    let ffmpeg_path = resolve_ffmpeg_path(&app)?;
+    let keyframe_track = tracks
+        .iter()
+        .find(|track| track.track_type == "video")
+        .or_else(|| tracks.first())
+        .map_or("v:0".to_string(), |track| track.track_index.to_string());
+    let clip = resolve_extract_clip(&app, &input_path, &keyframe_track, clip).await?;
+    let options = ExtractOptions {
+        clip,
+        duration_us,
+        overwrite_policy: overwrite_policy.unwrap_or_default(),
+    };
+    extract_tracks_with_ffmpeg_and_progress(
         Some(&app),
         &ffmpeg_path,
         &input_path,
-        &output_path,
-        track_index,
-        &track_type,
-        &codec,
-        duration_us,
+        &tracks,
+        &options,
     )
     .await
 }
@@ -343,9 +893,28 @@ pub(crate) async fn extract_track(
 #[cfg(test)]
 mod tests {
     use super::{
-        build_extract_args, extract_track_with_ffmpeg, get_ffmpeg_format_for_codec,
-        has_recognized_extension,
+        Duration, ExtractConversion, ExtractOptions, ExtractTrackRequest, FFMPEG_EXTRACT_TIMEOUT,
+        build_extract_args, build_multi_extract_args, extract_timeout, extract_track_with_ffmpeg,
+        extract_track_with_ffmpeg_and_progress, extract_tracks_with_ffmpeg_and_progress,
+        get_ffmpeg_format_for_codec, has_recognized_extension, validate_extract_conversion,
     };
+    use crate::shared::clip::{ClipCut, ClipMode, ClipRange};
+    use crate::shared::output::OverwritePolicy;
+
+    fn track(
+        track_index: i32,
+        track_type: &str,
+        codec: &str,
+        output_path: &str,
+    ) -> ExtractTrackRequest {
+        ExtractTrackRequest {
+            track_index,
+            track_type: track_type.to_string(),
+            codec: codec.to_string(),
+            output_path: output_path.to_string(),
+            conversion: None,
+        }
+    }
 
     #[test]
     fn get_ffmpeg_format_for_codec_matches_known_codec_case_insensitive() {
@@ -353,6 +922,20 @@ mod tests {
         assert_eq!(get_ffmpeg_format_for_codec("unknown"), None);
     }
 
+    #[test]
+    fn extract_timeout_scales_with_outputs_and_duration() {
+        assert_eq!(extract_timeout(None, 1), FFMPEG_EXTRACT_TIMEOUT);
+        assert_eq!(extract_timeout(None, 4), FFMPEG_EXTRACT_TIMEOUT * 4);
+        assert_eq!(
+            extract_timeout(Some(60_000_000), 2),
+            FFMPEG_EXTRACT_TIMEOUT * 2
+        );
+        assert_eq!(
+            extract_timeout(Some(7_200_000_000), 3),
+            Duration::from_secs(7200)
+        );
+    }
+
     #[test]
     fn has_recognized_extension_supports_common_extensions() {
         assert!(has_recognized_extension("/tmp/file.MP3"));
@@ -361,23 +944,304 @@ mod tests {
 
     #[test]
     fn build_extract_args_adds_explicit_format_for_audio_codec_when_needed() {
-        let args = build_extract_args("/tmp/input.mkv", "/tmp/output.bin", 1, "audio", "wmav2");
+        let args = build_extract_args(
+            "/tmp/input.mkv",
+            "/tmp/output.bin",
+            1,
+            "audio",
+            "wmav2",
+            None,
+            None,
+        );
         assert!(args.windows(2).any(|w| w == ["-f", "asf"]));
     }
 
     #[test]
     fn build_extract_args_for_video_disables_audio_and_subtitles() {
-        let args = build_extract_args("/tmp/input.mkv", "/tmp/output.mkv", 0, "video", "h264");
+        let args = build_extract_args(
+            "/tmp/input.mkv",
+            "/tmp/output.mkv",
+            0,
+            "video",
+            "h264",
+            None,
+            None,
+        );
         assert!(args.contains(&"-an".to_string()));
         assert!(args.contains(&"-sn".to_string()));
     }
 
     #[test]
     fn build_extract_args_enables_progress_output() {
-        let args = build_extract_args("/tmp/input.mkv", "/tmp/output.mkv", 0, "video", "h264");
+        let args = build_extract_args(
+            "/tmp/input.mkv",
+            "/tmp/output.mkv",
+            0,
+            "video",
+            "h264",
+            None,
+            None,
+        );
         assert!(args.windows(2).any(|w| w == ["-progress", "pipe:1"]));
     }
 
+    #[test]
+    fn build_multi_extract_args_groups_options_per_output() {
+        let audio = track(1, "audio", "truehd", "/tmp/out/audio.thd");
+        let subtitle = track(3, "subtitle", "subrip", "/tmp/out/sub.srt");
+        let outputs = vec![
+            ("/tmp/out/audio.thd", &audio),
+            ("/tmp/out/sub.srt", &subtitle),
+        ];
+
+        let args = build_multi_extract_args("/tmp/input.mkv", &outputs, None);
+
+        assert_eq!(args.iter().filter(|arg| *arg == "-i").count(), 1);
+        let audio_map = args.iter().position(|arg| arg == "0:1").expect("audio map");
+        let audio_out = args
+            .iter()
+            .position(|arg| arg == "/tmp/out/audio.thd")
+            .expect("audio output");
+        let sub_map = args
+            .iter()
+            .position(|arg| arg == "0:3")
+            .expect("subtitle map");
+        assert!(audio_map < audio_out && audio_out < sub_map);
+        assert_eq!(args[audio_out - 2..audio_out], ["-f", "mlp"]);
+        assert_eq!(args.last().map(String::as_str), Some("/tmp/out/sub.srt"));
+        assert!(args.windows(2).any(|w| w == ["-c:s", "srt"]));
+    }
+
+    #[test]
+    fn build_extract_args_seeks_input_and_limits_output_for_clips() {
+        let mut clip = ClipCut::exact(&ClipRange {
+            start_ms: 90_000,
+            end_ms: Some(180_500),
+            mode: ClipMode::Keyframe,
+        });
+        let args = build_extract_args(
+            "/tmp/input.mkv",
+            "/tmp/output.mkv",
+            0,
+            "video",
+            "h264",
+            None,
+            Some(&clip),
+        );
+        assert_eq!(args[..5], ["-y", "-ss", "90.000", "-i", "/tmp/input.mkv"]);
+        assert!(args.windows(2).any(|w| w == ["-t", "90.500"]));
+        assert!(args.windows(2).any(|w| w == ["-c:v", "copy"]));
+
+        clip.mode = ClipMode::Accurate;
+        let args = build_extract_args(
+            "/tmp/input.mkv",
+            "/tmp/output.mkv",
+            0,
+            "video",
+            "h264",
+            None,
+            Some(&clip),
+        );
+        assert!(args.windows(2).any(|w| w == ["-c:v", "libx264"]));
+        assert!(!args.contains(&"copy".to_string()));
+    }
+
+    #[test]
+    fn build_extract_args_writes_mks_subtitles_as_matroska() {
+        let args = build_extract_args(
+            "/tmp/input.mkv",
+            "/tmp/output.mks",
+            5,
+            "subtitle",
+            "dvd_subtitle",
+            None,
+            None,
+        );
+        assert!(args.windows(2).any(|w| w == ["-c:s", "copy"]));
+        assert!(args.windows(2).any(|w| w == ["-f", "matroska"]));
+    }
+
+    fn conversion(codec: &str, bitrate: Option<&str>) -> ExtractConversion {
+        ExtractConversion {
+            codec: codec.to_string(),
+            bitrate: bitrate.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn build_extract_args_reencodes_converted_tracks() {
+        let srt = conversion("srt", None);
+        let args = build_extract_args(
+            "/tmp/input.mkv",
+            "/tmp/output.srt",
+            2,
+            "subtitle",
+            "ass",
+            Some(&srt),
+            None,
+        );
+        assert!(args.windows(2).any(|w| w == ["-c:s", "srt"]));
+
+        let opus = conversion("opus", Some("160k"));
+        let args = build_extract_args(
+            "/tmp/input.mkv",
+            "/tmp/output.opus",
+            1,
+            "audio",
+            "truehd",
+            Some(&opus),
+            None,
+        );
+        assert!(args.windows(2).any(|w| w == ["-c:a", "libopus"]));
+        assert!(args.windows(2).any(|w| w == ["-b:a", "160k"]));
+        assert!(!args.contains(&"-f".to_string()));
+        assert!(!args.contains(&"copy".to_string()));
+    }
+
+    #[test]
+    fn validate_extract_conversion_rejects_incompatible_requests() {
+        let check = |output: &str, track_type: &str, codec: &str, target: ExtractConversion| {
+            validate_extract_conversion(output, track_type, codec, &target)
+        };
+
+        assert!(
+            check(
+                "/tmp/a.srt",
+                "subtitle",
+                "mov_text",
+                conversion("srt", None)
+            )
+            .is_ok()
+        );
+        assert!(check("/tmp/a.flac", "audio", "dts", conversion("flac", None)).is_ok());
+        assert!(
+            check(
+                "/tmp/a.m4a",
+                "audio",
+                "ac3",
+                conversion("aac", Some("256k"))
+            )
+            .is_ok()
+        );
+
+        // Bitmap subtitles need OCR, not a format conversion
+        assert!(
+            check(
+                "/tmp/a.srt",
+                "subtitle",
+                "hdmv_pgs_subtitle",
+                conversion("srt", None)
+            )
+            .is_err()
+        );
+        assert!(check("/tmp/a.ass", "subtitle", "ass", conversion("srt", None)).is_err());
+        assert!(
+            check(
+                "/tmp/a.flac",
+                "audio",
+                "dts",
+                conversion("flac", Some("320k"))
+            )
+            .is_err()
+        );
+        assert!(
+            check(
+                "/tmp/a.mp3",
+                "audio",
+                "aac",
+                conversion("mp3", Some("fast"))
+            )
+            .is_err()
+        );
+        assert!(check("/tmp/a.mkv", "video", "h264", conversion("aac", None)).is_err());
+        assert!(check("/tmp/a.wav", "audio", "aac", conversion("wav", None)).is_err());
+    }
+
+    #[tokio::test]
+    async fn extract_tracks_rejects_duplicate_outputs_before_spawning() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let input = temp.path().join("input.mkv");
+        std::fs::write(&input, b"media").expect("failed to write input");
+        let output = temp.path().join("track.srt");
+        let output = output.to_string_lossy();
+
+        let error = extract_tracks_with_ffmpeg_and_progress(
+            None,
+            "/tmp/definitely-not-a-real-ffmpeg-binary",
+            input.to_string_lossy().as_ref(),
+            &[
+                track(2, "subtitle", "subrip", &output),
+                track(3, "subtitle", "ass", &output),
+            ],
+            &ExtractOptions::default(),
+        )
+        .await
+        .expect_err("duplicate outputs should fail");
+
+        assert!(error.contains("more than one track"));
+
+        let vobsub = temp.path().join("track.idx");
+        let error = extract_tracks_with_ffmpeg_and_progress(
+            None,
+            "/tmp/definitely-not-a-real-ffmpeg-binary",
+            input.to_string_lossy().as_ref(),
+            &[track(
+                4,
+                "subtitle",
+                "dvd_subtitle",
+                &vobsub.to_string_lossy(),
+            )],
+            &ExtractOptions::default(),
+        )
+        .await
+        .expect_err("VobSub outputs should be extracted on their own");
+
+        assert!(error.contains("VobSub"));
+
+        // A text subtitle may still be written to .sub (MicroDVD) in a multi-track pass
+        let microdvd = temp.path().join("track.sub");
+        let error = extract_tracks_with_ffmpeg_and_progress(
+            None,
+            "/tmp/definitely-not-a-real-ffmpeg-binary",
+            input.to_string_lossy().as_ref(),
+            &[track(5, "subtitle", "subrip", &microdvd.to_string_lossy())],
+            &ExtractOptions::default(),
+        )
+        .await
+        .expect_err("missing ffmpeg binary should fail");
+
+        assert!(error.contains("Failed to execute ffmpeg"));
+    }
+
+    #[tokio::test]
+    async fn extract_tracks_missing_ffmpeg_leaves_no_temp_outputs() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let input = temp.path().join("input.mkv");
+        std::fs::write(&input, b"media").expect("failed to write input");
+        let audio = temp.path().join("audio.ac3");
+        let subtitle = temp.path().join("sub.srt");
+
+        let error = extract_tracks_with_ffmpeg_and_progress(
+            None,
+            "/tmp/definitely-not-a-real-ffmpeg-binary",
+            input.to_string_lossy().as_ref(),
+            &[
+                track(1, "audio", "ac3", &audio.to_string_lossy()),
+                track(2, "subtitle", "subrip", &subtitle.to_string_lossy()),
+            ],
+            &ExtractOptions::default(),
+        )
+        .await
+        .expect_err("missing ffmpeg binary should fail");
+
+        assert!(error.contains("Failed to execute ffmpeg"));
+        let leftovers = std::fs::read_dir(temp.path())
+            .expect("read tempdir")
+            .filter_map(Result::ok)
+            .count();
+        assert_eq!(leftovers, 1);
+    }
+
     #[tokio::test]
     async fn extract_track_extracts_video_stream_from_sample_video() {
         let video = crate::test_support::assets::ensure_sample_video()
@@ -386,14 +1250,12 @@ mod tests {
         let temp = tempfile::tempdir().expect("failed to create tempdir");
         let output = temp.path().join("video.mkv");
 
-        let probe_json = crate::tools::ffprobe::probe::probe_file_with_ffprobe(
+        let probe_value = crate::tools::ffprobe::probe::probe_json_with_ffprobe(
             "ffprobe",
             video.to_string_lossy().as_ref(),
         )
         .await
         .expect("probe should succeed");
-        let probe_value: serde_json::Value =
-            serde_json::from_str(&probe_json).expect("valid probe json expected");
         let track_index = probe_value
             .get("streams")
             .and_then(|v| v.as_array())
@@ -467,6 +1329,58 @@ mod tests {
         assert!(error.contains("ffmpeg extraction failed"));
     }
 
+    #[tokio::test]
+    async fn extract_track_keeps_existing_output_under_fail_policy() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let input = temp.path().join("input.mkv");
+        let output = temp.path().join("track.srt");
+        std::fs::write(&input, b"media").expect("failed to write input");
+        std::fs::write(&output, b"existing").expect("failed to write output");
+
+        let error = extract_track_with_ffmpeg_and_progress(
+            None,
+            "/tmp/definitely-not-a-real-ffmpeg-binary",
+            input.to_string_lossy().as_ref(),
+            &track(0, "subtitle", "subrip", &output.to_string_lossy()),
+            &ExtractOptions {
+                overwrite_policy: OverwritePolicy::Fail,
+                ..ExtractOptions::default()
+            },
+        )
+        .await
+        .expect_err("existing output should fail");
+
+        assert!(error.contains("already exists"));
+        assert_eq!(std::fs::read(&output).expect("read output"), b"existing");
+    }
+
+    #[tokio::test]
+    async fn extract_track_failure_leaves_no_temp_output() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let input = temp.path().join("corrupted.mp4");
+        std::fs::write(&input, b"this-is-not-valid-media").expect("failed to write input");
+        let output = temp.path().join("out.mkv");
+
+        extract_track_with_ffmpeg(
+            "ffmpeg",
+            input.to_string_lossy().as_ref(),
+            output.to_string_lossy().as_ref(),
+            0,
+            "video",
+            "h264",
+        )
+        .await
+        .expect_err("corrupted input should fail");
+
+        let leftovers = std::fs::read_dir(temp.path())
+            .expect("read tempdir")
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_name().to_string_lossy().contains(".part"))
+            .count();
+        assert_eq!(leftovers, 0);
+        assert!(!output.exists());
+    }
+
     #[tokio::test]
     async fn extract_track_reports_error_when_ffmpeg_binary_is_missing() {
         let video = crate::test_support::assets::ensure_sample_video()
@@ -1,5 +1,9 @@
+pub(crate) mod attachments;
 pub(crate) mod cancel;
+pub(crate) mod captions;
+pub(crate) mod chapters;
 pub(crate) mod download;
 pub(crate) mod extract;
 mod state;
 pub(crate) mod version;
+mod vobsub;
@@ -0,0 +1,529 @@
+use crate::shared::clip::ClipCut;
+use crate::shared::language::iso639_1_code;
+use crate::shared::output::{StagedOutput, resolve_output_paths};
+use crate::shared::validation::{validate_media_path, validate_output_path};
+use crate::tools::ffmpeg::extract::{
+    ExtractOptions, ExtractTrackRequest, StagedExtract, commit_staged_extract, extract_timeout,
+    run_staged_ffmpeg,
+};
+use crate::tools::ffprobe::FFPROBE_TIMEOUT;
+use serde_json::Value;
+use std::path::Path;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+/// Palette written when the source carries none, as used by VobSub itself
+const DEFAULT_VOBSUB_PALETTE: &str = "000000, f0f0f0, cccccc, 999999, 3333fa, 1111bb, fa3333, \
+bb1111, 33fa33, 11bb11, fafa33, bbbb11, fa33fa, bb11bb, 33fafa, 11bbbb";
+
+/// Whether an extraction target is a VobSub .idx/.sub pair
+pub(super) fn is_vobsub_output(path: &str) -> bool {
+    let lower = path.to_lowercase();
+    lower.ends_with(".idx") || lower.ends_with(".sub")
+}
+
+/// The .idx and .sub paths sharing the requested file's stem
+fn vobsub_pair_paths(path: &str) -> (String, String) {
+    let path = Path::new(path);
+    (
+        path.with_extension("idx").to_string_lossy().to_string(),
+        path.with_extension("sub").to_string_lossy().to_string(),
+    )
+}
+
+/// Two-letter language for the .idx `id:` line, "--" when unknown
+fn idx_language(language: Option<&str>) -> &'static str {
+    language.and_then(iso639_1_code).unwrap_or("--")
+}
+
+/// Bytes of an ffprobe `-show_data` hex dump (`00000000: 7369 7a65 ...  size...`)
+fn parse_hex_dump(dump: &str) -> Vec<u8> {
+    dump.lines()
+        .filter_map(|line| line.get(10..line.len().min(50)))
+        .flat_map(|hex| {
+            hex.split_whitespace()
+                .flat_map(|group| {
+                    (0..group.len())
+                        .step_by(2)
+                        .filter_map(|i| u8::from_str_radix(group.get(i..i + 2)?, 16).ok())
+                        .collect::<Vec<u8>>()
+                })
+                .collect::<Vec<u8>>()
+        })
+        .collect()
+}
+
+/// Header lines of the .idx, taken from the codec private data of the source when present
+fn vobsub_header(extradata: Option<&str>, width: Option<u64>, height: Option<u64>) -> String {
+    let mut lines: Vec<String> = extradata
+        .unwrap_or_default()
+        .lines()
+        .map(str::trim)
+        .filter(|line| {
+            !line.is_empty()
+                && !line.starts_with('#')
+                && !line.starts_with("langidx:")
+                && !line.starts_with("id:")
+        })
+        .map(str::to_string)
+        .collect();
+
+    if !lines.iter().any(|line| line.starts_with("size:")) {
+        lines.insert(
+            0,
+            format!("size: {}x{}", width.unwrap_or(720), height.unwrap_or(480)),
+        );
+    }
+    if !lines.iter().any(|line| line.starts_with("palette:")) {
+        lines.push(format!("palette: {}", DEFAULT_VOBSUB_PALETTE));
+    }
+
+    lines.join("\n")
+}
+
+/// PTS (90 kHz) of a private stream 1 PES payload that starts a DVD subtitle
+fn subtitle_pes_pts(pes: &[u8]) -> Option<u64> {
+    // MPEG-2 PES header with the PTS flag set
+    if pes.len() < 3 || pes[0] & 0xC0 != 0x80 || pes[1] & 0x80 == 0 {
+        return None;
+    }
+    let substream = *pes.get(3 + pes[2] as usize)?;
+    if substream & 0xE0 != 0x20 {
+        return None;
+    }
+
+    let pts = pes.get(3..8)?;
+    Some(
+        ((u64::from(pts[0]) >> 1) & 0x07) << 30
+            | u64::from(pts[1]) << 22
+            | (u64::from(pts[2]) >> 1) << 15
+            | u64::from(pts[3]) << 7
+            | u64::from(pts[4]) >> 1,
+    )
+}
+
+/// Start time (ms) and pack offset of every subtitle in an MPEG-PS .sub file
+fn subtitle_pack_positions(data: &[u8]) -> Vec<(u64, u64)> {
+    let mut entries = Vec::new();
+    let mut pos = 0;
+    let mut pack_pos = 0;
+
+    while pos + 6 <= data.len() {
+        if data[pos..pos + 3] != [0, 0, 1] {
+            pos += 1;
+            continue;
+        }
+
+        match data[pos + 3] {
+            0xBA => {
+                pack_pos = pos;
+                // MPEG-2 pack headers are 14 bytes plus stuffing, MPEG-1 ones 12
+                pos += if data[pos + 4] & 0xC0 == 0x40 {
+                    14 + data.get(pos + 13).map_or(0, |b| (b & 0x07) as usize)
+                } else {
+                    12
+                };
+            }
+            // Program end code
+            0xB9 => pos += 4,
+            stream_id => {
+                let length = u16::from_be_bytes([data[pos + 4], data[pos + 5]]) as usize;
+                let end = (pos + 6 + length).min(data.len());
+                if stream_id == 0xBD
+                    && let Some(pts) = subtitle_pes_pts(&data[pos + 6..end])
+                {
+                    entries.push((pts / 90, pack_pos as u64));
+                }
+                pos += 6 + length;
+            }
+        }
+    }
+
+    entries
+}
+
+fn format_idx_timestamp(ms: u64) -> String {
+    format!(
+        "{:02}:{:02}:{:02}:{:03}",
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+fn build_idx(header: &str, language: &str, entries: &[(u64, u64)]) -> String {
+    let mut idx = format!(
+        "# VobSub index file, v7 (do not modify this line!)\n#\n{}\n\nlangidx: 0\n\nid: {}, index: 0\n",
+        header, language
+    );
+    for (ms, filepos) in entries {
+        idx.push_str(&format!(
+            "timestamp: {}, filepos: {:09x}\n",
+            format_idx_timestamp(*ms),
+            filepos
+        ));
+    }
+    idx
+}
+
+/// Write the subtitle packets as a DVD program stream; preload 0 keeps their PTS unshifted
+fn build_vobsub_args(
+    input_path: &str,
+    sub_path: &str,
+    track_index: i32,
+    clip: Option<&ClipCut>,
+) -> Vec<String> {
+    let mut args = vec!["-y".to_string()];
+    if let Some(clip) = clip {
+        args.extend(clip.seek_args());
+    }
+    args.extend(["-i".to_string(), input_path.to_string()]);
+    args.push("-map".to_string());
+    args.push(format!("0:{}", track_index));
+    if let Some(clip) = clip {
+        args.extend(clip.duration_args());
+    }
+    args.extend(
+        [
+            "-c:s",
+            "copy",
+            "-f",
+            "vob",
+            "-muxpreload",
+            "0",
+            "-progress",
+            "pipe:1",
+            sub_path,
+        ]
+        .iter()
+        .map(|arg| arg.to_string()),
+    );
+    args
+}
+
+/// ffprobe entry of one stream, with its codec private data
+async fn probe_stream_with_data(
+    ffprobe_path: &str,
+    path: &str,
+    track_index: i32,
+) -> Result<Value, String> {
+    let stream_specifier = track_index.to_string();
+    let probe_future = async move {
+        Command::new(ffprobe_path)
+            .args([
+                "-v",
+                "quiet",
+                "-print_format",
+                "json",
+                "-show_streams",
+                "-show_data",
+                "-select_streams",
+                &stream_specifier,
+                path,
+            ])
+            .output()
+            .await
+    };
+
+    let output = timeout(FFPROBE_TIMEOUT, probe_future)
+        .await
+        .map_err(|_| {
+            format!(
+                "FFprobe timeout after {} seconds",
+                FFPROBE_TIMEOUT.as_secs()
+            )
+        })?
+        .map_err(|e| {
+            format!(
+                "Failed to execute ffprobe: {}. Make sure FFmpeg is installed.",
+                e
+            )
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffprobe failed: {}", stderr));
+    }
+
+    let probe: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse probe output: {}", e))?;
+    probe
+        .get("streams")
+        .and_then(|streams| streams.get(0))
+        .cloned()
+        .ok_or_else(|| format!("Stream {} not found in {}", track_index, path))
+}
+
+/// Extract a `dvd_subtitle` stream to an .idx/.sub pair and return the .idx path
+pub(super) async fn extract_vobsub_with_bins(
+    app: Option<&tauri::AppHandle>,
+    ffprobe_path: &str,
+    ffmpeg_path: &str,
+    input_path: &str,
+    track: &ExtractTrackRequest,
+    options: &ExtractOptions,
+) -> Result<String, String> {
+    let output_path = track.output_path.as_str();
+    let track_index = track.track_index;
+    validate_media_path(input_path)?;
+    validate_output_path(output_path)?;
+
+    let stream = probe_stream_with_data(ffprobe_path, input_path, track_index).await?;
+    let codec = stream.get("codec_name").and_then(|v| v.as_str());
+    if codec != Some("dvd_subtitle") {
+        return Err(format!(
+            "Stream {} is not a DVD subtitle ({})",
+            track_index,
+            codec.unwrap_or("unknown codec")
+        ));
+    }
+    let extradata = stream
+        .get("extradata")
+        .and_then(|v| v.as_str())
+        .map(|dump| String::from_utf8_lossy(&parse_hex_dump(dump)).to_string());
+    let header = vobsub_header(
+        extradata.as_deref(),
+        stream.get("width").and_then(|v| v.as_u64()),
+        stream.get("height").and_then(|v| v.as_u64()),
+    );
+    let language = idx_language(
+        stream
+            .get("tags")
+            .and_then(|tags| tags.get("language"))
+            .and_then(|v| v.as_str()),
+    );
+
+    let (idx_path, sub_path) = vobsub_pair_paths(output_path);
+    let resolved = resolve_output_paths(&[&idx_path, &sub_path], options.overwrite_policy)?;
+    let idx_staged = StagedOutput::prepare(&resolved[0], options.overwrite_policy)?;
+    let sub_staged = StagedOutput::prepare(&resolved[1], options.overwrite_policy)?;
+    let args = build_vobsub_args(
+        input_path,
+        sub_staged.temp_path(),
+        track_index,
+        options.clip.as_ref(),
+    );
+    let duration_us = options.output_duration_us();
+
+    let extract = StagedExtract {
+        input_path,
+        staged: sub_staged,
+        args,
+        track_index,
+        duration_us,
+        time_limit: extract_timeout(duration_us, 1),
+    };
+    run_staged_ffmpeg(app, ffmpeg_path, &extract).await?;
+
+    // The .idx is built from the staged .sub, and the pair only replaces existing files
+    // once both halves are written
+    let write_idx = || {
+        let data = std::fs::read(extract.staged.temp_path())
+            .map_err(|e| format!("Failed to read .sub: {}", e))?;
+        let entries = subtitle_pack_positions(&data);
+        if entries.is_empty() {
+            return Err(format!("No subtitles found in stream {}", track_index));
+        }
+        std::fs::write(
+            idx_staged.temp_path(),
+            build_idx(&header, language, &entries),
+        )
+        .map_err(|e| format!("Failed to write .idx: {}", e))
+    };
+    if let Err(e) = write_idx() {
+        idx_staged.discard();
+        extract.staged.discard();
+        return Err(e);
+    }
+
+    commit_staged_extract(app, &extract).inspect_err(|_| idx_staged.discard())?;
+    idx_staged.commit()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        build_idx, build_vobsub_args, extract_vobsub_with_bins, idx_language, parse_hex_dump,
+        subtitle_pack_positions, vobsub_header, vobsub_pair_paths,
+    };
+    use crate::tools::ffmpeg::extract::{ExtractOptions, ExtractTrackRequest};
+
+    fn pts_bytes(pts: u64) -> [u8; 5] {
+        [
+            0x21 | ((pts >> 29) & 0x0E) as u8,
+            (pts >> 22) as u8,
+            ((pts >> 14) & 0xFE) as u8 | 1,
+            (pts >> 7) as u8,
+            ((pts << 1) & 0xFE) as u8 | 1,
+        ]
+    }
+
+    /// MPEG-2 pack holding one private stream 1 PES for subtitle substream 0x20
+    fn pack(pts: Option<u64>, payload_len: usize) -> Vec<u8> {
+        let mut data = vec![0, 0, 1, 0xBA, 0x44, 0, 4, 0, 4, 1, 1, 0x89, 0xC3, 0xF8];
+        let mut pes = match pts {
+            Some(pts) => {
+                let mut header = vec![0x81, 0x80, 5];
+                header.extend(pts_bytes(pts));
+                header
+            }
+            None => vec![0x81, 0x00, 0],
+        };
+        pes.push(0x20);
+        pes.extend(std::iter::repeat_n(0xAA, payload_len));
+        data.extend([0, 0, 1, 0xBD]);
+        data.extend((pes.len() as u16).to_be_bytes());
+        data.extend(pes);
+        data
+    }
+
+    #[test]
+    fn subtitle_pack_positions_reads_pts_of_starting_packs() {
+        let mut data = pack(Some(135_000), 40);
+        let second = data.len() as u64;
+        data.extend(pack(None, 40));
+        let third = data.len() as u64;
+        data.extend(pack(Some(5_400_000 + 45), 10));
+        data.extend([0, 0, 1, 0xB9]);
+
+        let entries = subtitle_pack_positions(&data);
+
+        assert_eq!(entries, vec![(1_500, 0), (60_000, third)]);
+        assert!(second < third);
+    }
+
+    #[test]
+    fn build_idx_keeps_source_header_and_lists_timestamps() {
+        let extradata =
+            "# VobSub index file, v7\nsize: 720x576\npalette: 000000, ffffff\n\nlangidx: 0\n";
+        let header = vobsub_header(Some(extradata), Some(720), Some(480));
+        assert_eq!(header, "size: 720x576\npalette: 000000, ffffff");
+
+        let idx = build_idx(
+            &header,
+            idx_language(Some("fre")),
+            &[(1_500, 0), (3_723_004, 0x1800)],
+        );
+        assert!(idx.starts_with("# VobSub index file, v7 (do not modify this line!)\n"));
+        assert!(idx.contains("\nid: fr, index: 0\n"));
+        assert!(idx.contains("timestamp: 00:00:01:500, filepos: 000000000\n"));
+        assert!(idx.contains("timestamp: 01:02:03:004, filepos: 000001800\n"));
+
+        let header = vobsub_header(None, Some(720), Some(480));
+        assert!(header.starts_with("size: 720x480\npalette: 000000, f0f0f0"));
+        assert_eq!(idx_language(Some("xyz")), "--");
+    }
+
+    #[test]
+    fn parse_hex_dump_reads_ffprobe_data_lines() {
+        let dump = "\n00000000: 7369 7a65 3a20 3732 3078 3438 300a 6f72  size: 720x480.or\n\
+                    00000010: 673a                                     og:\n";
+
+        assert_eq!(
+            String::from_utf8(parse_hex_dump(dump)).expect("utf-8 expected"),
+            "size: 720x480\norg:"
+        );
+    }
+
+    #[test]
+    fn build_vobsub_args_writes_program_stream_next_to_idx() {
+        let (idx, sub) = vobsub_pair_paths("/tmp/out/movie.eng.sub");
+        assert_eq!(idx, "/tmp/out/movie.eng.idx");
+        assert_eq!(sub, "/tmp/out/movie.eng.sub");
+
+        let args = build_vobsub_args("/tmp/input.mkv", &sub, 4, None);
+        assert!(args.windows(2).any(|w| w == ["-map", "0:4"]));
+        assert!(args.windows(2).any(|w| w == ["-f", "vob"]));
+        assert!(args.windows(2).any(|w| w == ["-muxpreload", "0"]));
+        assert_eq!(args.last(), Some(&sub));
+    }
+
+    #[tokio::test]
+    async fn extract_vobsub_reports_error_when_ffprobe_binary_is_missing() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let input = temp.path().join("input.mkv");
+        std::fs::write(&input, b"media").expect("failed to write input");
+        let output = temp.path().join("subs.idx");
+
+        let error = extract_vobsub_with_bins(
+            None,
+            "/tmp/definitely-not-a-real-ffprobe-binary",
+            "/tmp/definitely-not-a-real-ffmpeg-binary",
+            input.to_string_lossy().as_ref(),
+            &ExtractTrackRequest {
+                track_index: 3,
+                track_type: "subtitle".to_string(),
+                codec: "dvd_subtitle".to_string(),
+                output_path: output.to_string_lossy().to_string(),
+                conversion: None,
+            },
+            &ExtractOptions::default(),
+        )
+        .await
+        .expect_err("missing ffprobe binary should fail");
+
+        assert!(error.contains("Failed to execute ffprobe"));
+        assert!(!output.exists());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn extract_vobsub_keeps_existing_pair_when_no_subtitles_are_found() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let input = temp.path().join("input.mkv");
+        std::fs::write(&input, b"media").expect("failed to write input");
+        let idx = temp.path().join("subs.idx");
+        let sub = temp.path().join("subs.sub");
+        std::fs::write(&idx, b"good idx").expect("failed to write existing .idx");
+        std::fs::write(&sub, b"good sub").expect("failed to write existing .sub");
+
+        // Stand-ins for a DVD subtitle stream whose extraction yields no subtitle packs
+        let write_script = |name: &str, body: &str| {
+            let path = temp.path().join(name);
+            std::fs::write(&path, format!("#!/bin/sh\n{}\n", body))
+                .expect("failed to write fake binary");
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))
+                .expect("failed to make fake binary executable");
+            path.to_string_lossy().to_string()
+        };
+        let ffprobe = write_script(
+            "ffprobe",
+            r#"echo '{"streams": [{"codec_name": "dvd_subtitle"}]}'"#,
+        );
+        let ffmpeg = write_script("ffmpeg", r#"for last; do :; done; : > "$last""#);
+
+        let error = extract_vobsub_with_bins(
+            None,
+            &ffprobe,
+            &ffmpeg,
+            input.to_string_lossy().as_ref(),
+            &ExtractTrackRequest {
+                track_index: 3,
+                track_type: "subtitle".to_string(),
+                codec: "dvd_subtitle".to_string(),
+                output_path: idx.to_string_lossy().to_string(),
+                conversion: None,
+            },
+            &ExtractOptions::default(),
+        )
+        .await
+        .expect_err("an empty .sub should fail");
+
+        assert!(error.contains("No subtitles found"));
+        assert_eq!(
+            std::fs::read(&idx).expect("existing .idx remains"),
+            b"good idx"
+        );
+        assert_eq!(
+            std::fs::read(&sub).expect("existing .sub remains"),
+            b"good sub"
+        );
+        let files = std::fs::read_dir(temp.path())
+            .expect("failed to list tempdir")
+            .count();
+        assert_eq!(files, 5, "staged halves should be removed");
+    }
+}
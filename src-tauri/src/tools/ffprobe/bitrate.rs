@@ -0,0 +1,492 @@
+use crate::shared::process::terminate_process;
+use crate::shared::store::resolve_ffprobe_path;
+use crate::shared::validation::validate_media_path;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::{LazyLock, Mutex};
+use tauri::Emitter;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, BufReader};
+use tokio::process::Command;
+use tokio::time::{Duration, timeout};
+
+/// Reading every packet of a long remux takes a while, but far less than decoding it
+const BITRATE_ANALYSIS_TIMEOUT: Duration = Duration::from_secs(1800);
+
+const DEFAULT_BUCKET_SECS: f64 = 1.0;
+const MIN_BUCKET_SECS: f64 = 0.1;
+const MAX_BUCKET_SECS: f64 = 60.0;
+
+/// ffprobe process IDs keyed by input path for cancellation
+static BITRATE_PROCESS_IDS: LazyLock<Mutex<HashMap<String, u32>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Keyframe spacing of a stream
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct GopSummary {
+    pub(crate) count: usize,
+    pub(crate) min_secs: f64,
+    pub(crate) max_secs: f64,
+    pub(crate) average_secs: f64,
+    pub(crate) max_frames: u32,
+}
+
+/// Bitrate timeline of one stream; bucket `i` covers `start_secs + i * bucket_secs`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct BitrateAnalysis {
+    pub(crate) stream_index: usize,
+    pub(crate) bucket_secs: f64,
+    pub(crate) start_secs: f64,
+    pub(crate) duration_secs: f64,
+    pub(crate) packet_count: u64,
+    pub(crate) total_bytes: u64,
+    /// Bits per second of each bucket
+    pub(crate) buckets: Vec<u64>,
+    pub(crate) average_bitrate: Option<u64>,
+    pub(crate) peak_bitrate: u64,
+    pub(crate) peak_at_secs: f64,
+    /// Presentation times of keyframes, in seconds
+    pub(crate) keyframes: Vec<f64>,
+    /// Packets in each GOP, starting at the keyframe of the same position
+    pub(crate) gop_frames: Vec<u32>,
+    pub(crate) gop: Option<GopSummary>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct PacketSample {
+    pts: Option<f64>,
+    dts: Option<f64>,
+    duration: Option<f64>,
+    size: u64,
+    keyframe: bool,
+}
+
+/// One `-of compact=p=0` packet line: `pts_time=0.04|dts_time=0.00|duration_time=0.04|...`
+fn parse_packet_line(line: &str) -> Option<PacketSample> {
+    let mut sample = PacketSample {
+        pts: None,
+        dts: None,
+        duration: None,
+        size: 0,
+        keyframe: false,
+    };
+    let mut has_size = false;
+    for field in line.trim().split('|') {
+        let Some((key, value)) = field.split_once('=') else {
+            continue;
+        };
+        match key {
+            "pts_time" => sample.pts = value.parse().ok(),
+            "dts_time" => sample.dts = value.parse().ok(),
+            "duration_time" => sample.duration = value.parse().ok(),
+            "size" => {
+                sample.size = value.parse().ok()?;
+                has_size = true;
+            }
+            "flags" => sample.keyframe = value.starts_with('K'),
+            _ => {}
+        }
+    }
+    has_size.then_some(sample)
+}
+
+/// Packets aggregated in decode order
+struct BitrateAccumulator {
+    bucket_secs: f64,
+    start: Option<f64>,
+    /// End of the latest packet, relative to `start`
+    end: f64,
+    bucket_bytes: Vec<u64>,
+    total_bytes: u64,
+    packet_count: u64,
+    keyframes: Vec<f64>,
+    gop_frames: Vec<u32>,
+}
+
+impl BitrateAccumulator {
+    fn new(bucket_secs: f64) -> Self {
+        Self {
+            bucket_secs,
+            start: None,
+            end: 0.0,
+            bucket_bytes: Vec::new(),
+            total_bytes: 0,
+            packet_count: 0,
+            keyframes: Vec::new(),
+            gop_frames: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, packet: &PacketSample) {
+        self.packet_count += 1;
+        self.total_bytes += packet.size;
+
+        // Decode timestamps only move forward, unlike presentation ones with B-frames
+        let offset = packet.dts.or(packet.pts).map(|time| {
+            let start = *self.start.get_or_insert(time);
+            (time - start).max(0.0)
+        });
+        let bucket = match offset {
+            Some(offset) => (offset / self.bucket_secs) as usize,
+            None => self.bucket_bytes.len().saturating_sub(1),
+        };
+        if self.bucket_bytes.len() <= bucket {
+            self.bucket_bytes.resize(bucket + 1, 0);
+        }
+        self.bucket_bytes[bucket] += packet.size;
+        if let Some(offset) = offset {
+            self.end = self.end.max(offset + packet.duration.unwrap_or(0.0));
+        }
+
+        if packet.keyframe
+            && let Some(time) = packet.pts.or(packet.dts)
+        {
+            self.keyframes.push(time);
+            self.gop_frames.push(0);
+        }
+        if let Some(frames) = self.gop_frames.last_mut() {
+            *frames += 1;
+        }
+    }
+
+    /// Share of `duration_us` read so far, kept below 100 until ffprobe exits
+    fn progress(&self, duration_us: Option<u64>) -> Option<i32> {
+        let duration_secs = duration_us.filter(|us| *us > 0)? as f64 / 1_000_000.0;
+        Some(((self.end / duration_secs * 100.0) as i32).clamp(0, 99))
+    }
+
+    fn finish(self, stream_index: usize) -> BitrateAnalysis {
+        let buckets: Vec<u64> = self
+            .bucket_bytes
+            .iter()
+            .map(|bytes| (*bytes as f64 * 8.0 / self.bucket_secs).round() as u64)
+            .collect();
+        let (peak_index, peak_bitrate) = buckets
+            .iter()
+            .copied()
+            .enumerate()
+            .max_by_key(|(_, bitrate)| *bitrate)
+            .unwrap_or((0, 0));
+        let start_secs = self.start.unwrap_or(0.0);
+
+        let mut gop_secs: Vec<f64> = self.keyframes.windows(2).map(|w| w[1] - w[0]).collect();
+        if let Some(last) = self.keyframes.last() {
+            gop_secs.push(start_secs + self.end - last);
+        }
+        let gop = (!gop_secs.is_empty()).then(|| GopSummary {
+            count: gop_secs.len(),
+            min_secs: gop_secs.iter().copied().fold(f64::INFINITY, f64::min),
+            max_secs: gop_secs.iter().copied().fold(0.0, f64::max),
+            average_secs: gop_secs.iter().sum::<f64>() / gop_secs.len() as f64,
+            max_frames: self.gop_frames.iter().copied().max().unwrap_or(0),
+        });
+
+        BitrateAnalysis {
+            stream_index,
+            bucket_secs: self.bucket_secs,
+            start_secs,
+            duration_secs: self.end,
+            packet_count: self.packet_count,
+            total_bytes: self.total_bytes,
+            average_bitrate: (self.end > 0.0)
+                .then(|| (self.total_bytes as f64 * 8.0 / self.end).round() as u64),
+            peak_bitrate,
+            peak_at_secs: peak_index as f64 * self.bucket_secs,
+            buckets,
+            keyframes: self.keyframes,
+            gop_frames: self.gop_frames,
+            gop,
+        }
+    }
+}
+
+fn validate_bucket_secs(bucket_secs: f64) -> Result<(), String> {
+    if !(MIN_BUCKET_SECS..=MAX_BUCKET_SECS).contains(&bucket_secs) {
+        return Err(format!(
+            "Bucket length must be between {} and {} seconds",
+            MIN_BUCKET_SECS, MAX_BUCKET_SECS
+        ));
+    }
+    Ok(())
+}
+
+fn build_packet_args(input_path: &str, stream_index: usize) -> Vec<String> {
+    [
+        "-v",
+        "error",
+        "-select_streams",
+        &stream_index.to_string(),
+        "-show_entries",
+        "packet=pts_time,dts_time,duration_time,size,flags",
+        "-of",
+        "compact=p=0",
+        input_path,
+    ]
+    .iter()
+    .map(|arg| arg.to_string())
+    .collect()
+}
+
+fn clear_bitrate_registration(input_path: &str) -> Option<u32> {
+    BITRATE_PROCESS_IDS
+        .lock()
+        .ok()
+        .and_then(|mut guard| guard.remove(input_path))
+}
+
+fn emit_bitrate_progress(
+    app: &tauri::AppHandle,
+    input_path: &str,
+    stream_index: usize,
+    progress: i32,
+) {
+    let _ = app.emit(
+        "bitrate-progress",
+        json!({
+            "inputPath": input_path,
+            "streamIndex": stream_index,
+            "progress": progress
+        }),
+    );
+}
+
+pub(super) async fn analyze_bitrate_with_ffprobe(
+    app: Option<&tauri::AppHandle>,
+    ffprobe_path: &str,
+    input_path: &str,
+    stream_index: usize,
+    bucket_secs: f64,
+    duration_us: Option<u64>,
+) -> Result<BitrateAnalysis, String> {
+    validate_media_path(input_path)?;
+    validate_bucket_secs(bucket_secs)?;
+
+    let mut child = Command::new(ffprobe_path)
+        .args(build_packet_args(input_path, stream_index))
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            format!(
+                "Failed to execute ffprobe: {}. Make sure FFmpeg is installed.",
+                e
+            )
+        })?;
+
+    if let Some(pid) = child.id()
+        && let Ok(mut guard) = BITRATE_PROCESS_IDS.lock()
+    {
+        guard.insert(input_path.to_string(), pid);
+    }
+    let stdout = child
+        .stdout
+        .take()
+        .ok_or_else(|| "Failed to read ffprobe output".to_string())?;
+    // Drained alongside stdout, so errors about a damaged file cannot fill the pipe and
+    // stall ffprobe
+    let stderr_task = child.stderr.take().map(|mut stderr| {
+        tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            let _ = stderr.read_to_end(&mut buffer).await;
+            buffer
+        })
+    });
+
+    let read_future = async move {
+        let mut accumulator = BitrateAccumulator::new(bucket_secs);
+        let mut last_progress = None;
+        let mut lines = BufReader::new(stdout).lines();
+
+        while let Ok(Some(line)) = lines.next_line().await {
+            let Some(packet) = parse_packet_line(&line) else {
+                continue;
+            };
+            accumulator.push(&packet);
+
+            let progress = accumulator.progress(duration_us);
+            if let Some(app_handle) = app
+                && let Some(progress) = progress
+                && last_progress != Some(progress)
+            {
+                emit_bitrate_progress(app_handle, input_path, stream_index, progress);
+                last_progress = Some(progress);
+            }
+        }
+
+        (accumulator, child.wait().await)
+    };
+
+    let (accumulator, status) = timeout(BITRATE_ANALYSIS_TIMEOUT, read_future)
+        .await
+        .map_err(|_| {
+            if let Some(pid) = clear_bitrate_registration(input_path) {
+                terminate_process(pid);
+            }
+            format!(
+                "Bitrate analysis timeout after {} seconds",
+                BITRATE_ANALYSIS_TIMEOUT.as_secs()
+            )
+        })?;
+
+    // `cancel_analyze_bitrate` removes the registration before killing the process
+    let cancelled = clear_bitrate_registration(input_path).is_none();
+    let status = status.map_err(|e| format!("Failed to execute ffprobe: {}", e))?;
+    if cancelled && !status.success() {
+        return Err("Bitrate analysis cancelled".to_string());
+    }
+    if !status.success() {
+        let stderr = match stderr_task {
+            Some(task) => task.await.unwrap_or_default(),
+            None => Vec::new(),
+        };
+        return Err(format!(
+            "ffprobe failed: {}",
+            String::from_utf8_lossy(&stderr)
+        ));
+    }
+    if accumulator.packet_count == 0 {
+        return Err(format!(
+            "No packets found for stream {} in {}",
+            stream_index, input_path
+        ));
+    }
+
+    if let Some(app_handle) = app {
+        emit_bitrate_progress(app_handle, input_path, stream_index, 100);
+    }
+    Ok(accumulator.finish(stream_index))
+}
+
+/// Per-second (or `bucket_secs`) bitrate, peak and average, and keyframe index of one stream
+/// Emits `bitrate-progress` events when `duration_us` is known
+#[tauri::command]
+pub(crate) async fn analyze_bitrate(
+    app: tauri::AppHandle,
+    input_path: String,
+    stream_index: usize,
+    bucket_secs: Option<f64>,
+    duration_us: Option<u64>,
+) -> Result<BitrateAnalysis, String> {
+    let ffprobe_path = resolve_ffprobe_path(&app)?;
+    analyze_bitrate_with_ffprobe(
+        Some(&app),
+        &ffprobe_path,
+        &input_path,
+        stream_index,
+        bucket_secs.unwrap_or(DEFAULT_BUCKET_SECS),
+        duration_us,
+    )
+    .await
+}
+
+/// Stop the bitrate analysis running on a file
+#[tauri::command]
+pub(crate) async fn cancel_analyze_bitrate(input_path: String) -> Result<(), String> {
+    if let Some(pid) = clear_bitrate_registration(&input_path) {
+        terminate_process(pid);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        BitrateAccumulator, PacketSample, analyze_bitrate_with_ffprobe, build_packet_args,
+        parse_packet_line, validate_bucket_secs,
+    };
+
+    fn packet(dts: f64, pts: f64, size: u64, keyframe: bool) -> PacketSample {
+        PacketSample {
+            pts: Some(pts),
+            dts: Some(dts),
+            duration: Some(0.5),
+            size,
+            keyframe,
+        }
+    }
+
+    #[test]
+    fn parse_packet_line_reads_compact_fields() {
+        let packet = parse_packet_line(
+            "pts_time=1.042000|dts_time=0.959000|duration_time=0.041708|size=18240|flags=K__\n",
+        )
+        .expect("packet expected");
+        assert_eq!(packet.pts, Some(1.042));
+        assert_eq!(packet.dts, Some(0.959));
+        assert_eq!(packet.size, 18_240);
+        assert!(packet.keyframe);
+
+        let packet = parse_packet_line("pts_time=N/A|dts_time=N/A|size=100|flags=___")
+            .expect("packet expected");
+        assert_eq!((packet.pts, packet.dts), (None, None));
+        assert!(!packet.keyframe);
+        assert_eq!(parse_packet_line(""), None);
+    }
+
+    #[test]
+    fn bitrate_accumulator_builds_buckets_peak_and_gops() {
+        let mut accumulator = BitrateAccumulator::new(1.0);
+        for packet in [
+            packet(10.0, 10.0, 1_000, true),
+            packet(10.5, 11.0, 500, false),
+            packet(11.0, 10.5, 4_000, false),
+            packet(11.5, 11.5, 4_000, false),
+            packet(12.0, 12.0, 2_000, true),
+            packet(12.5, 12.5, 500, false),
+        ] {
+            accumulator.push(&packet);
+        }
+        assert_eq!(accumulator.progress(Some(6_000_000)), Some(50));
+
+        let analysis = accumulator.finish(0);
+
+        assert_eq!(analysis.start_secs, 10.0);
+        assert_eq!(analysis.duration_secs, 3.0);
+        assert_eq!(analysis.buckets, vec![12_000, 64_000, 20_000]);
+        assert_eq!(
+            (analysis.peak_bitrate, analysis.peak_at_secs),
+            (64_000, 1.0)
+        );
+        assert_eq!(analysis.average_bitrate, Some(32_000));
+        assert_eq!(analysis.keyframes, vec![10.0, 12.0]);
+        assert_eq!(analysis.gop_frames, vec![4, 2]);
+        let gop = analysis.gop.expect("GOPs expected");
+        assert_eq!((gop.count, gop.min_secs, gop.max_secs), (2, 1.0, 2.0));
+        assert_eq!((gop.average_secs, gop.max_frames), (1.5, 4));
+    }
+
+    #[test]
+    fn validate_bucket_secs_and_packet_args() {
+        assert!(validate_bucket_secs(1.0).is_ok());
+        assert!(validate_bucket_secs(0.0).is_err());
+        assert!(validate_bucket_secs(120.0).is_err());
+
+        let args = build_packet_args("/tmp/input.mkv", 2);
+        assert!(args.windows(2).any(|w| w == ["-select_streams", "2"]));
+        assert!(args.windows(2).any(|w| w == ["-of", "compact=p=0"]));
+        assert_eq!(args.last().map(String::as_str), Some("/tmp/input.mkv"));
+    }
+
+    #[tokio::test]
+    async fn analyze_bitrate_reports_error_when_ffprobe_binary_is_missing() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let input = temp.path().join("input.mkv");
+        std::fs::write(&input, b"media").expect("failed to write input");
+
+        let error = analyze_bitrate_with_ffprobe(
+            None,
+            "/tmp/definitely-not-a-real-ffprobe-binary",
+            input.to_string_lossy().as_ref(),
+            0,
+            1.0,
+            None,
+        )
+        .await
+        .expect_err("missing ffprobe binary should fail");
+
+        assert!(error.contains("Failed to execute ffprobe"));
+    }
+}
@@ -0,0 +1,377 @@
+use crate::shared::store::probe_cache_persist_enabled;
+use crate::tools::ffprobe::probe::probe_json_with_ffprobe;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::UNIX_EPOCH;
+use tauri::Manager;
+use tokio::time::Duration;
+
+/// On-disk store filename, in the app data directory
+const PROBE_CACHE_FILE: &str = "probe-cache.json";
+
+/// Bumped whenever the cached ffprobe output changes shape, which discards older stores
+const PROBE_CACHE_VERSION: u32 = 2;
+
+/// Oldest entries are dropped past this many files
+const MAX_PROBE_CACHE_ENTRIES: usize = 2000;
+
+/// How long changes are collected before the store is written, so a directory scan writes
+/// it once rather than once per file
+const PROBE_CACHE_PERSIST_DELAY: Duration = Duration::from_secs(2);
+
+/// What a probe result is valid for: the file at `path` with this size and modification time
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct ProbeCacheKey {
+    path: String,
+    size: u64,
+    modified_ns: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CachedProbe {
+    size: u64,
+    modified_ns: u64,
+    /// Insertion order, used to evict the oldest entries
+    seq: u64,
+    probe: Value,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProbeCacheStore {
+    version: u32,
+    entries: HashMap<String, CachedProbe>,
+}
+
+#[derive(Default)]
+struct ProbeCache {
+    entries: HashMap<String, CachedProbe>,
+    next_seq: u64,
+    /// Set when the on-disk store is enabled
+    store_path: Option<PathBuf>,
+    /// Set while changes wait for a scheduled write
+    persist_pending: bool,
+}
+
+static PROBE_CACHE: LazyLock<Mutex<ProbeCache>> =
+    LazyLock::new(|| Mutex::new(ProbeCache::default()));
+
+/// Held while the store is written or removed, so writes never overlap
+static PROBE_CACHE_WRITE: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// Numbers the temp file of each write
+static PROBE_CACHE_WRITE_SEQ: AtomicU64 = AtomicU64::new(0);
+
+fn probe_cache_key(path: &str) -> Option<ProbeCacheKey> {
+    let canonical = std::fs::canonicalize(path).ok()?;
+    let metadata = std::fs::metadata(&canonical).ok()?;
+    let modified_ns = metadata
+        .modified()
+        .ok()?
+        .duration_since(UNIX_EPOCH)
+        .ok()?
+        .as_nanos() as u64;
+
+    Some(ProbeCacheKey {
+        path: canonical.to_string_lossy().to_string(),
+        size: metadata.len(),
+        modified_ns,
+    })
+}
+
+impl ProbeCache {
+    /// Cached probe for `key`; an entry for a file that changed since is dropped
+    fn get(&mut self, key: &ProbeCacheKey) -> Option<Value> {
+        let entry = self.entries.get(&key.path)?;
+        if entry.size == key.size && entry.modified_ns == key.modified_ns {
+            return Some(entry.probe.clone());
+        }
+        self.entries.remove(&key.path);
+        None
+    }
+
+    fn insert(&mut self, key: ProbeCacheKey, probe: Value) {
+        while self.entries.len() >= MAX_PROBE_CACHE_ENTRIES {
+            let Some(oldest) = self
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.seq)
+                .map(|(path, _)| path.clone())
+            else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+
+        self.next_seq += 1;
+        self.entries.insert(
+            key.path,
+            CachedProbe {
+                size: key.size,
+                modified_ns: key.modified_ns,
+                seq: self.next_seq,
+                probe,
+            },
+        );
+    }
+
+    /// Record a change; true when no write is scheduled yet and one should be
+    fn mark_dirty(&mut self) -> bool {
+        if self.store_path.is_none() || self.persist_pending {
+            return false;
+        }
+        self.persist_pending = true;
+        true
+    }
+
+    /// Store to write, when persistence is on; later changes schedule another write
+    fn snapshot(&mut self) -> Option<(PathBuf, ProbeCacheStore)> {
+        self.persist_pending = false;
+        let store_path = self.store_path.clone()?;
+        let store = ProbeCacheStore {
+            version: PROBE_CACHE_VERSION,
+            entries: self.entries.clone(),
+        };
+        Some((store_path, store))
+    }
+}
+
+/// Write through a sibling file so a crash never leaves a truncated store
+fn write_store(store_path: &Path, store: &ProbeCacheStore) -> Result<(), String> {
+    let json = serde_json::to_string(store)
+        .map_err(|e| format!("Failed to serialize probe cache: {}", e))?;
+    let seq = PROBE_CACHE_WRITE_SEQ.fetch_add(1, Ordering::Relaxed);
+    let temp_path = store_path.with_extension(format!("json.{}-{}.part", std::process::id(), seq));
+    std::fs::write(&temp_path, json)
+        .and_then(|_| std::fs::rename(&temp_path, store_path))
+        .map_err(|e| {
+            let _ = std::fs::remove_file(&temp_path);
+            format!("Failed to write probe cache: {}", e)
+        })
+}
+
+fn read_store(store_path: &Path) -> HashMap<String, CachedProbe> {
+    std::fs::read(store_path)
+        .ok()
+        .and_then(|bytes| serde_json::from_slice::<ProbeCacheStore>(&bytes).ok())
+        .filter(|store| store.version == PROBE_CACHE_VERSION)
+        .map(|store| store.entries)
+        .unwrap_or_default()
+}
+
+/// Write the current entries to disk, one write at a time and off the async workers
+async fn flush_probe_cache() {
+    let _write = PROBE_CACHE_WRITE.lock().await;
+    let Some((store_path, store)) = PROBE_CACHE
+        .lock()
+        .ok()
+        .and_then(|mut cache| cache.snapshot())
+    else {
+        return;
+    };
+
+    let result = tokio::task::spawn_blocking(move || write_store(&store_path, &store))
+        .await
+        .map_err(|e| format!("Failed to write probe cache: {}", e))
+        .and_then(|result| result);
+    if let Err(error) = result {
+        eprintln!("{}", error);
+    }
+}
+
+/// Write the store once the changes made around now have settled
+fn schedule_persist(cache: &mut ProbeCache) {
+    if !cache.mark_dirty() {
+        return;
+    }
+    tokio::spawn(async {
+        tokio::time::sleep(PROBE_CACHE_PERSIST_DELAY).await;
+        flush_probe_cache().await;
+    });
+}
+
+/// Load the on-disk store when it is enabled in settings
+pub(crate) fn init_probe_cache(app: &tauri::AppHandle) {
+    if !probe_cache_persist_enabled(app) {
+        return;
+    }
+    let Ok(app_data_dir) = app.path().app_data_dir() else {
+        return;
+    };
+    if std::fs::create_dir_all(&app_data_dir).is_err() {
+        return;
+    }
+
+    let store_path = app_data_dir.join(PROBE_CACHE_FILE);
+    let entries = read_store(&store_path);
+    if let Ok(mut cache) = PROBE_CACHE.lock() {
+        cache.next_seq = entries.values().map(|entry| entry.seq).max().unwrap_or(0);
+        cache.entries = entries;
+        cache.store_path = Some(store_path);
+    }
+}
+
+/// ffprobe JSON for `path`, reused while the file keeps its size and modification time
+pub(crate) async fn cached_probe_json(ffprobe_path: &str, path: &str) -> Result<Value, String> {
+    let key = probe_cache_key(path);
+    if let Some(key) = key.as_ref()
+        && let Ok(mut cache) = PROBE_CACHE.lock()
+        && let Some(probe) = cache.get(key)
+    {
+        return Ok(probe);
+    }
+
+    let probe = probe_json_with_ffprobe(ffprobe_path, path).await?;
+    if let Some(key) = key
+        && let Ok(mut cache) = PROBE_CACHE.lock()
+    {
+        cache.insert(key, probe.clone());
+        schedule_persist(&mut cache);
+    }
+    Ok(probe)
+}
+
+/// Forget the cached probe of one file, e.g. after it was rewritten in place
+pub(crate) fn invalidate_probe_cache(path: &str) {
+    let canonical = std::fs::canonicalize(path)
+        .map(|p| p.to_string_lossy().to_string())
+        .unwrap_or_else(|_| path.to_string());
+    if let Ok(mut cache) = PROBE_CACHE.lock()
+        && cache.entries.remove(&canonical).is_some()
+    {
+        schedule_persist(&mut cache);
+    }
+}
+
+/// Drop every cached probe, in memory and on disk
+/// Returns how many entries were removed
+#[tauri::command]
+pub(crate) async fn clear_probe_cache() -> Result<usize, String> {
+    let _write = PROBE_CACHE_WRITE.lock().await;
+    let (removed, store_path) = {
+        let mut cache = PROBE_CACHE
+            .lock()
+            .map_err(|_| "Probe cache is unavailable".to_string())?;
+        let removed = cache.entries.len();
+        cache.entries.clear();
+        (removed, cache.store_path.clone())
+    };
+
+    if let Some(store_path) = store_path
+        && store_path.exists()
+    {
+        std::fs::remove_file(&store_path)
+            .map_err(|e| format!("Failed to remove probe cache: {}", e))?;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MAX_PROBE_CACHE_ENTRIES, PROBE_CACHE_VERSION, ProbeCache, ProbeCacheKey, probe_cache_key,
+        read_store, write_store,
+    };
+    use serde_json::json;
+
+    fn key(path: &str, size: u64, modified_ns: u64) -> ProbeCacheKey {
+        ProbeCacheKey {
+            path: path.to_string(),
+            size,
+            modified_ns,
+        }
+    }
+
+    #[test]
+    fn probe_cache_drops_entries_of_changed_files() {
+        let mut cache = ProbeCache::default();
+        cache.insert(key("/movies/a.mkv", 100, 1), json!({"streams": []}));
+
+        assert_eq!(
+            cache.get(&key("/movies/a.mkv", 100, 1)),
+            Some(json!({"streams": []}))
+        );
+        assert_eq!(cache.get(&key("/movies/a.mkv", 100, 2)), None);
+        // The stale entry is gone even for the original stamp
+        assert_eq!(cache.get(&key("/movies/a.mkv", 100, 1)), None);
+    }
+
+    #[test]
+    fn probe_cache_evicts_oldest_entries_when_full() {
+        let mut cache = ProbeCache::default();
+        for i in 0..=MAX_PROBE_CACHE_ENTRIES {
+            cache.insert(key(&format!("/movies/{}.mkv", i), 1, 1), json!({}));
+        }
+
+        assert_eq!(cache.entries.len(), MAX_PROBE_CACHE_ENTRIES);
+        assert!(!cache.entries.contains_key("/movies/0.mkv"));
+        assert!(cache.entries.contains_key("/movies/1.mkv"));
+    }
+
+    #[test]
+    fn probe_cache_store_round_trips_and_ignores_other_versions() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let store_path = temp.path().join("probe-cache.json");
+        let mut cache = ProbeCache {
+            store_path: Some(store_path.clone()),
+            ..ProbeCache::default()
+        };
+        cache.insert(key("/movies/a.mkv", 100, 7), json!({"format": {}}));
+
+        let (path, store) = cache.snapshot().expect("persistence is enabled");
+        write_store(&path, &store).expect("store should be written");
+        let entries = read_store(&store_path);
+        assert_eq!(entries["/movies/a.mkv"].modified_ns, 7);
+        let files = std::fs::read_dir(temp.path())
+            .expect("failed to list tempdir")
+            .count();
+        assert_eq!(files, 1, "the temp file should be renamed into place");
+
+        std::fs::write(
+            &store_path,
+            format!(
+                r#"{{"version": {}, "entries": {{}}}}"#,
+                PROBE_CACHE_VERSION + 1
+            ),
+        )
+        .expect("failed to write store");
+        assert!(read_store(&store_path).is_empty());
+    }
+
+    #[test]
+    fn probe_cache_schedules_one_write_per_burst_of_changes() {
+        let mut cache = ProbeCache::default();
+        assert!(!cache.mark_dirty(), "nothing is written without a store");
+
+        cache.store_path = Some("/tmp/probe-cache.json".into());
+        assert!(cache.mark_dirty());
+        assert!(!cache.mark_dirty(), "a write is already scheduled");
+
+        cache.snapshot().expect("persistence is enabled");
+        assert!(cache.mark_dirty(), "changes after a write schedule another");
+    }
+
+    #[test]
+    fn probe_cache_key_follows_file_size_and_canonical_path() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let file = temp.path().join("a.mkv");
+        std::fs::write(&file, b"media").expect("failed to write file");
+
+        let first = probe_cache_key(&file.to_string_lossy()).expect("file exists");
+        assert_eq!(first.size, 5);
+        let dotted = temp.path().join(".").join("a.mkv");
+        assert_eq!(
+            probe_cache_key(&dotted.to_string_lossy()).map(|k| k.path),
+            Some(first.path.clone())
+        );
+
+        std::fs::write(&file, b"longer media").expect("failed to rewrite file");
+        assert_ne!(probe_cache_key(&file.to_string_lossy()), Some(first));
+        assert!(probe_cache_key("/definitely/missing.mkv").is_none());
+    }
+}
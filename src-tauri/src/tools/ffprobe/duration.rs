@@ -1,5 +1,5 @@
 use crate::shared::store::resolve_ffprobe_path;
-use tokio::process::Command;
+use crate::tools::ffprobe::probe::probe_file_with_ffprobe;
 
 /// Get media duration in microseconds using ffprobe
 /// This is used to calculate progress percentage during transcoding
@@ -15,32 +15,10 @@ pub(crate) async fn get_media_duration_us_with_ffprobe(
     ffprobe_path: &str,
     path: &str,
 ) -> Result<u64, String> {
-    let output = Command::new(&ffprobe_path)
-        .args([
-            "-v",
-            "error",
-            "-show_entries",
-            "format=duration",
-            "-of",
-            "default=noprint_wrappers=1:nokey=1",
-            path,
-        ])
-        .output()
-        .await
-        .map_err(|e| format!("Failed to run ffprobe: {}", e))?;
-
-    if !output.status.success() {
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("ffprobe failed: {}", stderr));
-    }
-
-    let duration_str = String::from_utf8_lossy(&output.stdout);
-    let duration_secs: f64 = duration_str
-        .trim()
-        .parse()
-        .map_err(|_| format!("Invalid duration: {}", duration_str.trim()))?;
-
-    Ok((duration_secs * 1_000_000.0) as u64)
+    probe_file_with_ffprobe(ffprobe_path, path)
+        .await?
+        .duration_us()
+        .ok_or_else(|| format!("No duration found in {}", path))
 }
 
 #[cfg(test)]
@@ -0,0 +1,357 @@
+use crate::tools::ffprobe::FFPROBE_TIMEOUT;
+use crate::tools::ffprobe::media_info::MediaSideData;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use tokio::process::Command;
+use tokio::time::timeout;
+
+/// Packets of the first video stream read for frame-level HDR metadata
+const HDR_FRAME_SAMPLE: &str = "%+#5";
+
+const MASTERING_DISPLAY_SIDE_DATA: &str = "Mastering display metadata";
+const CONTENT_LIGHT_LEVEL_SIDE_DATA: &str = "Content light level metadata";
+const DOVI_CONFIGURATION_SIDE_DATA: &str = "DOVI configuration record";
+const HDR10_PLUS_SIDE_DATA: &str = "SMPTE2094-40";
+
+/// Transfer characteristics of PQ and HLG content
+const PQ_TRANSFER: &str = "smpte2084";
+const HLG_TRANSFER: &str = "arib-std-b67";
+
+/// Wide colour gamut primaries that HDR content is mastered in
+const BT2020_PRIMARIES: &str = "bt2020";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum HdrFormat {
+    Hdr10,
+    Hdr10Plus,
+    Hlg,
+    DolbyVision,
+}
+
+/// SMPTE ST 2086 mastering display colour volume; chromaticities as CIE 1931 xy, luminance in nits
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MasteringDisplay {
+    pub(crate) red_x: f64,
+    pub(crate) red_y: f64,
+    pub(crate) green_x: f64,
+    pub(crate) green_y: f64,
+    pub(crate) blue_x: f64,
+    pub(crate) blue_y: f64,
+    pub(crate) white_point_x: f64,
+    pub(crate) white_point_y: f64,
+    pub(crate) min_luminance: f64,
+    pub(crate) max_luminance: f64,
+}
+
+/// MaxCLL and MaxFALL, in nits
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ContentLightLevel {
+    pub(crate) max_cll: u32,
+    pub(crate) max_fall: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct DolbyVisionConfig {
+    pub(crate) version_major: u32,
+    pub(crate) version_minor: u32,
+    pub(crate) profile: u32,
+    pub(crate) level: u32,
+    pub(crate) rpu_present: bool,
+    pub(crate) el_present: bool,
+    pub(crate) bl_present: bool,
+    /// Base layer compatibility: 1 for HDR10, 2 for SDR, 4 for HLG
+    pub(crate) bl_signal_compatibility_id: Option<u32>,
+}
+
+/// HDR signalling of a video stream, from its stream and first-frame side data
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MediaHdr {
+    pub(crate) formats: Vec<HdrFormat>,
+    pub(crate) mastering_display: Option<MasteringDisplay>,
+    pub(crate) content_light_level: Option<ContentLightLevel>,
+    pub(crate) dolby_vision: Option<DolbyVisionConfig>,
+}
+
+/// ffprobe prints side data values as integers or `num/den` strings
+fn rational(fields: &Map<String, Value>, key: &str) -> Option<f64> {
+    match fields.get(key)? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => match s.split_once('/') {
+            Some((num, den)) => {
+                let den: f64 = den.trim().parse().ok()?;
+                (den != 0.0).then_some(num.trim().parse::<f64>().ok()? / den)
+            }
+            None => s.trim().parse().ok(),
+        },
+        _ => None,
+    }
+}
+
+fn integer(fields: &Map<String, Value>, key: &str) -> Option<u32> {
+    rational(fields, key).map(|v| v.round() as u32)
+}
+
+fn mastering_display(fields: &Map<String, Value>) -> Option<MasteringDisplay> {
+    Some(MasteringDisplay {
+        red_x: rational(fields, "red_x")?,
+        red_y: rational(fields, "red_y")?,
+        green_x: rational(fields, "green_x")?,
+        green_y: rational(fields, "green_y")?,
+        blue_x: rational(fields, "blue_x")?,
+        blue_y: rational(fields, "blue_y")?,
+        white_point_x: rational(fields, "white_point_x")?,
+        white_point_y: rational(fields, "white_point_y")?,
+        min_luminance: rational(fields, "min_luminance")?,
+        max_luminance: rational(fields, "max_luminance")?,
+    })
+}
+
+fn content_light_level(fields: &Map<String, Value>) -> Option<ContentLightLevel> {
+    Some(ContentLightLevel {
+        max_cll: integer(fields, "max_content")?,
+        max_fall: integer(fields, "max_average")?,
+    })
+}
+
+fn dolby_vision(fields: &Map<String, Value>) -> Option<DolbyVisionConfig> {
+    let flag = |key: &str| integer(fields, key) == Some(1);
+    Some(DolbyVisionConfig {
+        version_major: integer(fields, "dv_version_major").unwrap_or(1),
+        version_minor: integer(fields, "dv_version_minor").unwrap_or(0),
+        profile: integer(fields, "dv_profile")?,
+        level: integer(fields, "dv_level")?,
+        rpu_present: flag("rpu_present_flag"),
+        el_present: flag("el_present_flag"),
+        bl_present: flag("bl_present_flag"),
+        bl_signal_compatibility_id: integer(fields, "dv_bl_signal_compatibility_id"),
+    })
+}
+
+/// HDR info of a video stream; `None` for SDR content
+/// `side_data` lists stream side data first, then that of the first frames
+pub(crate) fn hdr_from_side_data(
+    color_transfer: Option<&str>,
+    side_data: &[MediaSideData],
+) -> Option<MediaHdr> {
+    let find = |side_data_type: &str| {
+        side_data
+            .iter()
+            .find(|entry| entry.side_data_type.contains(side_data_type))
+            .map(|entry| &entry.fields)
+    };
+
+    let mut hdr = MediaHdr {
+        mastering_display: find(MASTERING_DISPLAY_SIDE_DATA).and_then(mastering_display),
+        content_light_level: find(CONTENT_LIGHT_LEVEL_SIDE_DATA).and_then(content_light_level),
+        dolby_vision: find(DOVI_CONFIGURATION_SIDE_DATA).and_then(dolby_vision),
+        ..MediaHdr::default()
+    };
+    if hdr.dolby_vision.is_some() {
+        hdr.formats.push(HdrFormat::DolbyVision);
+    }
+    if find(HDR10_PLUS_SIDE_DATA).is_some() {
+        hdr.formats.push(HdrFormat::Hdr10Plus);
+    }
+    match color_transfer {
+        Some(PQ_TRANSFER) => hdr.formats.push(HdrFormat::Hdr10),
+        Some(HLG_TRANSFER) => hdr.formats.push(HdrFormat::Hlg),
+        _ => {}
+    }
+
+    (hdr != MediaHdr::default()).then_some(hdr)
+}
+
+/// Bit depth from `bits_per_raw_sample`, else from the pixel format (`yuv420p10le` is 10)
+pub(crate) fn bit_depth(
+    bits_per_raw_sample: Option<u32>,
+    pixel_format: Option<&str>,
+) -> Option<u32> {
+    if let Some(bits) = bits_per_raw_sample.filter(|bits| *bits > 0) {
+        return Some(bits);
+    }
+
+    let pixel_format = pixel_format?;
+    let base = pixel_format
+        .strip_suffix("le")
+        .or_else(|| pixel_format.strip_suffix("be"))
+        .unwrap_or(pixel_format);
+    let digits_start = base.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    let (prefix, digits) = base.split_at(digits_start);
+    if prefix.ends_with('p') && !digits.is_empty() {
+        return digits.parse().ok();
+    }
+    // Planar YUV without a depth suffix is 8-bit
+    (digits.is_empty() && prefix.starts_with("yuv") && prefix.ends_with('p')).then_some(8)
+}
+
+/// Whether a probed video stream signals HDR, so that its frames are worth probing
+/// Looks at the transfer and primaries, and for a Dolby Vision configuration record
+pub(crate) fn stream_signals_hdr(stream: &Value) -> bool {
+    let field = |key: &str| stream.get(key).and_then(|v| v.as_str());
+    let has_dovi_record = stream
+        .get("side_data_list")
+        .and_then(|list| list.as_array())
+        .is_some_and(|list| {
+            list.iter().any(|entry| {
+                entry
+                    .get("side_data_type")
+                    .and_then(|v| v.as_str())
+                    .is_some_and(|kind| kind.contains(DOVI_CONFIGURATION_SIDE_DATA))
+            })
+        });
+
+    matches!(field("color_transfer"), Some(PQ_TRANSFER | HLG_TRANSFER))
+        || field("color_primaries") == Some(BT2020_PRIMARIES)
+        || has_dovi_record
+}
+
+/// Frames of the first video stream, with their colour properties and side data
+pub(crate) async fn probe_hdr_frames(ffprobe_path: &str, path: &str) -> Result<Value, String> {
+    let probe_future = async move {
+        Command::new(ffprobe_path)
+            .args([
+                "-v",
+                "quiet",
+                "-print_format",
+                "json",
+                "-select_streams",
+                "v:0",
+                "-read_intervals",
+                HDR_FRAME_SAMPLE,
+                "-show_frames",
+                "-show_entries",
+                "frame=stream_index,color_primaries,color_transfer,color_space,side_data_list",
+                path,
+            ])
+            .output()
+            .await
+    };
+
+    let output = timeout(FFPROBE_TIMEOUT, probe_future)
+        .await
+        .map_err(|_| {
+            format!(
+                "FFprobe timeout after {} seconds",
+                FFPROBE_TIMEOUT.as_secs()
+            )
+        })?
+        .map_err(|e| format!("Failed to execute ffprobe: {}", e))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffprobe failed: {}", stderr));
+    }
+
+    let probe: Value = serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse probe output: {}", e))?;
+    Ok(probe
+        .get("frames")
+        .cloned()
+        .unwrap_or(Value::Array(Vec::new())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HdrFormat, bit_depth, hdr_from_side_data, stream_signals_hdr};
+    use crate::tools::ffprobe::media_info::MediaSideData;
+    use serde_json::{Value, json};
+
+    fn side_data(value: Value) -> MediaSideData {
+        let mut fields = value.as_object().cloned().expect("object expected");
+        let side_data_type = fields
+            .remove("side_data_type")
+            .and_then(|v| v.as_str().map(str::to_string))
+            .expect("side data type expected");
+        MediaSideData {
+            side_data_type,
+            fields,
+        }
+    }
+
+    #[test]
+    fn hdr_from_side_data_reads_mastering_display_cll_and_dolby_vision() {
+        let entries = vec![
+            side_data(json!({
+                "side_data_type": "DOVI configuration record",
+                "dv_version_major": 1, "dv_version_minor": 0, "dv_profile": 8, "dv_level": 6,
+                "rpu_present_flag": 1, "el_present_flag": 0, "bl_present_flag": 1,
+                "dv_bl_signal_compatibility_id": 1
+            })),
+            side_data(json!({
+                "side_data_type": "Mastering display metadata",
+                "red_x": "34000/50000", "red_y": "16000/50000",
+                "green_x": "13250/50000", "green_y": "34500/50000",
+                "blue_x": "7500/50000", "blue_y": "3000/50000",
+                "white_point_x": "15635/50000", "white_point_y": "16450/50000",
+                "min_luminance": "50/10000", "max_luminance": "10000000/10000"
+            })),
+            side_data(json!({
+                "side_data_type": "Content light level metadata",
+                "max_content": 1000, "max_average": 400
+            })),
+        ];
+
+        let hdr = hdr_from_side_data(Some("smpte2084"), &entries).expect("HDR expected");
+
+        assert_eq!(hdr.formats, vec![HdrFormat::DolbyVision, HdrFormat::Hdr10]);
+        let display = hdr.mastering_display.expect("mastering display expected");
+        assert_eq!((display.red_x, display.green_y), (0.68, 0.69));
+        assert_eq!(
+            (display.min_luminance, display.max_luminance),
+            (0.005, 1000.0)
+        );
+        let cll = hdr.content_light_level.expect("light level expected");
+        assert_eq!((cll.max_cll, cll.max_fall), (1000, 400));
+        let dovi = hdr.dolby_vision.expect("Dolby Vision expected");
+        assert_eq!((dovi.profile, dovi.level), (8, 6));
+        assert!(dovi.rpu_present && dovi.bl_present && !dovi.el_present);
+        assert_eq!(dovi.bl_signal_compatibility_id, Some(1));
+    }
+
+    #[test]
+    fn hdr_from_side_data_detects_hlg_hdr10_plus_and_sdr() {
+        let hlg = hdr_from_side_data(Some("arib-std-b67"), &[]).expect("HLG expected");
+        assert_eq!(hlg.formats, vec![HdrFormat::Hlg]);
+
+        let plus = vec![side_data(json!({
+            "side_data_type": "HDR Dynamic Metadata SMPTE2094-40 (HDR10+)",
+            "application_version": 1
+        }))];
+        let hdr = hdr_from_side_data(Some("smpte2084"), &plus).expect("HDR10+ expected");
+        assert_eq!(hdr.formats, vec![HdrFormat::Hdr10Plus, HdrFormat::Hdr10]);
+
+        assert_eq!(hdr_from_side_data(Some("bt709"), &[]), None);
+    }
+
+    #[test]
+    fn stream_signals_hdr_from_colour_properties_or_dolby_vision() {
+        assert!(stream_signals_hdr(&json!({"color_transfer": "smpte2084"})));
+        assert!(stream_signals_hdr(
+            &json!({"color_transfer": "arib-std-b67"})
+        ));
+        assert!(stream_signals_hdr(&json!({"color_primaries": "bt2020"})));
+        assert!(stream_signals_hdr(&json!({
+            "side_data_list": [{"side_data_type": "DOVI configuration record"}]
+        })));
+        assert!(!stream_signals_hdr(&json!({
+            "color_transfer": "bt709",
+            "color_primaries": "bt709"
+        })));
+        assert!(!stream_signals_hdr(&json!({"codec_type": "video"})));
+    }
+
+    #[test]
+    fn bit_depth_prefers_raw_sample_bits_then_pixel_format() {
+        assert_eq!(bit_depth(Some(12), Some("yuv420p10le")), Some(12));
+        assert_eq!(bit_depth(None, Some("yuv420p10le")), Some(10));
+        assert_eq!(bit_depth(None, Some("p010le")), Some(10));
+        assert_eq!(bit_depth(Some(0), Some("yuv420p")), Some(8));
+        assert_eq!(bit_depth(None, Some("nv12")), None);
+        assert_eq!(bit_depth(None, None), None);
+    }
+}
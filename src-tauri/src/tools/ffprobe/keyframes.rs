@@ -0,0 +1,205 @@
+use crate::shared::clip::{
+    ClipCut, ClipMode, ClipRange, keyframes_from_packets, snap_to_keyframes,
+};
+use crate::shared::store::resolve_ffprobe_path;
+use crate::shared::validation::validate_media_path;
+use crate::tools::ffprobe::FFPROBE_TIMEOUT;
+use serde_json::Value;
+use tokio::process::Command;
+use tokio::time::timeout;
+
+/// How far around each boundary keyframes are looked for, in seconds
+const KEYFRAME_SEARCH_WINDOW_SECS: f64 = 60.0;
+
+/// `-read_intervals` covering the window before the start and after the end of a range
+/// Intervals are absolute timestamps, so the range is shifted by the file's start time
+fn keyframe_read_intervals(range: &ClipRange, start_time_secs: f64) -> String {
+    let start = start_time_secs + range.start_ms as f64 / 1000.0;
+    let mut intervals = format!(
+        "{:.3}%{:.3}",
+        (start - KEYFRAME_SEARCH_WINDOW_SECS).max(start_time_secs),
+        start + 0.001
+    );
+    if let Some(end_ms) = range.end_ms {
+        intervals.push_str(&format!(
+            ",{:.3}%+{}",
+            start_time_secs + end_ms as f64 / 1000.0,
+            KEYFRAME_SEARCH_WINDOW_SECS
+        ));
+    }
+    intervals
+}
+
+/// Run ffprobe with `args` and parse its JSON output
+async fn run_ffprobe_json(ffprobe_path: &str, args: &[&str]) -> Result<Value, String> {
+    let probe_future = async move { Command::new(ffprobe_path).args(args).output().await };
+
+    let output = timeout(FFPROBE_TIMEOUT, probe_future)
+        .await
+        .map_err(|_| {
+            format!(
+                "FFprobe timeout after {} seconds",
+                FFPROBE_TIMEOUT.as_secs()
+            )
+        })?
+        .map_err(|e| {
+            format!(
+                "Failed to execute ffprobe: {}. Make sure FFmpeg is installed.",
+                e
+            )
+        })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(format!("ffprobe failed: {}", stderr));
+    }
+
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse probe output: {}", e))
+}
+
+/// Start time of a file in seconds, which ffmpeg's `-ss` and cut points are relative to
+async fn probe_start_time(ffprobe_path: &str, path: &str) -> Result<f64, String> {
+    let probe = run_ffprobe_json(
+        ffprobe_path,
+        &[
+            "-v",
+            "quiet",
+            "-show_entries",
+            "format=start_time",
+            "-print_format",
+            "json",
+            path,
+        ],
+    )
+    .await?;
+    Ok(probe
+        .get("format")
+        .and_then(|format| format.get("start_time"))
+        .and_then(|start| start.as_str())
+        .and_then(|start| start.parse::<f64>().ok())
+        .unwrap_or(0.0))
+}
+
+/// Keyframe timestamps (ms from the file's start) of one stream near the boundaries of a range
+async fn probe_keyframes(
+    ffprobe_path: &str,
+    path: &str,
+    stream_specifier: &str,
+    range: &ClipRange,
+) -> Result<Vec<u64>, String> {
+    let start_time_secs = probe_start_time(ffprobe_path, path).await?;
+    let intervals = keyframe_read_intervals(range, start_time_secs);
+    let probe = run_ffprobe_json(
+        ffprobe_path,
+        &[
+            "-v",
+            "quiet",
+            "-select_streams",
+            stream_specifier,
+            "-read_intervals",
+            &intervals,
+            "-show_entries",
+            "packet=pts_time,flags",
+            "-print_format",
+            "json",
+            path,
+        ],
+    )
+    .await?;
+    Ok(keyframes_from_packets(&probe, start_time_secs))
+}
+
+/// Cut points for a range: snapped to the keyframes of `stream_specifier` in keyframe mode,
+/// exactly as requested in accurate mode
+pub(crate) async fn resolve_clip(
+    ffprobe_path: &str,
+    path: &str,
+    stream_specifier: &str,
+    range: &ClipRange,
+) -> Result<ClipCut, String> {
+    range.validate()?;
+    match range.mode {
+        ClipMode::Accurate => Ok(ClipCut::exact(range)),
+        ClipMode::Keyframe => {
+            let keyframes = probe_keyframes(ffprobe_path, path, stream_specifier, range).await?;
+            Ok(snap_to_keyframes(range, &keyframes))
+        }
+    }
+}
+
+/// Preview where a clip would actually be cut
+/// Keyframes come from `stream_index`, or the first video stream by default
+#[tauri::command]
+pub(crate) async fn resolve_clip_cut(
+    app: tauri::AppHandle,
+    input_path: String,
+    clip: ClipRange,
+    stream_index: Option<usize>,
+) -> Result<ClipCut, String> {
+    validate_media_path(&input_path)?;
+    let ffprobe_path = resolve_ffprobe_path(&app)?;
+    let stream_specifier = stream_index.map_or("v:0".to_string(), |index| index.to_string());
+    resolve_clip(&ffprobe_path, &input_path, &stream_specifier, &clip).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{keyframe_read_intervals, resolve_clip};
+    use crate::shared::clip::{ClipMode, ClipRange};
+
+    #[test]
+    fn keyframe_read_intervals_cover_both_boundaries() {
+        let range = ClipRange {
+            start_ms: 90_500,
+            end_ms: Some(120_000),
+            mode: ClipMode::Keyframe,
+        };
+        assert_eq!(
+            keyframe_read_intervals(&range, 0.0),
+            "30.500%90.501,120.000%+60"
+        );
+
+        let range = ClipRange {
+            start_ms: 10_000,
+            end_ms: None,
+            mode: ClipMode::Keyframe,
+        };
+        assert_eq!(keyframe_read_intervals(&range, 0.0), "0.000%10.001");
+
+        // A file starting at 1.4s is probed at its absolute timestamps
+        assert_eq!(keyframe_read_intervals(&range, 1.4), "1.400%11.401");
+    }
+
+    #[tokio::test]
+    async fn resolve_clip_skips_probing_in_accurate_mode() {
+        let range = ClipRange {
+            start_ms: 1_000,
+            end_ms: Some(2_000),
+            mode: ClipMode::Accurate,
+        };
+        let cut = resolve_clip(
+            "/tmp/definitely-not-a-real-ffprobe-binary",
+            "/tmp/x.mkv",
+            "v:0",
+            &range,
+        )
+        .await
+        .expect("accurate mode should not run ffprobe");
+        assert_eq!((cut.start_ms, cut.end_ms), (1_000, Some(2_000)));
+
+        let keyframe_range = ClipRange {
+            mode: ClipMode::Keyframe,
+            ..range
+        };
+        let error = resolve_clip(
+            "/tmp/definitely-not-a-real-ffprobe-binary",
+            "/tmp/x.mkv",
+            "v:0",
+            &keyframe_range,
+        )
+        .await
+        .expect_err("missing ffprobe binary should fail");
+        assert!(error.contains("Failed to execute ffprobe"));
+    }
+}
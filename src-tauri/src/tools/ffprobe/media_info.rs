@@ -0,0 +1,569 @@
+use crate::tools::ffprobe::hdr::{MediaHdr, bit_depth, hdr_from_side_data};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Version of the `MediaInfo` schema sent to the UI, bumped on breaking changes
+pub(crate) const MEDIA_INFO_SCHEMA_VERSION: u32 = 1;
+
+/// Everything ffprobe reports about a file, normalized once for every tool and the UI
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MediaInfo {
+    pub(crate) schema_version: u32,
+    pub(crate) path: String,
+    pub(crate) format: MediaFormat,
+    pub(crate) streams: Vec<MediaStream>,
+    pub(crate) chapters: Vec<MediaChapter>,
+    pub(crate) attachments: Vec<MediaAttachment>,
+    pub(crate) closed_captions: Vec<MediaClosedCaptions>,
+}
+
+/// Container-level information
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MediaFormat {
+    pub(crate) format_name: Option<String>,
+    pub(crate) format_long_name: Option<String>,
+    pub(crate) duration_secs: Option<f64>,
+    pub(crate) start_time_secs: Option<f64>,
+    pub(crate) size_bytes: Option<u64>,
+    pub(crate) bit_rate: Option<u64>,
+    pub(crate) tags: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum MediaStreamKind {
+    Video,
+    Audio,
+    Subtitle,
+    Data,
+    Attachment,
+    Unknown,
+}
+
+impl MediaStreamKind {
+    fn from_codec_type(codec_type: Option<&str>) -> Self {
+        match codec_type {
+            Some("video") => Self::Video,
+            Some("audio") => Self::Audio,
+            Some("subtitle") => Self::Subtitle,
+            Some("data") => Self::Data,
+            Some("attachment") => Self::Attachment,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+/// Disposition flags ffprobe reports as 0/1
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MediaDisposition {
+    pub(crate) default: bool,
+    pub(crate) dub: bool,
+    pub(crate) original: bool,
+    pub(crate) comment: bool,
+    pub(crate) lyrics: bool,
+    pub(crate) karaoke: bool,
+    pub(crate) forced: bool,
+    pub(crate) hearing_impaired: bool,
+    pub(crate) visual_impaired: bool,
+    pub(crate) clean_effects: bool,
+    pub(crate) attached_pic: bool,
+    pub(crate) captions: bool,
+    pub(crate) descriptions: bool,
+    pub(crate) metadata: bool,
+    pub(crate) dependent: bool,
+    pub(crate) still_image: bool,
+}
+
+/// One entry of a stream's `side_data_list`, such as a display matrix or HDR metadata
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MediaSideData {
+    pub(crate) side_data_type: String,
+    pub(crate) fields: Map<String, Value>,
+}
+
+/// One stream; fields that don't apply to its kind are `None`
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MediaStream {
+    pub(crate) index: usize,
+    pub(crate) kind: MediaStreamKind,
+    pub(crate) codec_name: Option<String>,
+    pub(crate) codec_long_name: Option<String>,
+    pub(crate) profile: Option<String>,
+    pub(crate) language: Option<String>,
+    pub(crate) title: Option<String>,
+    pub(crate) duration_secs: Option<f64>,
+    pub(crate) bit_rate: Option<u64>,
+    pub(crate) size_bytes: Option<u64>,
+    pub(crate) frame_count: Option<u64>,
+    pub(crate) width: Option<u32>,
+    pub(crate) height: Option<u32>,
+    pub(crate) frame_rate: Option<String>,
+    pub(crate) avg_frame_rate: Option<String>,
+    pub(crate) pixel_format: Option<String>,
+    pub(crate) field_order: Option<String>,
+    pub(crate) sample_aspect_ratio: Option<String>,
+    pub(crate) display_aspect_ratio: Option<String>,
+    pub(crate) color_range: Option<String>,
+    pub(crate) color_space: Option<String>,
+    pub(crate) color_transfer: Option<String>,
+    pub(crate) color_primaries: Option<String>,
+    pub(crate) chroma_location: Option<String>,
+    pub(crate) bit_depth: Option<u32>,
+    /// Set for PQ, HLG and Dolby Vision video
+    pub(crate) hdr: Option<MediaHdr>,
+    pub(crate) channels: Option<u32>,
+    pub(crate) channel_layout: Option<String>,
+    pub(crate) sample_rate: Option<u32>,
+    pub(crate) closed_captions: bool,
+    pub(crate) disposition: MediaDisposition,
+    pub(crate) tags: BTreeMap<String, String>,
+    pub(crate) side_data: Vec<MediaSideData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MediaChapter {
+    pub(crate) id: i64,
+    pub(crate) start_secs: f64,
+    pub(crate) end_secs: f64,
+    pub(crate) title: Option<String>,
+    pub(crate) tags: BTreeMap<String, String>,
+}
+
+/// A file embedded in the container, such as an ASS font or cover image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MediaAttachment {
+    /// Absolute stream index, as used by `-dump_attachment:<index>`
+    pub(crate) index: usize,
+    pub(crate) filename: Option<String>,
+    pub(crate) mimetype: Option<String>,
+    pub(crate) size_bytes: Option<u64>,
+}
+
+/// A video stream carrying EIA-608/708 captions inside its frames
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MediaClosedCaptions {
+    pub(crate) stream_index: usize,
+    pub(crate) video_codec: Option<String>,
+}
+
+fn str_field(value: &Value, key: &str) -> Option<String> {
+    value
+        .get(key)
+        .and_then(|v| v.as_str())
+        .filter(|v| !v.is_empty() && *v != "N/A" && *v != "unknown")
+        .map(str::to_string)
+}
+
+/// ffprobe prints most numbers as strings; accept both
+fn number_field<T: std::str::FromStr>(value: &Value, key: &str) -> Option<T> {
+    match value.get(key)? {
+        Value::String(s) => s.trim().parse().ok(),
+        Value::Number(n) => n.to_string().parse().ok(),
+        _ => None,
+    }
+}
+
+fn tags_of(value: &Value) -> BTreeMap<String, String> {
+    value
+        .get("tags")
+        .and_then(|tags| tags.as_object())
+        .map(|tags| {
+            tags.iter()
+                .filter_map(|(key, v)| Some((key.clone(), v.as_str()?.to_string())))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Tag lookup ignoring case, trying the Matroska `-eng` statistics variant too
+fn tag<'a>(tags: &'a BTreeMap<String, String>, key: &str) -> Option<&'a str> {
+    let eng = format!("{}-eng", key);
+    tags.iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(key) || name.eq_ignore_ascii_case(&eng))
+        .map(|(_, v)| v.as_str())
+        .filter(|v| !v.is_empty())
+}
+
+/// Seconds of a Matroska `DURATION` tag (`HH:MM:SS.nnnnnnnnn`)
+fn parse_tag_duration(value: &str) -> Option<f64> {
+    let mut parts = value.trim().splitn(3, ':');
+    let hours: f64 = parts.next()?.parse().ok()?;
+    let minutes: f64 = parts.next()?.parse().ok()?;
+    let seconds: f64 = parts.next()?.parse().ok()?;
+    Some(hours * 3600.0 + minutes * 60.0 + seconds)
+}
+
+fn disposition_of(stream: &Value) -> MediaDisposition {
+    let flag = |key: &str| {
+        stream
+            .get("disposition")
+            .and_then(|d| d.get(key))
+            .and_then(|v| v.as_u64())
+            == Some(1)
+    };
+    MediaDisposition {
+        default: flag("default"),
+        dub: flag("dub"),
+        original: flag("original"),
+        comment: flag("comment"),
+        lyrics: flag("lyrics"),
+        karaoke: flag("karaoke"),
+        forced: flag("forced"),
+        hearing_impaired: flag("hearing_impaired"),
+        visual_impaired: flag("visual_impaired"),
+        clean_effects: flag("clean_effects"),
+        attached_pic: flag("attached_pic"),
+        captions: flag("captions"),
+        descriptions: flag("descriptions"),
+        metadata: flag("metadata"),
+        dependent: flag("dependent"),
+        still_image: flag("still_image"),
+    }
+}
+
+fn side_data_of(value: &Value) -> Vec<MediaSideData> {
+    value
+        .get("side_data_list")
+        .and_then(|list| list.as_array())
+        .map(|list| {
+            list.iter()
+                .filter_map(|entry| {
+                    let mut fields = entry.as_object()?.clone();
+                    let side_data_type = fields.remove("side_data_type")?.as_str()?.to_string();
+                    Some(MediaSideData {
+                        side_data_type,
+                        fields,
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+impl MediaStream {
+    /// `frames` are the first decoded frames of this stream, when they were probed
+    fn from_probe(stream: &Value, frames: &[&Value]) -> Option<Self> {
+        let tags = tags_of(stream);
+        let tag_number = |key: &str| tag(&tags, key).and_then(|v| v.parse::<u64>().ok());
+        // Containers often leave colour properties to the bitstream, where frames expose them
+        let color = |key: &str| {
+            str_field(stream, key).or_else(|| frames.iter().find_map(|f| str_field(f, key)))
+        };
+        let kind =
+            MediaStreamKind::from_codec_type(stream.get("codec_type").and_then(|v| v.as_str()));
+        let color_transfer = color("color_transfer");
+        let pixel_format = str_field(stream, "pix_fmt");
+
+        let hdr = if kind == MediaStreamKind::Video {
+            let mut side_data = side_data_of(stream);
+            side_data.extend(frames.iter().flat_map(|frame| side_data_of(frame)));
+            hdr_from_side_data(color_transfer.as_deref(), &side_data)
+        } else {
+            None
+        };
+
+        Some(Self {
+            index: stream.get("index")?.as_u64()? as usize,
+            kind,
+            codec_name: str_field(stream, "codec_name"),
+            codec_long_name: str_field(stream, "codec_long_name"),
+            profile: str_field(stream, "profile"),
+            language: tag(&tags, "language").map(str::to_string),
+            title: tag(&tags, "title").map(str::to_string),
+            duration_secs: number_field(stream, "duration")
+                .or_else(|| tag(&tags, "DURATION").and_then(parse_tag_duration)),
+            bit_rate: number_field(stream, "bit_rate").or_else(|| tag_number("BPS")),
+            size_bytes: tag_number("NUMBER_OF_BYTES"),
+            frame_count: number_field(stream, "nb_frames")
+                .or_else(|| tag_number("NUMBER_OF_FRAMES")),
+            width: number_field(stream, "width"),
+            height: number_field(stream, "height"),
+            frame_rate: str_field(stream, "r_frame_rate").filter(|rate| rate != "0/0"),
+            avg_frame_rate: str_field(stream, "avg_frame_rate").filter(|rate| rate != "0/0"),
+            bit_depth: if kind == MediaStreamKind::Video {
+                bit_depth(
+                    number_field(stream, "bits_per_raw_sample"),
+                    pixel_format.as_deref(),
+                )
+            } else {
+                None
+            },
+            pixel_format,
+            field_order: str_field(stream, "field_order"),
+            sample_aspect_ratio: str_field(stream, "sample_aspect_ratio"),
+            display_aspect_ratio: str_field(stream, "display_aspect_ratio"),
+            color_range: str_field(stream, "color_range"),
+            color_space: color("color_space"),
+            color_transfer,
+            color_primaries: color("color_primaries"),
+            chroma_location: str_field(stream, "chroma_location"),
+            hdr,
+            channels: number_field(stream, "channels"),
+            channel_layout: str_field(stream, "channel_layout"),
+            sample_rate: number_field(stream, "sample_rate"),
+            closed_captions: stream.get("closed_captions").and_then(|v| v.as_u64()) == Some(1),
+            disposition: disposition_of(stream),
+            side_data: side_data_of(stream),
+            tags,
+        })
+    }
+}
+
+impl MediaFormat {
+    fn from_probe(format: &Value) -> Self {
+        Self {
+            format_name: str_field(format, "format_name"),
+            format_long_name: str_field(format, "format_long_name"),
+            duration_secs: number_field(format, "duration"),
+            start_time_secs: number_field(format, "start_time"),
+            size_bytes: number_field(format, "size"),
+            bit_rate: number_field(format, "bit_rate"),
+            tags: tags_of(format),
+        }
+    }
+}
+
+impl MediaChapter {
+    fn from_probe(chapter: &Value) -> Option<Self> {
+        let tags = tags_of(chapter);
+        Some(Self {
+            id: chapter
+                .get("id")
+                .and_then(|v| v.as_i64())
+                .unwrap_or_default(),
+            start_secs: number_field(chapter, "start_time")?,
+            end_secs: number_field(chapter, "end_time")?,
+            title: tag(&tags, "title").map(str::to_string),
+            tags,
+        })
+    }
+}
+
+fn probe_list<'a>(probe: &'a Value, key: &str) -> &'a [Value] {
+    probe
+        .get(key)
+        .and_then(|list| list.as_array())
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+}
+
+/// Attachment streams of an ffprobe result produced with `-show_streams`
+pub(crate) fn attachments_from_probe(probe: &Value) -> Vec<MediaAttachment> {
+    probe_list(probe, "streams")
+        .iter()
+        .filter(|stream| stream.get("codec_type").and_then(|v| v.as_str()) == Some("attachment"))
+        .filter_map(|stream| {
+            let tags = tags_of(stream);
+            Some(MediaAttachment {
+                index: stream.get("index")?.as_u64()? as usize,
+                filename: tag(&tags, "filename").map(str::to_string),
+                mimetype: tag(&tags, "mimetype").map(str::to_string),
+                size_bytes: stream.get("extradata_size").and_then(|v| v.as_u64()),
+            })
+        })
+        .collect()
+}
+
+/// Video streams ffprobe flagged with `closed_captions`
+pub(crate) fn closed_captions_from_probe(probe: &Value) -> Vec<MediaClosedCaptions> {
+    probe_list(probe, "streams")
+        .iter()
+        .filter(|stream| {
+            stream.get("codec_type").and_then(|v| v.as_str()) == Some("video")
+                && stream.get("closed_captions").and_then(|v| v.as_u64()) == Some(1)
+        })
+        .filter_map(|stream| {
+            Some(MediaClosedCaptions {
+                stream_index: stream.get("index")?.as_u64()? as usize,
+                video_codec: str_field(stream, "codec_name"),
+            })
+        })
+        .collect()
+}
+
+impl MediaInfo {
+    /// Build from ffprobe JSON produced with `-show_streams -show_format -show_chapters`, plus
+    /// the `frames` of `probe_hdr_frames` when present
+    pub(crate) fn from_probe(path: &str, probe: &Value) -> Self {
+        let frames = probe_list(probe, "frames");
+        let frames_of = |stream: &Value| -> Vec<&Value> {
+            let index = stream.get("index").and_then(|v| v.as_u64());
+            frames
+                .iter()
+                .filter(|frame| frame.get("stream_index").and_then(|v| v.as_u64()) == index)
+                .collect()
+        };
+
+        Self {
+            schema_version: MEDIA_INFO_SCHEMA_VERSION,
+            path: path.to_string(),
+            format: probe
+                .get("format")
+                .map(MediaFormat::from_probe)
+                .unwrap_or_default(),
+            streams: probe_list(probe, "streams")
+                .iter()
+                .filter_map(|stream| MediaStream::from_probe(stream, &frames_of(stream)))
+                .collect(),
+            chapters: probe_list(probe, "chapters")
+                .iter()
+                .filter_map(MediaChapter::from_probe)
+                .collect(),
+            attachments: attachments_from_probe(probe),
+            closed_captions: closed_captions_from_probe(probe),
+        }
+    }
+
+    pub(crate) fn duration_us(&self) -> Option<u64> {
+        self.format
+            .duration_secs
+            .map(|secs| (secs * 1_000_000.0) as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MEDIA_INFO_SCHEMA_VERSION, MediaInfo, MediaStreamKind, attachments_from_probe,
+        closed_captions_from_probe,
+    };
+    use crate::tools::ffprobe::hdr::HdrFormat;
+    use serde_json::json;
+
+    #[test]
+    fn attachments_from_probe_lists_attachment_streams() {
+        let probe = json!({"streams": [
+            {"index": 0, "codec_type": "video", "codec_name": "h264"},
+            {"index": 3, "codec_type": "attachment", "codec_name": "ttf", "extradata_size": 1024,
+             "tags": {"filename": "Arial.ttf", "mimetype": "application/x-truetype-font"}},
+            {"index": 4, "codec_type": "attachment", "tags": {"filename": ""}}
+        ]});
+
+        let attachments = attachments_from_probe(&probe);
+
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(attachments[0].index, 3);
+        assert_eq!(attachments[0].filename.as_deref(), Some("Arial.ttf"));
+        assert_eq!(
+            attachments[0].mimetype.as_deref(),
+            Some("application/x-truetype-font")
+        );
+        assert_eq!(attachments[0].size_bytes, Some(1024));
+        assert_eq!(attachments[1].filename, None);
+    }
+
+    #[test]
+    fn closed_captions_from_probe_lists_flagged_video_streams() {
+        let probe = json!({"streams": [
+            {"index": 0, "codec_type": "video", "codec_name": "h264", "closed_captions": 1},
+            {"index": 1, "codec_type": "video", "codec_name": "mjpeg", "closed_captions": 0},
+            {"index": 2, "codec_type": "audio", "codec_name": "ac3"}
+        ]});
+
+        let captions = closed_captions_from_probe(&probe);
+
+        assert_eq!(captions.len(), 1);
+        assert_eq!(captions[0].stream_index, 0);
+        assert_eq!(captions[0].video_codec.as_deref(), Some("h264"));
+    }
+
+    #[test]
+    fn media_info_normalizes_format_streams_and_chapters() {
+        let probe = json!({
+            "streams": [
+                {"index": 0, "codec_type": "video", "codec_name": "hevc", "width": 3840,
+                 "height": 2160, "r_frame_rate": "24000/1001", "pix_fmt": "yuv420p10le",
+                 "bits_per_raw_sample": "10", "disposition": {"default": 1, "forced": 0},
+                 "side_data_list": [{"side_data_type": "DOVI configuration record",
+                                     "dv_profile": 8, "dv_level": 6}]},
+                {"index": 1, "codec_type": "audio", "codec_name": "eac3", "channels": 6,
+                 "sample_rate": "48000",
+                 "tags": {"language": "fre", "BPS-eng": "640000",
+                          "DURATION-eng": "01:02:03.500000000"}},
+                {"index": 2, "codec_type": "subtitle", "codec_name": "subrip",
+                 "disposition": {"forced": 1, "hearing_impaired": 1},
+                 "tags": {"title": "Forced", "NUMBER_OF_FRAMES": "42"}}
+            ],
+            "format": {"format_name": "matroska,webm", "duration": "3723.500000",
+                       "size": "1048576", "bit_rate": "N/A", "tags": {"title": "Movie"}},
+            "chapters": [
+                {"id": 1, "start_time": "0.000000", "end_time": "90.500000",
+                 "tags": {"title": "Opening"}}
+            ],
+            "frames": [
+                {"stream_index": 0, "color_transfer": "smpte2084",
+                 "color_primaries": "bt2020",
+                 "side_data_list": [{"side_data_type": "Content light level metadata",
+                                     "max_content": 1000, "max_average": 400}]}
+            ]
+        });
+
+        let info = MediaInfo::from_probe("/movies/a.mkv", &probe);
+
+        assert_eq!(info.schema_version, MEDIA_INFO_SCHEMA_VERSION);
+        assert_eq!(info.format.duration_secs, Some(3723.5));
+        assert_eq!(info.format.size_bytes, Some(1_048_576));
+        assert_eq!(info.format.bit_rate, None);
+        assert_eq!(info.duration_us(), Some(3_723_500_000));
+
+        let video = &info.streams[0];
+        assert_eq!(video.kind, MediaStreamKind::Video);
+        assert_eq!((video.width, video.height), (Some(3840), Some(2160)));
+        assert!(video.disposition.default && !video.disposition.forced);
+        assert_eq!(
+            video.side_data[0].side_data_type,
+            "DOVI configuration record"
+        );
+        assert_eq!(video.side_data[0].fields["dv_profile"], 8);
+        assert_eq!(video.bit_depth, Some(10));
+        // Colour properties and side data fall back to the first frames
+        assert_eq!(video.color_transfer.as_deref(), Some("smpte2084"));
+        assert_eq!(video.color_primaries.as_deref(), Some("bt2020"));
+        let hdr = video.hdr.as_ref().expect("HDR expected");
+        assert_eq!(hdr.formats, vec![HdrFormat::DolbyVision, HdrFormat::Hdr10]);
+        assert_eq!(
+            hdr.content_light_level.as_ref().map(|cll| cll.max_cll),
+            Some(1000)
+        );
+        assert_eq!(hdr.dolby_vision.as_ref().map(|dv| dv.profile), Some(8));
+
+        let audio = &info.streams[1];
+        assert_eq!(audio.language.as_deref(), Some("fre"));
+        assert_eq!(audio.bit_rate, Some(640_000));
+        assert_eq!(audio.sample_rate, Some(48_000));
+        assert_eq!(audio.duration_secs, Some(3723.5));
+
+        let subtitle = &info.streams[2];
+        assert!(subtitle.disposition.forced && subtitle.disposition.hearing_impaired);
+        assert_eq!(subtitle.frame_count, Some(42));
+        assert_eq!(subtitle.title.as_deref(), Some("Forced"));
+
+        assert_eq!(info.chapters[0].end_secs, 90.5);
+        assert_eq!(info.chapters[0].title.as_deref(), Some("Opening"));
+    }
+
+    #[test]
+    fn media_info_serializes_with_camel_case_schema() {
+        let info = MediaInfo::from_probe(
+            "/movies/a.mkv",
+            &json!({"streams": [{"index": 0, "codec_type": "audio"}]}),
+        );
+        let value = serde_json::to_value(&info).expect("media info should serialize");
+
+        assert_eq!(value["schemaVersion"], MEDIA_INFO_SCHEMA_VERSION);
+        assert_eq!(value["streams"][0]["kind"], "audio");
+        assert_eq!(value["streams"][0]["disposition"]["default"], false);
+        assert_eq!(value["attachments"], json!([]));
+        assert_eq!(value["closedCaptions"], json!([]));
+        assert_eq!(value["chapters"], json!([]));
+    }
+}
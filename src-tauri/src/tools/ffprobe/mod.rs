@@ -1,5 +1,11 @@
+pub(crate) mod bitrate;
+pub(crate) mod cache;
 mod duration;
+pub(crate) mod hdr;
+pub(crate) mod keyframes;
+pub(crate) mod media_info;
 pub(crate) mod probe;
+pub(crate) mod scan;
 
 use std::time::Duration;
 
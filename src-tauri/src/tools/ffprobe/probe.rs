@@ -1,23 +1,63 @@
 use crate::shared::store::resolve_ffprobe_path;
 use crate::shared::validation::validate_media_path;
 use crate::tools::ffprobe::FFPROBE_TIMEOUT;
+use crate::tools::ffprobe::cache::cached_probe_json;
+use crate::tools::ffprobe::hdr::{probe_hdr_frames, stream_signals_hdr};
+use crate::tools::ffprobe::media_info::MediaInfo;
+use serde_json::Value;
 use tokio::process::Command;
 use tokio::time::timeout;
 
-/// Probe a video file using ffprobe and return JSON output
+/// Probe a video file using ffprobe and return its `MediaInfo`
 /// Uses async tokio::process::Command with timeout
 #[tauri::command]
-pub(crate) async fn probe_file(app: tauri::AppHandle, path: String) -> Result<String, String> {
+pub(crate) async fn probe_file(app: tauri::AppHandle, path: String) -> Result<MediaInfo, String> {
     // Validate input path
     validate_media_path(&path)?;
     let ffprobe_path = resolve_ffprobe_path(&app)?;
     probe_file_with_ffprobe(&ffprobe_path, &path).await
 }
 
+/// `MediaInfo` of a file, from the probe cache while the file is unchanged
 pub(crate) async fn probe_file_with_ffprobe(
     ffprobe_path: &str,
     path: &str,
-) -> Result<String, String> {
+) -> Result<MediaInfo, String> {
+    let probe = cached_probe_json(ffprobe_path, path).await?;
+    Ok(MediaInfo::from_probe(path, &probe))
+}
+
+/// Raw ffprobe JSON (streams, format, chapters and the first video frames) that `MediaInfo`
+/// is built from
+pub(crate) async fn probe_json_with_ffprobe(
+    ffprobe_path: &str,
+    path: &str,
+) -> Result<Value, String> {
+    let mut probe = probe_container_json(ffprobe_path, path).await?;
+
+    // Frame-level HDR metadata is best effort, the container probe already succeeded, and
+    // is only looked for when the first video stream signals HDR
+    let signals_hdr = probe
+        .get("streams")
+        .and_then(|s| s.as_array())
+        .and_then(|streams| {
+            streams
+                .iter()
+                .find(|stream| stream.get("codec_type").and_then(|v| v.as_str()) == Some("video"))
+        })
+        .is_some_and(stream_signals_hdr);
+    if signals_hdr
+        && let Ok(frames) = probe_hdr_frames(ffprobe_path, path).await
+        && let Some(object) = probe.as_object_mut()
+    {
+        object.insert("frames".to_string(), frames);
+    }
+
+    Ok(probe)
+}
+
+/// Streams, format and chapters of a file, without frame-level metadata
+pub(crate) async fn probe_container_json(ffprobe_path: &str, path: &str) -> Result<Value, String> {
     let probe_future = async move {
         Command::new(ffprobe_path)
             .args([
@@ -27,6 +67,7 @@ pub(crate) async fn probe_file_with_ffprobe(
                 "json",
                 "-show_streams",
                 "-show_format",
+                "-show_chapters",
                 path,
             ])
             .output()
@@ -54,12 +95,13 @@ pub(crate) async fn probe_file_with_ffprobe(
         return Err(format!("ffprobe failed: {}", stderr));
     }
 
-    String::from_utf8(output.stdout).map_err(|e| format!("Invalid UTF-8 output: {}", e))
+    serde_json::from_slice(&output.stdout)
+        .map_err(|e| format!("Failed to parse probe output: {}", e))
 }
 
 #[cfg(test)]
 mod tests {
-    use super::probe_file_with_ffprobe;
+    use super::{probe_file_with_ffprobe, probe_json_with_ffprobe};
 
     #[tokio::test]
     async fn probe_file_returns_streams_json_for_sample_video() {
@@ -67,10 +109,18 @@ mod tests {
             .await
             .expect("failed to load local sample video");
 
-        let json = probe_file_with_ffprobe("ffprobe", video.to_string_lossy().as_ref())
+        let value = probe_json_with_ffprobe("ffprobe", video.to_string_lossy().as_ref())
             .await
             .expect("probe should succeed");
-        let value: serde_json::Value = serde_json::from_str(&json).expect("valid json expected");
         assert!(value.get("streams").is_some());
     }
+
+    #[tokio::test]
+    async fn probe_file_reports_error_when_ffprobe_binary_is_missing() {
+        let error = probe_file_with_ffprobe("/tmp/definitely-not-a-real-ffprobe-binary", "/x.mkv")
+            .await
+            .expect_err("missing ffprobe binary should fail");
+
+        assert!(error.contains("Failed to execute ffprobe"));
+    }
 }
@@ -0,0 +1,380 @@
+use crate::shared::glob::glob_matches;
+use crate::shared::store::resolve_ffprobe_path;
+use crate::shared::validation::{has_media_extension, validate_directory_path};
+use crate::tools::ffprobe::media_info::MediaInfo;
+use crate::tools::ffprobe::probe::probe_file_with_ffprobe;
+use futures_util::stream::{self, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use tauri::Emitter;
+use walkdir::{DirEntry, WalkDir};
+
+/// Number of ffprobe processes run at once when the caller does not set a limit
+const DEFAULT_SCAN_CONCURRENCY: usize = 4;
+
+const MAX_SCAN_CONCURRENCY: usize = 16;
+
+/// Cancel flag of each running scan, by scanned folder
+/// Set by `cancel_scan_directory` so that scan stops probing queued files
+static SCAN_CANCEL_FLAGS: LazyLock<Mutex<HashMap<String, Arc<AtomicBool>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// A running scan's cancel flag, unregistered when the scan ends
+struct ScanRegistration {
+    dir_path: String,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl ScanRegistration {
+    fn register(dir_path: &str) -> Result<Self, String> {
+        let mut flags = SCAN_CANCEL_FLAGS
+            .lock()
+            .map_err(|_| "Failed to acquire scan lock".to_string())?;
+        if flags.contains_key(dir_path) {
+            return Err(format!("{} is already being scanned", dir_path));
+        }
+
+        let cancelled = Arc::new(AtomicBool::new(false));
+        flags.insert(dir_path.to_string(), cancelled.clone());
+        Ok(Self {
+            dir_path: dir_path.to_string(),
+            cancelled,
+        })
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+impl Drop for ScanRegistration {
+    fn drop(&mut self) {
+        if let Ok(mut flags) = SCAN_CANCEL_FLAGS.lock() {
+            flags.remove(&self.dir_path);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct ScanDirectoryOptions {
+    /// Descend into subfolders, on by default
+    #[serde(default)]
+    pub(crate) recursive: Option<bool>,
+    /// Globs a file must match one of, relative to the scanned folder; empty keeps every file
+    #[serde(default)]
+    pub(crate) include: Vec<String>,
+    /// Globs of files and folders to leave out
+    #[serde(default)]
+    pub(crate) exclude: Vec<String>,
+    #[serde(default)]
+    pub(crate) concurrency: Option<usize>,
+}
+
+/// One probed file, streamed as a `scan-directory-file` event
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScannedMediaFile {
+    pub(crate) path: String,
+    pub(crate) media_info: Option<MediaInfo>,
+    pub(crate) error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct ScanDirectorySummary {
+    /// Media files found after filtering
+    pub(crate) total: usize,
+    pub(crate) probed: usize,
+    pub(crate) failed: usize,
+    pub(crate) cancelled: bool,
+}
+
+fn validate_scan_concurrency(concurrency: Option<usize>) -> Result<usize, String> {
+    match concurrency {
+        None => Ok(DEFAULT_SCAN_CONCURRENCY),
+        Some(0) => Err("Scan concurrency must be at least 1".to_string()),
+        Some(n) if n > MAX_SCAN_CONCURRENCY => Err(format!(
+            "Scan concurrency cannot exceed {}",
+            MAX_SCAN_CONCURRENCY
+        )),
+        Some(n) => Ok(n),
+    }
+}
+
+fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Hidden entries include staged `.part` outputs and macOS resource forks
+fn is_hidden(entry: &DirEntry) -> bool {
+    entry.depth() > 0 && entry.file_name().to_string_lossy().starts_with('.')
+}
+
+/// Media files under `root` that pass the filters, sorted by path
+fn collect_media_files(root: &Path, options: &ScanDirectoryOptions) -> Vec<PathBuf> {
+    let max_depth = if options.recursive.unwrap_or(true) {
+        usize::MAX
+    } else {
+        1
+    };
+    let excluded = |relative: &str| {
+        options
+            .exclude
+            .iter()
+            .any(|pattern| glob_matches(pattern, relative))
+    };
+
+    let mut files: Vec<PathBuf> = WalkDir::new(root)
+        .max_depth(max_depth)
+        .into_iter()
+        .filter_entry(|entry| !is_hidden(entry) && !excluded(&relative_path(root, entry.path())))
+        .filter_map(Result::ok)
+        .filter(|entry| entry.file_type().is_file() && has_media_extension(entry.path()))
+        .filter(|entry| {
+            let relative = relative_path(root, entry.path());
+            options.include.is_empty()
+                || options
+                    .include
+                    .iter()
+                    .any(|pattern| glob_matches(pattern, &relative))
+        })
+        .map(DirEntry::into_path)
+        .collect();
+    files.sort();
+    files
+}
+
+pub(super) async fn scan_directory_with_ffprobe(
+    app: Option<&tauri::AppHandle>,
+    ffprobe_path: &str,
+    dir_path: &str,
+    options: ScanDirectoryOptions,
+) -> Result<ScanDirectorySummary, String> {
+    validate_directory_path(dir_path)?;
+    let concurrency = validate_scan_concurrency(options.concurrency)?;
+    let registration = ScanRegistration::register(dir_path)?;
+    let registration = &registration;
+
+    let root = PathBuf::from(dir_path);
+    let files = tokio::task::spawn_blocking(move || collect_media_files(&root, &options))
+        .await
+        .map_err(|e| format!("Failed to list {}: {}", dir_path, e))?;
+    let total = files.len();
+    if let Some(app) = app {
+        let _ = app.emit(
+            "scan-directory-started",
+            json!({ "dirPath": dir_path, "total": total }),
+        );
+    }
+
+    let completed = Arc::new(AtomicUsize::new(0));
+    let results: Vec<Option<bool>> = stream::iter(files)
+        .map(|path| {
+            let completed = completed.clone();
+            async move {
+                if registration.is_cancelled() {
+                    return None;
+                }
+
+                let path = path.to_string_lossy().to_string();
+                let file = match probe_file_with_ffprobe(ffprobe_path, &path).await {
+                    Ok(media_info) => ScannedMediaFile {
+                        path,
+                        media_info: Some(media_info),
+                        error: None,
+                    },
+                    Err(e) => ScannedMediaFile {
+                        path,
+                        media_info: None,
+                        error: Some(e),
+                    },
+                };
+                let done = completed.fetch_add(1, Ordering::SeqCst) + 1;
+                if let Some(app) = app {
+                    let _ = app.emit(
+                        "scan-directory-file",
+                        json!({
+                            "dirPath": dir_path,
+                            "completed": done,
+                            "total": total,
+                            "file": file
+                        }),
+                    );
+                }
+                Some(file.error.is_none())
+            }
+        })
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+
+    Ok(ScanDirectorySummary {
+        total,
+        probed: results.iter().filter(|r| **r == Some(true)).count(),
+        failed: results.iter().filter(|r| **r == Some(false)).count(),
+        cancelled: results.iter().any(Option::is_none),
+    })
+}
+
+/// Probe every media file of a folder, `concurrency` files at a time
+/// Emits `scan-directory-started` with the file count, then one `scan-directory-file` event
+/// per file as soon as it is probed
+#[tauri::command]
+pub(crate) async fn scan_directory(
+    app: tauri::AppHandle,
+    dir_path: String,
+    options: Option<ScanDirectoryOptions>,
+) -> Result<ScanDirectorySummary, String> {
+    let ffprobe_path = resolve_ffprobe_path(&app)?;
+    scan_directory_with_ffprobe(
+        Some(&app),
+        &ffprobe_path,
+        &dir_path,
+        options.unwrap_or_default(),
+    )
+    .await
+}
+
+/// Stop the running scan of `dir_path`; files already being probed still finish
+#[tauri::command]
+pub(crate) async fn cancel_scan_directory(dir_path: String) -> Result<(), String> {
+    let flags = SCAN_CANCEL_FLAGS
+        .lock()
+        .map_err(|_| "Failed to acquire scan lock".to_string())?;
+    let cancelled = flags
+        .get(&dir_path)
+        .ok_or_else(|| format!("No scan of {} is running", dir_path))?;
+    cancelled.store(true, Ordering::SeqCst);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        MAX_SCAN_CONCURRENCY, ScanDirectoryOptions, ScanRegistration, cancel_scan_directory,
+        collect_media_files, scan_directory_with_ffprobe, validate_scan_concurrency,
+    };
+    use std::path::Path;
+
+    fn touch(root: &Path, relative: &str) {
+        let path = root.join(relative);
+        std::fs::create_dir_all(path.parent().expect("parent expected"))
+            .expect("failed to create folder");
+        std::fs::write(path, b"media").expect("failed to write file");
+    }
+
+    fn names(root: &Path, options: &ScanDirectoryOptions) -> Vec<String> {
+        collect_media_files(root, options)
+            .iter()
+            .map(|path| super::relative_path(root, path))
+            .collect()
+    }
+
+    #[test]
+    fn collect_media_files_filters_extensions_hidden_entries_and_globs() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let root = temp.path();
+        for file in [
+            "Season 1/E01.mkv",
+            "Season 1/E01.srt",
+            "Season 1/notes.txt",
+            "Season 1/.E02.token.part.mkv",
+            "Season 2/E01.MKV",
+            "Season 2/extras/bts.mkv",
+            ".cache/x.mkv",
+            "poster.mp4",
+        ] {
+            touch(root, file);
+        }
+
+        let all = names(root, &ScanDirectoryOptions::default());
+        assert_eq!(
+            all,
+            vec![
+                "Season 1/E01.mkv",
+                "Season 1/E01.srt",
+                "Season 2/E01.MKV",
+                "Season 2/extras/bts.mkv",
+                "poster.mp4"
+            ]
+        );
+
+        let options = ScanDirectoryOptions {
+            include: vec!["*.mkv".to_string()],
+            exclude: vec!["extras".to_string()],
+            ..ScanDirectoryOptions::default()
+        };
+        assert_eq!(
+            names(root, &options),
+            vec!["Season 1/E01.mkv", "Season 2/E01.MKV"]
+        );
+
+        let options = ScanDirectoryOptions {
+            recursive: Some(false),
+            ..ScanDirectoryOptions::default()
+        };
+        assert_eq!(names(root, &options), vec!["poster.mp4"]);
+    }
+
+    #[test]
+    fn validate_scan_concurrency_bounds_worker_count() {
+        assert_eq!(validate_scan_concurrency(None), Ok(4));
+        assert_eq!(validate_scan_concurrency(Some(2)), Ok(2));
+        assert!(validate_scan_concurrency(Some(0)).is_err());
+        assert!(validate_scan_concurrency(Some(MAX_SCAN_CONCURRENCY + 1)).is_err());
+    }
+
+    #[tokio::test]
+    async fn scan_directory_reports_probe_failures_per_file() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        touch(temp.path(), "a.mkv");
+        touch(temp.path(), "b/c.mp4");
+
+        let summary = scan_directory_with_ffprobe(
+            None,
+            "/tmp/definitely-not-a-real-ffprobe-binary",
+            temp.path().to_string_lossy().as_ref(),
+            ScanDirectoryOptions::default(),
+        )
+        .await
+        .expect("scan should finish even when probes fail");
+
+        assert_eq!(summary.total, 2);
+        assert_eq!(summary.probed, 0);
+        assert_eq!(summary.failed, 2);
+        assert!(!summary.cancelled);
+    }
+
+    #[tokio::test]
+    async fn cancel_scan_directory_stops_only_the_named_scan() {
+        let first = ScanRegistration::register("/tmp/scan-cancel-test/a").expect("first scan");
+        let second = ScanRegistration::register("/tmp/scan-cancel-test/b").expect("second scan");
+        assert!(ScanRegistration::register("/tmp/scan-cancel-test/a").is_err());
+
+        cancel_scan_directory("/tmp/scan-cancel-test/a".to_string())
+            .await
+            .expect("running scan should be cancelled");
+        assert!(first.is_cancelled());
+        assert!(!second.is_cancelled());
+
+        // A finished scan no longer takes a cancel, and a new scan starts uncancelled
+        drop(first);
+        assert!(
+            cancel_scan_directory("/tmp/scan-cancel-test/a".to_string())
+                .await
+                .is_err()
+        );
+        let again = ScanRegistration::register("/tmp/scan-cancel-test/a").expect("new scan");
+        assert!(!again.is_cancelled());
+    }
+}
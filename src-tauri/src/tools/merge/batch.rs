@@ -0,0 +1,671 @@
+use crate::tools::merge::cancel::cancel_video_merge;
+use crate::tools::merge::job::MergeJob;
+use crate::tools::merge::merge::{MERGE_CANCELLED, MergeProgressHook, run_merge_job};
+use crate::tools::merge::verify::MergeVerificationReport;
+use futures_util::stream::{self, StreamExt};
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tauri::Emitter;
+
+/// Number of merges run at once when the caller does not set a limit
+const DEFAULT_BATCH_CONCURRENCY: usize = 2;
+
+/// Each merge is its own ffmpeg process, so keep the limit modest
+const MAX_BATCH_CONCURRENCY: usize = 8;
+
+/// Finished batches kept for `get_merge_batch_status` after the UI reloads
+const MAX_FINISHED_BATCHES: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum MergeBatchJobStatus {
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+/// Outcome of one job of a batch, in the order the jobs were submitted
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeBatchJobResult {
+    pub(crate) index: usize,
+    pub(crate) video_path: String,
+    pub(crate) output_path: String,
+    pub(crate) status: MergeBatchJobStatus,
+    pub(crate) report: Option<MergeVerificationReport>,
+    pub(crate) error: Option<String>,
+    pub(crate) elapsed_ms: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeBatchSummary {
+    pub(crate) batch_id: String,
+    pub(crate) completed: usize,
+    pub(crate) failed: usize,
+    pub(crate) cancelled: usize,
+    pub(crate) results: Vec<MergeBatchJobResult>,
+}
+
+/// One job of a batch as seen by `get_merge_batch_status`; queued until it runs
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeBatchJobState {
+    pub(crate) index: usize,
+    pub(crate) video_path: String,
+    pub(crate) output_path: String,
+    pub(crate) running: bool,
+    pub(crate) progress: i32,
+    pub(crate) result: Option<MergeBatchJobResult>,
+}
+
+/// Snapshot of a batch, enough for a reloaded window to pick up where it was
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeBatchStatus {
+    pub(crate) batch_id: String,
+    pub(crate) running: bool,
+    pub(crate) cancel_requested: bool,
+    pub(crate) progress: i32,
+    pub(crate) speed_bytes_per_sec: Option<f64>,
+    pub(crate) eta_secs: Option<f64>,
+    pub(crate) completed: usize,
+    pub(crate) failed: usize,
+    pub(crate) cancelled: usize,
+    pub(crate) total: usize,
+    pub(crate) jobs: Vec<MergeBatchJobState>,
+}
+
+#[derive(Debug, Clone)]
+struct BatchJobProgress {
+    weight_bytes: u64,
+    progress: i32,
+    speed_bytes_per_sec: Option<f64>,
+    finished: bool,
+}
+
+/// Aggregate progress of a batch, with jobs weighted by their input size
+#[derive(Debug)]
+struct MergeBatchProgress {
+    jobs: Vec<BatchJobProgress>,
+    completed: usize,
+    failed: usize,
+    cancelled: usize,
+}
+
+impl MergeBatchProgress {
+    fn new(weights: Vec<u64>) -> Self {
+        Self {
+            jobs: weights
+                .into_iter()
+                .map(|weight_bytes| BatchJobProgress {
+                    weight_bytes: weight_bytes.max(1),
+                    progress: 0,
+                    speed_bytes_per_sec: None,
+                    finished: false,
+                })
+                .collect(),
+            completed: 0,
+            failed: 0,
+            cancelled: 0,
+        }
+    }
+
+    fn update(&mut self, index: usize, progress: i32, speed_bytes_per_sec: Option<f64>) {
+        if let Some(job) = self.jobs.get_mut(index)
+            && !job.finished
+        {
+            job.progress = progress.clamp(0, 100);
+            job.speed_bytes_per_sec = speed_bytes_per_sec;
+        }
+    }
+
+    fn finish(&mut self, index: usize, status: MergeBatchJobStatus) {
+        let Some(job) = self.jobs.get_mut(index) else {
+            return;
+        };
+        job.finished = true;
+        job.progress = 100;
+        job.speed_bytes_per_sec = None;
+        match status {
+            MergeBatchJobStatus::Completed => self.completed += 1,
+            MergeBatchJobStatus::Failed => self.failed += 1,
+            MergeBatchJobStatus::Cancelled => self.cancelled += 1,
+        }
+    }
+
+    fn remaining_bytes(&self) -> f64 {
+        self.jobs
+            .iter()
+            .map(|job| job.weight_bytes as f64 * f64::from(100 - job.progress) / 100.0)
+            .sum()
+    }
+
+    /// Overall progress (0-100); failed and cancelled jobs count as done
+    fn progress(&self) -> i32 {
+        let total: f64 = self.jobs.iter().map(|job| job.weight_bytes as f64).sum();
+        if total <= 0.0 {
+            return 0;
+        }
+        (100.0 * (1.0 - self.remaining_bytes() / total)).floor() as i32
+    }
+
+    /// Combined write speed of the merges currently running
+    fn speed_bytes_per_sec(&self) -> Option<f64> {
+        let speed: f64 = self
+            .jobs
+            .iter()
+            .filter(|job| !job.finished)
+            .filter_map(|job| job.speed_bytes_per_sec)
+            .sum();
+        (speed > 0.0).then_some(speed)
+    }
+
+    /// Seconds left for the whole batch, queued jobs included
+    fn eta_secs(&self) -> Option<f64> {
+        self.speed_bytes_per_sec()
+            .map(|speed| self.remaining_bytes() / speed)
+    }
+}
+
+#[derive(Debug)]
+struct MergeBatchState {
+    progress: MergeBatchProgress,
+    jobs: Vec<MergeBatchJobState>,
+    running: bool,
+}
+
+/// A submitted batch, shared between its running jobs and the registry
+#[derive(Debug)]
+pub(crate) struct MergeBatch {
+    id: String,
+    cancelled: AtomicBool,
+    state: Mutex<MergeBatchState>,
+}
+
+impl MergeBatch {
+    fn new(id: String, jobs: &[MergeJob]) -> Self {
+        Self {
+            id,
+            cancelled: AtomicBool::new(false),
+            state: Mutex::new(MergeBatchState {
+                progress: MergeBatchProgress::new(jobs.iter().map(job_input_bytes).collect()),
+                jobs: jobs
+                    .iter()
+                    .enumerate()
+                    .map(|(index, job)| MergeBatchJobState {
+                        index,
+                        video_path: job.video_path.clone(),
+                        output_path: job.output_path.clone(),
+                        running: false,
+                        progress: 0,
+                        result: None,
+                    })
+                    .collect(),
+                running: true,
+            }),
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Mark a job as running unless the batch is cancelled, under the lock `cancel` reads
+    fn start_job(&self, index: usize) -> bool {
+        let Ok(mut state) = self.state.lock() else {
+            return false;
+        };
+        if self.is_cancelled() {
+            return false;
+        }
+        if let Some(job) = state.jobs.get_mut(index) {
+            job.running = true;
+        }
+        true
+    }
+
+    /// Flag the batch as cancelled and return the video paths of its running jobs
+    fn flag_cancelled(&self) -> Vec<String> {
+        let state = self.state.lock();
+        self.cancelled.store(true, Ordering::SeqCst);
+        state
+            .map(|state| {
+                state
+                    .jobs
+                    .iter()
+                    .filter(|job| job.running)
+                    .map(|job| job.video_path.clone())
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Stop starting queued jobs and kill the merges of this batch that are running
+    /// Jobs still preparing see the flag before and after their ffmpeg process is registered
+    fn cancel(&self) -> Result<(), String> {
+        for video_path in self.flag_cancelled() {
+            cancel_video_merge(&video_path)?;
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Option<MergeBatchStatus> {
+        let state = self.state.lock().ok()?;
+        Some(MergeBatchStatus {
+            batch_id: self.id.clone(),
+            running: state.running,
+            cancel_requested: self.is_cancelled(),
+            progress: state.progress.progress(),
+            speed_bytes_per_sec: state.progress.speed_bytes_per_sec(),
+            eta_secs: state.progress.eta_secs(),
+            completed: state.progress.completed,
+            failed: state.progress.failed,
+            cancelled: state.progress.cancelled,
+            total: state.jobs.len(),
+            jobs: state.jobs.clone(),
+        })
+    }
+}
+
+/// Batches started in this session, registered as Tauri managed state
+#[derive(Debug, Default)]
+pub(crate) struct MergeBatchRegistry {
+    batches: Mutex<Vec<Arc<MergeBatch>>>,
+}
+
+impl MergeBatchRegistry {
+    fn register(&self, jobs: &[MergeJob]) -> Arc<MergeBatch> {
+        static NEXT_BATCH: AtomicU64 = AtomicU64::new(1);
+        let started_ms = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        let id = format!(
+            "batch-{}-{}",
+            started_ms,
+            NEXT_BATCH.fetch_add(1, Ordering::SeqCst)
+        );
+        let batch = Arc::new(MergeBatch::new(id, jobs));
+
+        if let Ok(mut batches) = self.batches.lock() {
+            let is_running = |batch: &MergeBatch| batch.state.lock().is_ok_and(|s| s.running);
+            let finished = batches.iter().filter(|b| !is_running(b)).count();
+            let mut to_drop = (finished + 1).saturating_sub(MAX_FINISHED_BATCHES);
+            batches.retain(|b| {
+                let drop = to_drop > 0 && !is_running(b);
+                if drop {
+                    to_drop -= 1;
+                }
+                !drop
+            });
+            batches.push(batch.clone());
+        }
+        batch
+    }
+
+    fn find(&self, batch_id: &str) -> Option<Arc<MergeBatch>> {
+        self.batches
+            .lock()
+            .ok()?
+            .iter()
+            .find(|batch| batch.id == batch_id)
+            .cloned()
+    }
+
+    /// Status of one batch, or of every known batch, oldest first
+    pub(crate) fn statuses(&self, batch_id: Option<&str>) -> Result<Vec<MergeBatchStatus>, String> {
+        let batches = match batch_id {
+            Some(id) => vec![
+                self.find(id)
+                    .ok_or_else(|| format!("Unknown merge batch {}", id))?,
+            ],
+            None => self
+                .batches
+                .lock()
+                .map_err(|_| "Failed to acquire batch lock".to_string())?
+                .clone(),
+        };
+        Ok(batches.iter().filter_map(|batch| batch.status()).collect())
+    }
+
+    /// Flag every running batch so none of them starts another job
+    pub(crate) fn cancel_all(&self) {
+        if let Ok(batches) = self.batches.lock() {
+            for batch in batches.iter() {
+                batch.flag_cancelled();
+            }
+        }
+    }
+}
+
+/// Size of every file a job reads, used to weight it against the rest of the batch
+fn job_input_bytes(job: &MergeJob) -> u64 {
+    std::iter::once(job.video_path.as_str())
+        .chain(job.tracks.iter().map(|track| track.input_path.as_str()))
+        .chain(job.replacements.iter().map(|r| r.input_path.as_str()))
+        .filter_map(|path| std::fs::metadata(path).ok())
+        .map(|metadata| metadata.len())
+        .sum()
+}
+
+/// Check the batch as a whole and return the concurrency to run it with
+fn validate_batch(jobs: &[MergeJob], concurrency: Option<usize>) -> Result<usize, String> {
+    if jobs.is_empty() {
+        return Err("Merge batch has no jobs".to_string());
+    }
+
+    let concurrency = concurrency.unwrap_or(DEFAULT_BATCH_CONCURRENCY);
+    if !(1..=MAX_BATCH_CONCURRENCY).contains(&concurrency) {
+        return Err(format!(
+            "Batch concurrency must be between 1 and {}",
+            MAX_BATCH_CONCURRENCY
+        ));
+    }
+
+    // Running merges are tracked (and cancelled) by video path
+    let mut video_paths = HashSet::new();
+    let mut output_paths = HashSet::new();
+    for job in jobs {
+        if !video_paths.insert(job.video_path.as_str()) {
+            return Err(format!(
+                "Video {} appears more than once in the batch",
+                job.video_path
+            ));
+        }
+        if !output_paths.insert(job.output_path.as_str()) {
+            return Err(format!(
+                "Output {} is written by more than one job",
+                job.output_path
+            ));
+        }
+    }
+
+    Ok(concurrency.min(jobs.len()))
+}
+
+fn emit_batch_progress(
+    app: &tauri::AppHandle,
+    batch_id: &str,
+    batch: &MergeBatchProgress,
+    job_index: usize,
+    video_path: &str,
+) {
+    let _ = app.emit(
+        "merge-batch-progress",
+        json!({
+            "batchId": batch_id,
+            "jobIndex": job_index,
+            "videoPath": video_path,
+            "jobProgress": batch.jobs.get(job_index).map(|job| job.progress),
+            "progress": batch.progress(),
+            "speedBytesPerSec": batch.speed_bytes_per_sec(),
+            "etaSecs": batch.eta_secs(),
+            "completed": batch.completed,
+            "failed": batch.failed,
+            "cancelled": batch.cancelled,
+            "total": batch.jobs.len(),
+        }),
+    );
+}
+
+/// A failed job is cancelled when it never started or its merge was stopped; any other
+/// error is a failure, even in a cancelled batch
+fn job_status(
+    outcome: &Result<MergeVerificationReport, String>,
+    started: bool,
+) -> MergeBatchJobStatus {
+    match outcome {
+        Ok(_) => MergeBatchJobStatus::Completed,
+        Err(e) if !started || e == MERGE_CANCELLED => MergeBatchJobStatus::Cancelled,
+        Err(_) => MergeBatchJobStatus::Failed,
+    }
+}
+
+async fn run_batch_job(
+    app: tauri::AppHandle,
+    batch: Arc<MergeBatch>,
+    index: usize,
+    job: MergeJob,
+) -> MergeBatchJobResult {
+    let started_at = Instant::now();
+    let video_path = job.video_path.clone();
+    let output_path = job.output_path.clone();
+
+    let started = batch.start_job(index);
+    let outcome = if !started {
+        Err("Cancelled before start".to_string())
+    } else {
+        let hook: MergeProgressHook = {
+            let app = app.clone();
+            let batch = batch.clone();
+            let video_path = video_path.clone();
+            Arc::new(move |progress, speed_bytes_per_sec| {
+                if let Ok(mut state) = batch.state.lock() {
+                    state.progress.update(index, progress, speed_bytes_per_sec);
+                    if let Some(job) = state.jobs.get_mut(index) {
+                        job.progress = progress.clamp(0, 100);
+                    }
+                    emit_batch_progress(&app, &batch.id, &state.progress, index, &video_path);
+                }
+            })
+        };
+        run_merge_job(&app, job, Some(hook), Some(&batch.cancelled)).await
+    };
+
+    let status = job_status(&outcome, started);
+    let (report, error) = match outcome {
+        Ok(report) => (Some(report), None),
+        Err(e) => (None, Some(e)),
+    };
+    let result = MergeBatchJobResult {
+        index,
+        video_path: video_path.clone(),
+        output_path,
+        status,
+        report,
+        error,
+        elapsed_ms: started_at.elapsed().as_millis() as u64,
+    };
+
+    if let Ok(mut state) = batch.state.lock() {
+        state.progress.finish(index, status);
+        if let Some(job) = state.jobs.get_mut(index) {
+            job.running = false;
+            job.progress = 100;
+            job.result = Some(result.clone());
+        }
+        emit_batch_progress(&app, &batch.id, &state.progress, index, &video_path);
+    }
+
+    result
+}
+
+/// Merge several videos, running up to `concurrency` ffmpeg processes at once
+/// Emits `merge-batch-started` with the batch id, then `merge-batch-progress` events
+#[tauri::command]
+pub(crate) async fn start_merge_batch(
+    app: tauri::AppHandle,
+    batches: tauri::State<'_, MergeBatchRegistry>,
+    jobs: Vec<MergeJob>,
+    concurrency: Option<usize>,
+) -> Result<MergeBatchSummary, String> {
+    let concurrency = validate_batch(&jobs, concurrency)?;
+    let batch = batches.register(&jobs);
+    let _ = app.emit(
+        "merge-batch-started",
+        json!({ "batchId": batch.id, "total": jobs.len() }),
+    );
+
+    let mut results: Vec<MergeBatchJobResult> = stream::iter(jobs.into_iter().enumerate())
+        .map(|(index, job)| run_batch_job(app.clone(), batch.clone(), index, job))
+        .buffer_unordered(concurrency)
+        .collect()
+        .await;
+    results.sort_by_key(|result| result.index);
+    if let Ok(mut state) = batch.state.lock() {
+        state.running = false;
+    }
+
+    let count = |status| results.iter().filter(|r| r.status == status).count();
+    Ok(MergeBatchSummary {
+        batch_id: batch.id.clone(),
+        completed: count(MergeBatchJobStatus::Completed),
+        failed: count(MergeBatchJobStatus::Failed),
+        cancelled: count(MergeBatchJobStatus::Cancelled),
+        results,
+    })
+}
+
+/// Progress and per-job results of one batch, or of every batch when no id is given
+#[tauri::command]
+pub(crate) async fn get_merge_batch_status(
+    batches: tauri::State<'_, MergeBatchRegistry>,
+    batch_id: Option<String>,
+) -> Result<Vec<MergeBatchStatus>, String> {
+    batches.statuses(batch_id.as_deref())
+}
+
+/// Cancel one batch: queued jobs are skipped and its running merges are stopped
+#[tauri::command]
+pub(crate) async fn cancel_merge_batch(
+    batches: tauri::State<'_, MergeBatchRegistry>,
+    batch_id: String,
+) -> Result<(), String> {
+    batches
+        .find(&batch_id)
+        .ok_or_else(|| format!("Unknown merge batch {}", batch_id))?
+        .cancel()
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        MAX_BATCH_CONCURRENCY, MAX_FINISHED_BATCHES, MergeBatchJobStatus, MergeBatchProgress,
+        MergeBatchRegistry, job_status, validate_batch,
+    };
+    use crate::tools::merge::job::MergeJob;
+    use crate::tools::merge::merge::MERGE_CANCELLED;
+
+    fn job(video_path: &str, output_path: &str) -> MergeJob {
+        serde_json::from_value(json!({"videoPath": video_path, "outputPath": output_path}))
+            .expect("job should deserialize")
+    }
+
+    #[test]
+    fn validate_batch_checks_concurrency_and_duplicate_paths() {
+        let jobs = vec![job("/a.mkv", "/out/a.mkv"), job("/b.mkv", "/out/b.mkv")];
+
+        assert_eq!(validate_batch(&jobs, None), Ok(2));
+        assert_eq!(validate_batch(&jobs, Some(MAX_BATCH_CONCURRENCY)), Ok(2));
+        assert_eq!(validate_batch(&jobs[..1], Some(4)), Ok(1));
+        assert!(validate_batch(&jobs, Some(0)).is_err());
+        assert!(validate_batch(&[], None).is_err());
+
+        let same_video = vec![job("/a.mkv", "/out/a.mkv"), job("/a.mkv", "/out/b.mkv")];
+        assert!(validate_batch(&same_video, None).is_err());
+        let same_output = vec![job("/a.mkv", "/out/x.mkv"), job("/b.mkv", "/out/x.mkv")];
+        assert!(validate_batch(&same_output, None).is_err());
+    }
+
+    #[test]
+    fn batch_progress_weights_jobs_by_size_and_estimates_eta() {
+        let mut batch = MergeBatchProgress::new(vec![3_000, 1_000]);
+        assert_eq!(batch.progress(), 0);
+        assert_eq!(batch.eta_secs(), None);
+
+        batch.update(0, 50, Some(100.0));
+        assert_eq!(batch.progress(), 37);
+        // 1500 bytes left on the first job plus the queued 1000
+        assert_eq!(batch.eta_secs(), Some(25.0));
+
+        batch.finish(0, MergeBatchJobStatus::Completed);
+        batch.update(1, 20, Some(200.0));
+        assert_eq!(batch.progress(), 80);
+        assert_eq!(batch.speed_bytes_per_sec(), Some(200.0));
+        assert_eq!(batch.eta_secs(), Some(4.0));
+
+        batch.finish(1, MergeBatchJobStatus::Failed);
+        batch.update(1, 10, Some(50.0));
+        assert_eq!(batch.progress(), 100);
+        assert_eq!((batch.completed, batch.failed), (1, 1));
+        assert_eq!(batch.eta_secs(), None);
+    }
+
+    #[test]
+    fn registry_reports_status_and_scopes_cancel_to_one_batch() {
+        let registry = MergeBatchRegistry::default();
+        let first = registry.register(&[job("/a.mkv", "/out/a.mkv")]);
+        let second = registry.register(&[job("/b.mkv", "/out/b.mkv"), job("/c.mkv", "/out/c.mkv")]);
+
+        first.cancel().expect("cancel should succeed");
+
+        let statuses = registry.statuses(None).expect("statuses expected");
+        assert_eq!(statuses.len(), 2);
+        assert!(statuses[0].cancel_requested);
+        assert!(!statuses[1].cancel_requested);
+        assert_eq!(statuses[1].total, 2);
+        assert_eq!(statuses[1].jobs[1].video_path, "/c.mkv");
+        assert!(
+            statuses[1]
+                .jobs
+                .iter()
+                .all(|j| !j.running && j.result.is_none())
+        );
+
+        let only_second = registry
+            .statuses(Some(&second.id))
+            .expect("known batch should be found");
+        assert_eq!(only_second[0].batch_id, second.id);
+        assert!(registry.statuses(Some("batch-unknown")).is_err());
+    }
+
+    #[test]
+    fn registry_keeps_running_batches_and_the_latest_finished_ones() {
+        let registry = MergeBatchRegistry::default();
+        let running = registry.register(&[job("/a.mkv", "/out/a.mkv")]);
+        for _ in 0..MAX_FINISHED_BATCHES + 2 {
+            let batch = registry.register(&[job("/b.mkv", "/out/b.mkv")]);
+            batch.state.lock().expect("state lock").running = false;
+        }
+
+        let statuses = registry.statuses(None).expect("statuses expected");
+        assert_eq!(statuses.len(), MAX_FINISHED_BATCHES + 1);
+        assert_eq!(statuses[0].batch_id, running.id);
+    }
+
+    #[test]
+    fn job_status_reports_individually_cancelled_jobs() {
+        assert_eq!(
+            job_status(&Err(MERGE_CANCELLED.to_string()), true),
+            MergeBatchJobStatus::Cancelled
+        );
+        assert_eq!(
+            job_status(&Err("FFmpeg merge failed".to_string()), true),
+            MergeBatchJobStatus::Failed
+        );
+        // Jobs of a cancelled batch that never started
+        assert_eq!(
+            job_status(&Err("Cancelled before start".to_string()), false),
+            MergeBatchJobStatus::Cancelled
+        );
+    }
+
+    #[test]
+    fn cancelled_batch_starts_no_further_jobs() {
+        let registry = MergeBatchRegistry::default();
+        let batch = registry.register(&[
+            job("/tmp/a.mkv", "/tmp/a.out.mkv"),
+            job("/tmp/b.mkv", "/tmp/b.out.mkv"),
+        ]);
+
+        assert!(batch.start_job(0));
+        assert_eq!(batch.flag_cancelled(), vec!["/tmp/a.mkv".to_string()]);
+        assert!(!batch.start_job(1));
+    }
+}
@@ -1,22 +1,22 @@
 use crate::shared::process::terminate_process;
+use crate::tools::merge::batch::MergeBatchRegistry;
 
 fn remove_output_file(path: &str) {
     let _ = std::fs::remove_file(path);
 }
 
-/// Cancel a specific merge by video path
-#[tauri::command]
-pub(crate) async fn cancel_merge_file(video_path: String) -> Result<(), String> {
+/// Stop the merge of one video and remove its partial output
+pub(super) fn cancel_video_merge(video_path: &str) -> Result<(), String> {
     let pid = {
         match super::state::MERGE_PROCESS_IDS.lock() {
-            Ok(mut guard) => guard.remove(&video_path),
+            Ok(mut guard) => guard.remove(video_path),
             Err(_) => return Err("Failed to acquire process lock".to_string()),
         }
     };
 
     let output_path = {
         match super::state::MERGE_OUTPUT_PATHS.lock() {
-            Ok(mut guard) => guard.remove(&video_path),
+            Ok(mut guard) => guard.remove(video_path),
             Err(_) => None,
         }
     };
@@ -32,9 +32,14 @@ pub(crate) async fn cancel_merge_file(video_path: String) -> Result<(), String>
     Ok(())
 }
 
-/// Cancel all ongoing merges
+/// Cancel a specific merge by video path
 #[tauri::command]
-pub(crate) async fn cancel_merge() -> Result<(), String> {
+pub(crate) async fn cancel_merge_file(video_path: String) -> Result<(), String> {
+    cancel_video_merge(&video_path)
+}
+
+/// Stop every running merge and remove their partial outputs
+fn cancel_all_merges() -> Result<(), String> {
     let pids: Vec<u32> = {
         match super::state::MERGE_PROCESS_IDS.lock() {
             Ok(mut guard) => {
@@ -68,11 +73,20 @@ pub(crate) async fn cancel_merge() -> Result<(), String> {
     Ok(())
 }
 
+/// Cancel all ongoing merges, including the queued jobs of every batch
+#[tauri::command]
+pub(crate) async fn cancel_merge(
+    batches: tauri::State<'_, MergeBatchRegistry>,
+) -> Result<(), String> {
+    batches.cancel_all();
+    cancel_all_merges()
+}
+
 #[cfg(test)]
 mod tests {
     use serial_test::serial;
 
-    use super::{cancel_merge, cancel_merge_file};
+    use super::{cancel_all_merges, cancel_merge_file};
 
     #[tokio::test]
     #[serial]
@@ -138,7 +152,7 @@ mod tests {
             outputs.insert("video-b".to_string(), out_b.to_string_lossy().to_string());
         }
 
-        cancel_merge().await.expect("cancel all should succeed");
+        cancel_all_merges().expect("cancel all should succeed");
 
         assert!(!out_a.exists());
         assert!(!out_b.exists());
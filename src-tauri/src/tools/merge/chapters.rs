@@ -0,0 +1,236 @@
+use crate::shared::chapters::{
+    Chapter, chapters_from_probe, normalize_chapters, parse_matroska_chapters_xml,
+    parse_ogm_chapters, to_ffmetadata,
+};
+use crate::shared::clip::ClipCut;
+use crate::tools::merge::job::{MergeChapterImport, MergeJob, SourceChapterMode};
+use serde_json::Value;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static CHAPTERS_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// How `build_merge_args` maps chapters into the output
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) enum ChapterMapping {
+    /// Let ffmpeg copy the chapters of the source video
+    #[default]
+    Source,
+    Drop,
+    /// Read chapters from an FFMETADATA file
+    Metadata(String),
+}
+
+/// Delay applied to the source video stream, falling back to the first enabled source track
+fn source_video_delay_ms(job: &MergeJob) -> i64 {
+    let Some(configs) = job.source_track_configs.as_deref() else {
+        return 0;
+    };
+
+    let enabled = || configs.iter().filter(|c| c.is_enabled());
+    enabled()
+        .find(|c| c.track_type.as_deref() == Some("video"))
+        .or_else(|| enabled().next())
+        .map_or(0, |c| c.delay_ms())
+}
+
+/// Move chapters by a delay, dropping those that end up entirely before zero
+pub(super) fn shift_chapters(chapters: Vec<Chapter>, shift_ms: i64) -> Vec<Chapter> {
+    let shift = |ms: u64| (ms as i64).saturating_add(shift_ms).max(0) as u64;
+
+    chapters
+        .into_iter()
+        .filter(|chapter| match chapter.end_ms {
+            Some(end_ms) => (end_ms as i64).saturating_add(shift_ms) > 0,
+            None => (chapter.start_ms as i64).saturating_add(shift_ms) >= 0,
+        })
+        .map(|chapter| Chapter {
+            start_ms: shift(chapter.start_ms),
+            end_ms: chapter.end_ms.map(shift),
+            title: chapter.title,
+        })
+        .collect()
+}
+
+/// Keep the chapters inside a clip, moved so that the clip starts at zero
+pub(super) fn clip_chapters(chapters: Vec<Chapter>, clip: &ClipCut) -> Vec<Chapter> {
+    let shifted = shift_chapters(chapters, -(clip.start_ms as i64));
+    let Some(length_ms) = clip.duration_ms() else {
+        return shifted;
+    };
+
+    shifted
+        .into_iter()
+        .filter(|chapter| chapter.start_ms < length_ms)
+        .map(|chapter| Chapter {
+            end_ms: chapter.end_ms.map(|end_ms| end_ms.min(length_ms)),
+            ..chapter
+        })
+        .collect()
+}
+
+fn read_chapter_file(path: &str) -> Result<String, String> {
+    std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read chapter file {}: {}", path, e))
+}
+
+/// Chapters the output should carry, or None to pass the source chapters through
+pub(super) fn resolve_output_chapters(
+    job: &MergeJob,
+    source_probe: &Value,
+    total_ms: Option<u64>,
+) -> Result<Option<Vec<Chapter>>, String> {
+    let chapters = match &job.chapters {
+        Some(MergeChapterImport::Ogm { path }) => parse_ogm_chapters(&read_chapter_file(path)?)?,
+        Some(MergeChapterImport::MatroskaXml { path }) => {
+            parse_matroska_chapters_xml(&read_chapter_file(path)?)?
+        }
+        Some(MergeChapterImport::List { chapters }) => chapters.clone(),
+        None => match job.source_chapters {
+            SourceChapterMode::Keep => return Ok(None),
+            SourceChapterMode::Drop => Vec::new(),
+            SourceChapterMode::Shift => {
+                let shift_ms = job
+                    .chapter_shift_ms
+                    .unwrap_or_else(|| source_video_delay_ms(job));
+                shift_chapters(chapters_from_probe(source_probe), shift_ms)
+            }
+        },
+    };
+
+    Ok(Some(normalize_chapters(chapters, total_ms)))
+}
+
+/// Fresh FFMETADATA path in the temp directory
+pub(super) fn chapters_metadata_path() -> String {
+    std::env::temp_dir()
+        .join(format!(
+            "mediaflow-chapters-{}-{}.txt",
+            std::process::id(),
+            CHAPTERS_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// FFMETADATA file that lives as long as the merge using it
+pub(super) struct ChaptersMetadataFile {
+    path: Option<String>,
+}
+
+impl ChaptersMetadataFile {
+    pub(super) fn write(path: Option<&str>, chapters: Option<&[Chapter]>) -> Result<Self, String> {
+        let (Some(path), Some(chapters)) = (path, chapters) else {
+            return Ok(Self { path: None });
+        };
+
+        std::fs::write(path, to_ffmetadata(chapters))
+            .map_err(|e| format!("Failed to write chapter metadata: {}", e))?;
+        Ok(Self {
+            path: Some(path.to_string()),
+        })
+    }
+}
+
+impl Drop for ChaptersMetadataFile {
+    fn drop(&mut self) {
+        if let Some(path) = &self.path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{resolve_output_chapters, shift_chapters};
+    use crate::shared::chapters::Chapter;
+    use crate::tools::merge::job::MergeJob;
+
+    fn chapter(start_ms: u64, end_ms: u64) -> Chapter {
+        Chapter {
+            start_ms,
+            end_ms: Some(end_ms),
+            title: None,
+        }
+    }
+
+    fn source_probe() -> serde_json::Value {
+        json!({"chapters": [
+            {"start_time": "0.000000", "end_time": "60.000000", "tags": {"title": "A"}},
+            {"start_time": "60.000000", "end_time": "120.000000", "tags": {"title": "B"}}
+        ]})
+    }
+
+    #[test]
+    fn shift_chapters_clamps_and_drops_chapters_before_zero() {
+        let shifted = shift_chapters(vec![chapter(0, 1_000), chapter(1_000, 5_000)], -2_000);
+
+        assert_eq!(shifted, vec![chapter(0, 3_000)]);
+        assert_eq!(
+            shift_chapters(vec![chapter(0, 1_000)], 500),
+            vec![chapter(500, 1_500)]
+        );
+    }
+
+    #[test]
+    fn resolve_output_chapters_follows_source_mode() {
+        let job = |extra: serde_json::Value| -> MergeJob {
+            let mut value = json!({"videoPath": "/tmp/v.mkv", "outputPath": "/tmp/o.mkv"});
+            value
+                .as_object_mut()
+                .expect("job object")
+                .extend(extra.as_object().expect("extra object").clone());
+            serde_json::from_value(value).expect("job should deserialize")
+        };
+
+        let keep = resolve_output_chapters(&job(json!({})), &source_probe(), None)
+            .expect("keep should resolve");
+        assert_eq!(keep, None);
+
+        let dropped = resolve_output_chapters(
+            &job(json!({"sourceChapters": "drop"})),
+            &source_probe(),
+            None,
+        )
+        .expect("drop should resolve");
+        assert_eq!(dropped, Some(vec![]));
+
+        let shifted = resolve_output_chapters(
+            &job(json!({
+                "sourceChapters": "shift",
+                "sourceTrackConfigs": [
+                    {"originalIndex": 0, "type": "video", "config": {"delayMs": 1500}}
+                ]
+            })),
+            &source_probe(),
+            Some(121_500),
+        )
+        .expect("shift should resolve")
+        .expect("shift should produce chapters");
+        assert_eq!(shifted[0].start_ms, 1_500);
+        assert_eq!(shifted[1].end_ms, Some(121_500));
+    }
+
+    #[test]
+    fn resolve_output_chapters_reads_ogm_file() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let path = temp.path().join("chapters.txt");
+        std::fs::write(&path, "CHAPTER01=00:00:10.000\nCHAPTER01NAME=Cold Open\n")
+            .expect("failed to write chapters");
+        let job: MergeJob = serde_json::from_value(json!({
+            "videoPath": "/tmp/v.mkv",
+            "outputPath": "/tmp/o.mkv",
+            "chapters": {"source": "ogm", "path": path.to_string_lossy()}
+        }))
+        .expect("job should deserialize");
+
+        let chapters = resolve_output_chapters(&job, &source_probe(), Some(30_000))
+            .expect("ogm should resolve")
+            .expect("ogm should produce chapters");
+
+        assert_eq!(chapters.len(), 1);
+        assert_eq!(chapters[0].title.as_deref(), Some("Cold Open"));
+        assert_eq!(chapters[0].end_ms, Some(30_000));
+    }
+}
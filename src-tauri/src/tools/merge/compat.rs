@@ -0,0 +1,438 @@
+use crate::tools::merge::plan::{MergePlanStream, output_stream_specifier};
+use serde::Serialize;
+use std::path::Path;
+
+/// Bitrate used when incompatible audio is transcoded for MP4/MOV
+const MP4_AUDIO_FALLBACK_BITRATE: &str = "448k";
+
+/// Output container families with different codec support
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum OutputContainer {
+    Matroska,
+    Mp4,
+    Mov,
+    WebM,
+    /// Anything else is passed through without checks
+    Other,
+}
+
+impl OutputContainer {
+    pub(crate) fn from_path(path: &str) -> Self {
+        let ext = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        match ext.as_str() {
+            "mkv" | "mka" | "mks" => Self::Matroska,
+            "mp4" | "m4v" | "m4a" => Self::Mp4,
+            "mov" => Self::Mov,
+            "webm" => Self::WebM,
+            _ => Self::Other,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Matroska => "Matroska",
+            Self::Mp4 => "MP4",
+            Self::Mov => "MOV",
+            Self::WebM => "WebM",
+            Self::Other => "output",
+        }
+    }
+}
+
+/// Stream that is re-encoded instead of copied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct StreamConversion {
+    pub(crate) output_index: usize,
+    /// Type-relative ffmpeg stream specifier such as "a:1"
+    pub(crate) specifier: String,
+    pub(crate) from_codec: String,
+    /// Codec name ffprobe reports for the converted stream
+    pub(crate) to_codec: String,
+    pub(crate) encoder: String,
+    pub(crate) bitrate: Option<String>,
+    pub(crate) channel_layout: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum UnsupportedStreamKind {
+    Video,
+    Audio,
+    Subtitle,
+    Attachment,
+}
+
+/// A stream the output container cannot hold
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeCompatibilityIssue {
+    pub(crate) kind: UnsupportedStreamKind,
+    pub(crate) output_index: usize,
+    pub(crate) input_path: String,
+    pub(crate) input_stream_index: usize,
+    pub(crate) codec: String,
+    pub(crate) container: OutputContainer,
+    pub(crate) message: String,
+}
+
+/// Conversions, blocking issues and warnings for one output layout
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CompatibilityCheck {
+    pub(crate) conversions: Vec<StreamConversion>,
+    pub(crate) issues: Vec<MergeCompatibilityIssue>,
+    pub(crate) warnings: Vec<String>,
+}
+
+const TEXT_SUBTITLE_CODECS: &[&str] =
+    &["subrip", "srt", "ass", "ssa", "webvtt", "mov_text", "text"];
+const MP4_UNSUPPORTED_VIDEO: &[&str] = &[
+    "vp8", "theora", "wmv1", "wmv2", "wmv3", "rv30", "rv40", "flv1",
+];
+const MP4_UNSUPPORTED_AUDIO: &[&str] = &[
+    "truehd", "mlp", "wmav1", "wmav2", "wmapro", "cook", "vorbis",
+];
+const WEBM_VIDEO: &[&str] = &["vp8", "vp9", "av1"];
+const WEBM_AUDIO: &[&str] = &["opus", "vorbis"];
+
+enum CodecAction {
+    Copy,
+    Convert {
+        to_codec: &'static str,
+        encoder: &'static str,
+        bitrate: Option<&'static str>,
+    },
+    /// Only convertible when audio transcoding is allowed
+    TranscodeAudio {
+        to_codec: &'static str,
+        encoder: &'static str,
+        bitrate: Option<&'static str>,
+    },
+    Reject(&'static str),
+}
+
+fn codec_action(container: OutputContainer, codec_type: &str, codec: &str) -> CodecAction {
+    let is_text_subtitle = TEXT_SUBTITLE_CODECS.contains(&codec);
+
+    match (container, codec_type) {
+        (OutputContainer::Matroska, "subtitle") if codec == "mov_text" => CodecAction::Convert {
+            to_codec: "subrip",
+            encoder: "srt",
+            bitrate: None,
+        },
+        (OutputContainer::Mp4 | OutputContainer::Mov, "video") => {
+            if MP4_UNSUPPORTED_VIDEO.contains(&codec) {
+                CodecAction::Reject("video codec cannot be stored")
+            } else {
+                CodecAction::Copy
+            }
+        }
+        (OutputContainer::Mp4 | OutputContainer::Mov, "audio") => {
+            let is_pcm_in_mp4 = container == OutputContainer::Mp4 && codec.starts_with("pcm_");
+            if MP4_UNSUPPORTED_AUDIO.contains(&codec) || is_pcm_in_mp4 {
+                CodecAction::TranscodeAudio {
+                    to_codec: "aac",
+                    encoder: "aac",
+                    bitrate: Some(MP4_AUDIO_FALLBACK_BITRATE),
+                }
+            } else {
+                CodecAction::Copy
+            }
+        }
+        (OutputContainer::Mp4 | OutputContainer::Mov, "subtitle") => {
+            if codec == "mov_text" || (codec == "dvd_subtitle" && container == OutputContainer::Mp4)
+            {
+                CodecAction::Copy
+            } else if is_text_subtitle {
+                CodecAction::Convert {
+                    to_codec: "mov_text",
+                    encoder: "mov_text",
+                    bitrate: None,
+                }
+            } else {
+                CodecAction::Reject("image-based subtitles cannot be stored")
+            }
+        }
+        (OutputContainer::WebM, "video") => {
+            if WEBM_VIDEO.contains(&codec) {
+                CodecAction::Copy
+            } else {
+                CodecAction::Reject("only VP8, VP9 and AV1 video can be stored")
+            }
+        }
+        (OutputContainer::WebM, "audio") => {
+            if WEBM_AUDIO.contains(&codec) {
+                CodecAction::Copy
+            } else {
+                CodecAction::TranscodeAudio {
+                    to_codec: "opus",
+                    encoder: "libopus",
+                    bitrate: None,
+                }
+            }
+        }
+        (OutputContainer::WebM, "subtitle") => {
+            if codec == "webvtt" {
+                CodecAction::Copy
+            } else if is_text_subtitle {
+                CodecAction::Convert {
+                    to_codec: "webvtt",
+                    encoder: "webvtt",
+                    bitrate: None,
+                }
+            } else {
+                CodecAction::Reject("only text subtitles can be stored")
+            }
+        }
+        // Fonts and cover art picked from other inputs have no MP4/MOV/WebM mapping
+        (container, "attachment") if container != OutputContainer::Matroska => {
+            CodecAction::Reject("attachments cannot be stored")
+        }
+        _ => CodecAction::Copy,
+    }
+}
+
+fn issue_kind(codec_type: &str) -> UnsupportedStreamKind {
+    match codec_type {
+        "video" => UnsupportedStreamKind::Video,
+        "audio" => UnsupportedStreamKind::Audio,
+        "attachment" => UnsupportedStreamKind::Attachment,
+        _ => UnsupportedStreamKind::Subtitle,
+    }
+}
+
+/// Decide per output stream whether it can be copied, must be converted, or blocks the merge
+pub(crate) fn check_output_compatibility(
+    output_path: &str,
+    streams: &[MergePlanStream],
+    transcode_incompatible_audio: bool,
+) -> CompatibilityCheck {
+    let container = OutputContainer::from_path(output_path);
+    let mut check = CompatibilityCheck::default();
+
+    for stream in streams {
+        let (Some(codec_type), Some(codec)) =
+            (stream.codec_type.as_deref(), stream.codec.as_deref())
+        else {
+            continue;
+        };
+
+        let mut convert = |to_codec: &str, encoder: &str, bitrate: Option<&str>| {
+            check.conversions.push(StreamConversion {
+                output_index: stream.index,
+                specifier: output_stream_specifier(streams, stream.index),
+                from_codec: codec.to_string(),
+                to_codec: to_codec.to_string(),
+                encoder: encoder.to_string(),
+                bitrate: bitrate.map(str::to_string),
+                channel_layout: None,
+            });
+        };
+
+        match codec_action(container, codec_type, codec) {
+            CodecAction::Copy => {}
+            CodecAction::Convert {
+                to_codec,
+                encoder,
+                bitrate,
+            } => {
+                convert(to_codec, encoder, bitrate);
+                if matches!(codec, "ass" | "ssa") {
+                    check.warnings.push(format!(
+                        "Stream {}: ASS styling is lost when converting to {}",
+                        stream.index, to_codec
+                    ));
+                }
+            }
+            CodecAction::TranscodeAudio {
+                to_codec,
+                encoder,
+                bitrate,
+            } if transcode_incompatible_audio => {
+                convert(to_codec, encoder, bitrate);
+                check.warnings.push(format!(
+                    "Stream {}: {} audio is transcoded to {} for {}",
+                    stream.index,
+                    codec,
+                    to_codec,
+                    container.label()
+                ));
+            }
+            CodecAction::TranscodeAudio { .. } => {
+                check.issues.push(MergeCompatibilityIssue {
+                    kind: issue_kind(codec_type),
+                    output_index: stream.index,
+                    input_path: stream.input_path.clone(),
+                    input_stream_index: stream.input_stream_index,
+                    codec: codec.to_string(),
+                    container,
+                    message: format!(
+                        "{} audio cannot be stored in {}; enable audio transcoding or use MKV",
+                        codec,
+                        container.label()
+                    ),
+                });
+            }
+            CodecAction::Reject(reason) => {
+                check.issues.push(MergeCompatibilityIssue {
+                    kind: issue_kind(codec_type),
+                    output_index: stream.index,
+                    input_path: stream.input_path.clone(),
+                    input_stream_index: stream.input_stream_index,
+                    codec: codec.to_string(),
+                    container,
+                    message: format!("{}: {} in {}", codec, reason, container.label()),
+                });
+            }
+        }
+    }
+
+    check
+}
+
+/// Turn blocking issues into the error returned before ffmpeg runs
+pub(crate) fn ensure_compatible(issues: &[MergeCompatibilityIssue]) -> Result<(), String> {
+    if issues.is_empty() {
+        return Ok(());
+    }
+
+    Err(format!(
+        "Incompatible output: {}",
+        issues
+            .iter()
+            .map(|issue| format!(
+                "stream {} ({}:{}): {}",
+                issue.output_index, issue.input_path, issue.input_stream_index, issue.message
+            ))
+            .collect::<Vec<_>>()
+            .join("; ")
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        OutputContainer, UnsupportedStreamKind, check_output_compatibility, ensure_compatible,
+    };
+    use crate::tools::merge::plan::MergePlanStream;
+
+    fn stream(index: usize, codec_type: &str, codec: &str) -> MergePlanStream {
+        MergePlanStream {
+            index,
+            input_index: 0,
+            input_path: "/tmp/video.mkv".to_string(),
+            input_stream_index: index,
+            codec_type: Some(codec_type.to_string()),
+            codec: Some(codec.to_string()),
+            language: None,
+            title: None,
+            default: false,
+            forced: false,
+            audio_encode: None,
+        }
+    }
+
+    #[test]
+    fn output_container_is_detected_from_extension() {
+        assert_eq!(
+            OutputContainer::from_path("/tmp/a.MKV"),
+            OutputContainer::Matroska
+        );
+        assert_eq!(
+            OutputContainer::from_path("/tmp/a.m4v"),
+            OutputContainer::Mp4
+        );
+        assert_eq!(
+            OutputContainer::from_path("/tmp/a.avi"),
+            OutputContainer::Other
+        );
+    }
+
+    #[test]
+    fn mp4_converts_text_subtitles_to_mov_text() {
+        let streams = vec![
+            stream(0, "video", "h264"),
+            stream(1, "subtitle", "subrip"),
+            stream(2, "subtitle", "ass"),
+        ];
+
+        let check = check_output_compatibility("/tmp/out.mp4", &streams, false);
+
+        assert!(check.issues.is_empty());
+        assert_eq!(check.conversions.len(), 2);
+        assert!(check.conversions.iter().all(|c| c.encoder == "mov_text"));
+        assert!(check.warnings.iter().any(|w| w.contains("ASS styling")));
+    }
+
+    #[test]
+    fn mp4_rejects_truehd_and_pgs_unless_audio_transcode_is_allowed() {
+        let streams = vec![
+            stream(0, "video", "hevc"),
+            stream(1, "audio", "truehd"),
+            stream(2, "subtitle", "hdmv_pgs_subtitle"),
+        ];
+
+        let check = check_output_compatibility("/tmp/out.mp4", &streams, false);
+        let kinds: Vec<UnsupportedStreamKind> = check.issues.iter().map(|i| i.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                UnsupportedStreamKind::Audio,
+                UnsupportedStreamKind::Subtitle
+            ]
+        );
+        let error = ensure_compatible(&check.issues).expect_err("issues should fail");
+        assert!(error.contains("stream 1"));
+        assert!(error.contains("truehd audio cannot be stored in MP4"));
+
+        let check = check_output_compatibility("/tmp/out.mp4", &streams, true);
+        assert_eq!(check.issues.len(), 1);
+        assert_eq!(check.conversions[0].output_index, 1);
+        assert_eq!(check.conversions[0].to_codec, "aac");
+    }
+
+    #[test]
+    fn attachments_are_rejected_outside_matroska() {
+        let streams = vec![stream(0, "video", "av1"), stream(1, "attachment", "ttf")];
+
+        for output in [
+            "/tmp/out.mp4",
+            "/tmp/out.mov",
+            "/tmp/out.webm",
+            "/tmp/out.avi",
+        ] {
+            let check = check_output_compatibility(output, &streams, true);
+            let kinds: Vec<UnsupportedStreamKind> = check.issues.iter().map(|i| i.kind).collect();
+            assert_eq!(kinds, vec![UnsupportedStreamKind::Attachment], "{}", output);
+            assert!(
+                check.issues[0]
+                    .message
+                    .contains("attachments cannot be stored in")
+            );
+        }
+
+        let check = check_output_compatibility("/tmp/out.mkv", &streams, false);
+        assert!(check.issues.is_empty());
+        assert!(check.conversions.is_empty());
+    }
+
+    #[test]
+    fn matroska_copies_everything_except_mov_text() {
+        let streams = vec![
+            stream(0, "audio", "truehd"),
+            stream(1, "subtitle", "hdmv_pgs_subtitle"),
+            stream(2, "subtitle", "mov_text"),
+        ];
+
+        let check = check_output_compatibility("/tmp/out.mkv", &streams, false);
+
+        assert!(check.issues.is_empty());
+        assert_eq!(check.conversions.len(), 1);
+        assert_eq!(check.conversions[0].to_codec, "subrip");
+    }
+}
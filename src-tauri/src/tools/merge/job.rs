@@ -0,0 +1,1256 @@
+use crate::shared::chapters::Chapter;
+use crate::shared::clip::ClipRange;
+use crate::shared::language::{normalize_language_code, same_language};
+use crate::shared::output::OverwritePolicy;
+use crate::shared::validation::{is_valid_bitrate, validate_media_path, validate_output_path};
+use crate::tools::merge::retime::SubtitleFormat;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Largest delay accepted for a single track (24 hours)
+const MAX_TRACK_DELAY_MS: i64 = 24 * 60 * 60 * 1000;
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Per-track settings shared by source and attached tracks
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct MergeTrackConfig {
+    #[serde(default)]
+    pub(crate) track_id: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub(crate) enabled: bool,
+    #[serde(default)]
+    pub(crate) language: Option<String>,
+    #[serde(default)]
+    pub(crate) title: Option<String>,
+    #[serde(default)]
+    pub(crate) default: bool,
+    #[serde(default)]
+    pub(crate) forced: bool,
+    #[serde(default)]
+    pub(crate) delay_ms: i64,
+    #[serde(default)]
+    pub(crate) order: Option<u32>,
+    #[serde(default)]
+    pub(crate) audio_encode: Option<AudioEncodeConfig>,
+    #[serde(default)]
+    pub(crate) retime: Option<SubtitleRetime>,
+}
+
+impl MergeTrackConfig {
+    /// Configured language as an ISO 639-2 code, e.g. "en" becomes "eng"
+    pub(crate) fn language_code(&self) -> Option<String> {
+        let language = self.language.as_deref().filter(|l| !l.is_empty())?;
+        Some(normalize_language_code(language).unwrap_or_else(|| language.to_string()))
+    }
+}
+
+/// Smallest and largest accepted subtitle stretch factors
+const RETIME_FACTOR_RANGE: std::ops::RangeInclusive<f64> = 0.5..=2.0;
+
+/// Time stretch for an attached subtitle file, as a factor or a source→target fps pair
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct SubtitleRetime {
+    #[serde(default)]
+    pub(crate) factor: Option<f64>,
+    #[serde(default)]
+    pub(crate) source_fps: Option<f64>,
+    #[serde(default)]
+    pub(crate) target_fps: Option<f64>,
+}
+
+impl SubtitleRetime {
+    /// Multiplier applied to every timestamp; 25→23.976 fps slows cues down
+    pub(crate) fn factor(&self) -> Option<f64> {
+        match (self.factor, self.source_fps, self.target_fps) {
+            (Some(factor), None, None) => Some(factor),
+            (None, Some(source), Some(target)) if source > 0.0 && target > 0.0 => {
+                Some(source / target)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Codecs an audio track can be re-encoded to, with the ffmpeg encoder used for each
+const AUDIO_ENCODERS: &[(&str, &str)] = &[
+    ("aac", "aac"),
+    ("ac3", "ac3"),
+    ("eac3", "eac3"),
+    ("flac", "flac"),
+    ("opus", "libopus"),
+    ("mp3", "libmp3lame"),
+];
+
+const CHANNEL_LAYOUTS: &[&str] = &[
+    "mono",
+    "stereo",
+    "2.1",
+    "quad",
+    "5.0",
+    "5.1",
+    "5.1(side)",
+    "7.1",
+];
+
+/// Re-encode settings for an audio track that would otherwise be copied
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct AudioEncodeConfig {
+    pub(crate) codec: String,
+    /// ffmpeg bitrate such as "640k"; the encoder default when unset
+    #[serde(default)]
+    pub(crate) bitrate: Option<String>,
+    #[serde(default)]
+    pub(crate) channel_layout: Option<String>,
+}
+
+impl AudioEncodeConfig {
+    pub(crate) fn encoder(&self) -> Option<&'static str> {
+        AUDIO_ENCODERS
+            .iter()
+            .find(|(codec, _)| *codec == self.codec)
+            .map(|(_, encoder)| *encoder)
+    }
+}
+
+/// Stream of the source video, selected by its ffprobe index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct MergeSourceTrack {
+    pub(crate) original_index: usize,
+    #[serde(default, rename = "type")]
+    pub(crate) track_type: Option<String>,
+    #[serde(default)]
+    pub(crate) config: Option<MergeTrackConfig>,
+}
+
+impl MergeSourceTrack {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.config.as_ref().is_none_or(|config| config.enabled)
+    }
+
+    pub(crate) fn delay_ms(&self) -> i64 {
+        self.config.as_ref().map_or(0, |config| config.delay_ms)
+    }
+}
+
+/// External file muxed into the output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct MergeAttachedTrack {
+    pub(crate) input_path: String,
+    #[serde(default)]
+    pub(crate) track_index: usize,
+    #[serde(default)]
+    pub(crate) config: Option<MergeTrackConfig>,
+}
+
+impl MergeAttachedTrack {
+    pub(crate) fn delay_ms(&self) -> i64 {
+        self.config.as_ref().map_or(0, |config| config.delay_ms)
+    }
+}
+
+/// External stream that takes a source stream's place in the output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct MergeReplacement {
+    /// Source stream being replaced
+    pub(crate) original_index: usize,
+    pub(crate) input_path: String,
+    #[serde(default)]
+    pub(crate) track_index: usize,
+    /// Without a config the replaced stream's language and title are kept
+    #[serde(default)]
+    pub(crate) config: Option<MergeTrackConfig>,
+}
+
+impl MergeReplacement {
+    pub(crate) fn delay_ms(&self) -> i64 {
+        self.config.as_ref().map_or(0, |config| config.delay_ms)
+    }
+}
+
+/// A track in `outputOrder`: `{"source": originalIndex}` or `{"attached": trackPosition}`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum MergeTrackRef {
+    Source(usize),
+    Attached(usize),
+}
+
+/// Drops every source track matching all of its set fields
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct TrackDropRule {
+    #[serde(default, rename = "type")]
+    pub(crate) track_type: Option<String>,
+    #[serde(default)]
+    pub(crate) language: Option<String>,
+    #[serde(default)]
+    pub(crate) codec: Option<String>,
+    /// Case-insensitive substring of the track title, e.g. "commentary"
+    #[serde(default)]
+    pub(crate) title_contains: Option<String>,
+}
+
+impl TrackDropRule {
+    fn is_empty(&self) -> bool {
+        self.track_type.is_none()
+            && self.language.is_none()
+            && self.codec.is_none()
+            && self.title_contains.is_none()
+    }
+
+    /// Whether an ffprobe stream matches the rule
+    pub(crate) fn matches(&self, stream: &Value) -> bool {
+        let field = |key: &str| stream.get(key).and_then(|v| v.as_str());
+        let tag = |key: &str| {
+            stream
+                .get("tags")
+                .and_then(|tags| tags.get(key))
+                .and_then(|v| v.as_str())
+        };
+        let equals = |expected: &Option<String>, actual: Option<&str>| {
+            expected
+                .as_deref()
+                .is_none_or(|expected| actual.is_some_and(|a| a.eq_ignore_ascii_case(expected)))
+        };
+
+        !self.is_empty()
+            && equals(&self.track_type, field("codec_type"))
+            && self.language.as_deref().is_none_or(|expected| {
+                tag("language").is_some_and(|actual| same_language(expected, actual))
+            })
+            && equals(&self.codec, field("codec_name"))
+            && self.title_contains.as_deref().is_none_or(|needle| {
+                tag("title")
+                    .is_some_and(|title| title.to_lowercase().contains(&needle.to_lowercase()))
+            })
+    }
+}
+
+/// Attachment already in the source video, kept unless disabled
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct MergeSourceAttachment {
+    pub(crate) original_index: usize,
+    #[serde(default = "default_enabled")]
+    pub(crate) enabled: bool,
+}
+
+/// File attached to the output container (fonts, cover art)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct MergeAttachment {
+    pub(crate) input_path: String,
+    #[serde(default)]
+    pub(crate) file_name: Option<String>,
+    #[serde(default)]
+    pub(crate) mime_type: Option<String>,
+}
+
+/// Extensions recognised for attachments and their Matroska mimetypes
+const ATTACHMENT_MIME_TYPES: &[(&str, &str)] = &[
+    ("ttf", "application/x-truetype-font"),
+    ("ttc", "application/x-truetype-font"),
+    ("otf", "application/vnd.ms-opentype"),
+    ("woff", "font/woff"),
+    ("woff2", "font/woff2"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("png", "image/png"),
+    ("webp", "image/webp"),
+];
+
+impl MergeAttachment {
+    /// Name stored in the container, defaulting to the input file name
+    pub(crate) fn file_name(&self) -> String {
+        self.file_name
+            .clone()
+            .filter(|name| !name.trim().is_empty())
+            .unwrap_or_else(|| {
+                Path::new(&self.input_path)
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            })
+    }
+
+    /// Explicit mimetype, or one inferred from the file extension
+    pub(crate) fn mime_type(&self) -> Option<String> {
+        if let Some(mime_type) = self.mime_type.as_deref()
+            && !mime_type.trim().is_empty()
+        {
+            return Some(mime_type.to_string());
+        }
+
+        let ext = Path::new(&self.input_path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        ATTACHMENT_MIME_TYPES
+            .iter()
+            .find(|(known, _)| *known == ext)
+            .map(|(_, mime_type)| mime_type.to_string())
+    }
+}
+
+/// Only Matroska outputs can carry attachments
+pub(crate) fn supports_attachments(output_path: &str) -> bool {
+    let ext = Path::new(output_path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    matches!(ext.as_str(), "mkv" | "mka" | "mks")
+}
+
+/// Replacement chapters for the output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "source", rename_all = "camelCase")]
+pub(crate) enum MergeChapterImport {
+    /// OGM text file (`CHAPTER01=00:00:00.000`)
+    Ogm { path: String },
+    /// Matroska chapter XML file
+    MatroskaXml { path: String },
+    /// Explicit timestamps and titles
+    List { chapters: Vec<Chapter> },
+}
+
+impl MergeChapterImport {
+    fn path(&self) -> Option<&str> {
+        match self {
+            Self::Ogm { path } | Self::MatroskaXml { path } => Some(path),
+            Self::List { .. } => None,
+        }
+    }
+}
+
+/// What happens to the source chapters when no replacement is imported
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum SourceChapterMode {
+    #[default]
+    Keep,
+    Drop,
+    /// Move them by `chapterShiftMs`, or by the source video delay when unset
+    Shift,
+}
+
+/// A complete merge request for one video
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct MergeJob {
+    pub(crate) video_path: String,
+    #[serde(default)]
+    pub(crate) tracks: Vec<MergeAttachedTrack>,
+    #[serde(default)]
+    pub(crate) source_track_configs: Option<Vec<MergeSourceTrack>>,
+    pub(crate) output_path: String,
+    #[serde(default)]
+    pub(crate) duration_us: Option<u64>,
+    #[serde(default)]
+    pub(crate) overwrite_policy: OverwritePolicy,
+    #[serde(default)]
+    pub(crate) attachments: Vec<MergeAttachment>,
+    #[serde(default)]
+    pub(crate) source_attachments: Option<Vec<MergeSourceAttachment>>,
+    #[serde(default)]
+    pub(crate) chapters: Option<MergeChapterImport>,
+    #[serde(default)]
+    pub(crate) source_chapters: SourceChapterMode,
+    #[serde(default)]
+    pub(crate) chapter_shift_ms: Option<i64>,
+    /// Re-encode audio the output container cannot hold instead of failing
+    #[serde(default)]
+    pub(crate) transcode_incompatible_audio: bool,
+    #[serde(default)]
+    pub(crate) replacements: Vec<MergeReplacement>,
+    #[serde(default)]
+    pub(crate) drop_rules: Vec<TrackDropRule>,
+    /// Output track order; unlisted tracks follow in their default order
+    #[serde(default)]
+    pub(crate) output_order: Option<Vec<MergeTrackRef>>,
+    /// Keep only this time range of the source
+    #[serde(default)]
+    pub(crate) clip: Option<ClipRange>,
+}
+
+/// A single problem found while validating a merge job
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeJobIssue {
+    pub(crate) field: String,
+    pub(crate) message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeJobValidation {
+    pub(crate) valid: bool,
+    pub(crate) issues: Vec<MergeJobIssue>,
+}
+
+fn push_issue(issues: &mut Vec<MergeJobIssue>, field: String, message: impl Into<String>) {
+    issues.push(MergeJobIssue {
+        field,
+        message: message.into(),
+    });
+}
+
+fn validate_track_config(config: &MergeTrackConfig, field: &str, issues: &mut Vec<MergeJobIssue>) {
+    if let Some(language) = config.language.as_deref()
+        && !language.is_empty()
+        && normalize_language_code(language).is_none()
+    {
+        push_issue(
+            issues,
+            format!("{}.language", field),
+            format!(
+                "Invalid language code \"{}\" (expected e.g. \"eng\" or \"en\")",
+                language
+            ),
+        );
+    }
+
+    if let Some(title) = config.title.as_deref()
+        && title.chars().any(|c| c.is_control())
+    {
+        push_issue(
+            issues,
+            format!("{}.title", field),
+            "Title must not contain control characters",
+        );
+    }
+
+    if config.delay_ms.abs() > MAX_TRACK_DELAY_MS {
+        push_issue(
+            issues,
+            format!("{}.delayMs", field),
+            format!(
+                "Delay {} ms is out of range (maximum is ±{} ms)",
+                config.delay_ms, MAX_TRACK_DELAY_MS
+            ),
+        );
+    }
+
+    if let Some(encode) = &config.audio_encode {
+        if encode.encoder().is_none() {
+            push_issue(
+                issues,
+                format!("{}.audioEncode.codec", field),
+                format!("Unsupported audio codec \"{}\"", encode.codec),
+            );
+        }
+        if let Some(bitrate) = encode.bitrate.as_deref()
+            && !is_valid_bitrate(bitrate)
+        {
+            push_issue(
+                issues,
+                format!("{}.audioEncode.bitrate", field),
+                format!("Invalid bitrate \"{}\" (expected e.g. \"640k\")", bitrate),
+            );
+        }
+        if let Some(layout) = encode.channel_layout.as_deref()
+            && !CHANNEL_LAYOUTS.contains(&layout)
+        {
+            push_issue(
+                issues,
+                format!("{}.audioEncode.channelLayout", field),
+                format!("Unsupported channel layout \"{}\"", layout),
+            );
+        }
+    }
+}
+
+fn validate_retime(
+    retime: &SubtitleRetime,
+    input_path: &str,
+    field: &str,
+    issues: &mut Vec<MergeJobIssue>,
+) {
+    match retime.factor() {
+        None => push_issue(
+            issues,
+            field.to_string(),
+            "Set either factor or both sourceFps and targetFps",
+        ),
+        Some(factor) if !RETIME_FACTOR_RANGE.contains(&factor) => push_issue(
+            issues,
+            field.to_string(),
+            format!(
+                "Stretch factor {:.4} is out of range ({} to {})",
+                factor,
+                RETIME_FACTOR_RANGE.start(),
+                RETIME_FACTOR_RANGE.end()
+            ),
+        ),
+        Some(_) => {}
+    }
+
+    if SubtitleFormat::from_path(input_path).is_none() {
+        push_issue(
+            issues,
+            field.to_string(),
+            "Retiming supports SRT, ASS and VTT files",
+        );
+    }
+}
+
+impl MergeJob {
+    /// Check the job without touching the filesystem
+    pub(crate) fn structural_issues(&self) -> Vec<MergeJobIssue> {
+        let mut issues = Vec::new();
+
+        if let Some(source_configs) = &self.source_track_configs {
+            let mut seen_indices = HashSet::new();
+            for (i, source) in source_configs.iter().enumerate() {
+                let field = format!("sourceTrackConfigs[{}]", i);
+                if !seen_indices.insert(source.original_index) {
+                    push_issue(
+                        &mut issues,
+                        format!("{}.originalIndex", field),
+                        format!(
+                            "Source stream {} is configured more than once",
+                            source.original_index
+                        ),
+                    );
+                }
+                if let Some(config) = &source.config {
+                    validate_track_config(config, &format!("{}.config", field), &mut issues);
+                    if config.retime.is_some() {
+                        push_issue(
+                            &mut issues,
+                            format!("{}.config.retime", field),
+                            "Retiming is only supported for attached subtitle files",
+                        );
+                    }
+                }
+            }
+        }
+
+        for (i, track) in self.tracks.iter().enumerate() {
+            let field = format!("tracks[{}]", i);
+            if track.input_path.trim().is_empty() {
+                push_issue(
+                    &mut issues,
+                    format!("{}.inputPath", field),
+                    "Input path must not be empty",
+                );
+            }
+            if let Some(config) = &track.config {
+                validate_track_config(config, &format!("{}.config", field), &mut issues);
+                if let Some(retime) = &config.retime {
+                    validate_retime(
+                        retime,
+                        &track.input_path,
+                        &format!("{}.config.retime", field),
+                        &mut issues,
+                    );
+                }
+            }
+        }
+
+        let mut replaced_indices = HashSet::new();
+        for (i, replacement) in self.replacements.iter().enumerate() {
+            let field = format!("replacements[{}]", i);
+            if replacement.input_path.trim().is_empty() {
+                push_issue(
+                    &mut issues,
+                    format!("{}.inputPath", field),
+                    "Input path must not be empty",
+                );
+            }
+            if !replaced_indices.insert(replacement.original_index) {
+                push_issue(
+                    &mut issues,
+                    format!("{}.originalIndex", field),
+                    format!(
+                        "Source stream {} is replaced more than once",
+                        replacement.original_index
+                    ),
+                );
+            }
+            if let Some(config) = &replacement.config {
+                validate_track_config(config, &format!("{}.config", field), &mut issues);
+            }
+        }
+
+        for (i, rule) in self.drop_rules.iter().enumerate() {
+            if rule.is_empty() {
+                push_issue(
+                    &mut issues,
+                    format!("dropRules[{}]", i),
+                    "Drop rule must set at least one field",
+                );
+            }
+        }
+
+        if let Some(order) = &self.output_order {
+            let mut seen_refs = HashSet::new();
+            for (i, entry) in order.iter().enumerate() {
+                let field = format!("outputOrder[{}]", i);
+                if !seen_refs.insert(*entry) {
+                    push_issue(&mut issues, field, "Track is listed more than once");
+                } else if let MergeTrackRef::Attached(track) = entry
+                    && *track >= self.tracks.len()
+                {
+                    push_issue(
+                        &mut issues,
+                        field,
+                        format!(
+                            "Attached track {} does not exist ({} attached tracks)",
+                            track,
+                            self.tracks.len()
+                        ),
+                    );
+                }
+            }
+        }
+
+        let mut seen_file_names = HashSet::new();
+        for (i, attachment) in self.attachments.iter().enumerate() {
+            let field = format!("attachments[{}]", i);
+            if attachment.input_path.trim().is_empty() {
+                push_issue(
+                    &mut issues,
+                    format!("{}.inputPath", field),
+                    "Input path must not be empty",
+                );
+                continue;
+            }
+            if attachment.mime_type().is_none() {
+                push_issue(
+                    &mut issues,
+                    format!("{}.mimeType", field),
+                    "Unknown attachment type, set a mimetype",
+                );
+            }
+            if !seen_file_names.insert(attachment.file_name().to_lowercase()) {
+                push_issue(
+                    &mut issues,
+                    format!("{}.fileName", field),
+                    format!(
+                        "Attachment name \"{}\" is used more than once",
+                        attachment.file_name()
+                    ),
+                );
+            }
+        }
+
+        match &self.chapters {
+            Some(MergeChapterImport::List { chapters }) => {
+                for (i, chapter) in chapters.iter().enumerate() {
+                    if chapter.end_ms.is_some_and(|end| end < chapter.start_ms) {
+                        push_issue(
+                            &mut issues,
+                            format!("chapters.chapters[{}].endMs", i),
+                            "Chapter ends before it starts",
+                        );
+                    }
+                }
+            }
+            Some(import) if import.path().is_some_and(|p| p.trim().is_empty()) => {
+                push_issue(
+                    &mut issues,
+                    "chapters.path".to_string(),
+                    "Chapter file path must not be empty",
+                );
+            }
+            _ => {}
+        }
+
+        if let Some(shift_ms) = self.chapter_shift_ms
+            && shift_ms.abs() > MAX_TRACK_DELAY_MS
+        {
+            push_issue(
+                &mut issues,
+                "chapterShiftMs".to_string(),
+                format!(
+                    "Chapter shift {} ms is out of range (maximum is ±{} ms)",
+                    shift_ms, MAX_TRACK_DELAY_MS
+                ),
+            );
+        }
+
+        if !self.attachments.is_empty() && !supports_attachments(&self.output_path) {
+            push_issue(
+                &mut issues,
+                "attachments".to_string(),
+                "Attachments require a Matroska output (.mkv, .mka, .mks)",
+            );
+        }
+
+        if let Some(Err(message)) = self.clip.as_ref().map(ClipRange::validate) {
+            push_issue(&mut issues, "clip.endMs".to_string(), message);
+        }
+
+        issues
+    }
+
+    /// Check the job, including that every input exists and the output location is writable
+    pub(crate) fn issues(&self) -> Vec<MergeJobIssue> {
+        let mut issues = Vec::new();
+
+        if let Err(message) = validate_media_path(&self.video_path) {
+            push_issue(&mut issues, "videoPath".to_string(), message);
+        }
+        if let Err(message) = validate_output_path(&self.output_path) {
+            push_issue(&mut issues, "outputPath".to_string(), message);
+        }
+        if self.overwrite_policy == OverwritePolicy::Fail && Path::new(&self.output_path).exists() {
+            push_issue(
+                &mut issues,
+                "outputPath".to_string(),
+                "Output file already exists",
+            );
+        }
+        for (i, track) in self.tracks.iter().enumerate() {
+            if track.input_path.trim().is_empty() {
+                continue;
+            }
+            if let Err(message) = validate_media_path(&track.input_path) {
+                push_issue(&mut issues, format!("tracks[{}].inputPath", i), message);
+            }
+        }
+        for (i, replacement) in self.replacements.iter().enumerate() {
+            if replacement.input_path.trim().is_empty() {
+                continue;
+            }
+            if let Err(message) = validate_media_path(&replacement.input_path) {
+                push_issue(
+                    &mut issues,
+                    format!("replacements[{}].inputPath", i),
+                    message,
+                );
+            }
+        }
+        if let Some(path) = self.chapters.as_ref().and_then(MergeChapterImport::path)
+            && !path.trim().is_empty()
+            && !Path::new(path).is_file()
+        {
+            push_issue(
+                &mut issues,
+                "chapters.path".to_string(),
+                format!("File not found: {}", path),
+            );
+        }
+        for (i, attachment) in self.attachments.iter().enumerate() {
+            let path = Path::new(&attachment.input_path);
+            if !attachment.input_path.trim().is_empty() && !path.is_file() {
+                push_issue(
+                    &mut issues,
+                    format!("attachments[{}].inputPath", i),
+                    format!("File not found: {}", path.display()),
+                );
+            }
+        }
+
+        issues.extend(self.structural_issues());
+        issues
+    }
+
+    /// Validate the job and return every problem as a single error message
+    pub(crate) fn validate(&self) -> Result<(), String> {
+        let issues = self.issues();
+        if issues.is_empty() {
+            return Ok(());
+        }
+
+        Err(format!(
+            "Invalid merge job: {}",
+            issues
+                .iter()
+                .map(|issue| format!("{}: {}", issue.field, issue.message))
+                .collect::<Vec<_>>()
+                .join("; ")
+        ))
+    }
+
+    /// Make sure every configured source stream exists in the probed video
+    pub(crate) fn validate_source_indices(
+        &self,
+        original_stream_count: usize,
+    ) -> Result<(), String> {
+        if let Some(replacement) = self
+            .replacements
+            .iter()
+            .find(|r| r.original_index >= original_stream_count)
+        {
+            return Err(format!(
+                "Replaced stream {} does not exist ({} has {} streams)",
+                replacement.original_index, self.video_path, original_stream_count
+            ));
+        }
+
+        let Some(source_configs) = &self.source_track_configs else {
+            return Ok(());
+        };
+
+        for source in source_configs.iter().filter(|s| s.is_enabled()) {
+            if source.original_index >= original_stream_count {
+                return Err(format!(
+                    "Source stream {} does not exist ({} has {} streams)",
+                    source.original_index, self.video_path, original_stream_count
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Source tracks removed by the drop rules; replaced streams are never dropped
+    pub(crate) fn dropped_source_indices(&self, source_streams: &[Value]) -> Vec<usize> {
+        source_streams
+            .iter()
+            .enumerate()
+            .filter(|(index, stream)| {
+                stream.get("codec_type").and_then(|v| v.as_str()) != Some("attachment")
+                    && !self.replacements.iter().any(|r| r.original_index == *index)
+                    && self.drop_rules.iter().any(|rule| rule.matches(stream))
+            })
+            .map(|(index, _)| index)
+            .collect()
+    }
+
+    /// Source attachment streams that stay in the output, in stream order
+    pub(crate) fn kept_source_attachments(
+        &self,
+        source_attachment_indices: &[usize],
+    ) -> Vec<usize> {
+        if !supports_attachments(&self.output_path) {
+            return Vec::new();
+        }
+
+        let is_disabled = |index: usize| {
+            let dropped_as_attachment = self.source_attachments.as_ref().is_some_and(|configs| {
+                configs
+                    .iter()
+                    .any(|c| c.original_index == index && !c.enabled)
+            });
+            let dropped_as_track = self.source_track_configs.as_ref().is_some_and(|configs| {
+                configs
+                    .iter()
+                    .any(|c| c.original_index == index && !c.is_enabled())
+            });
+            dropped_as_attachment || dropped_as_track
+        };
+
+        source_attachment_indices
+            .iter()
+            .copied()
+            .filter(|index| !is_disabled(*index))
+            .collect()
+    }
+}
+
+fn validate_merge_job_value(job: Value) -> MergeJobValidation {
+    let issues = match serde_json::from_value::<MergeJob>(job) {
+        Ok(job) => job.issues(),
+        Err(e) => vec![MergeJobIssue {
+            field: "job".to_string(),
+            message: e.to_string(),
+        }],
+    };
+
+    MergeJobValidation {
+        valid: issues.is_empty(),
+        issues,
+    }
+}
+
+/// Validate a merge job before it is submitted
+/// Reports unknown fields, invalid languages, out-of-range delays and missing files
+#[tauri::command]
+pub(crate) async fn validate_merge_job(job: Value) -> Result<MergeJobValidation, String> {
+    Ok(validate_merge_job_value(job))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{MergeAttachment, MergeJob, validate_merge_job_value};
+
+    fn parse_job(value: serde_json::Value) -> MergeJob {
+        serde_json::from_value(value).expect("job should deserialize")
+    }
+
+    #[test]
+    fn merge_job_accepts_frontend_payload_shape() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "tracks": [{
+                "inputPath": "/tmp/sub.srt",
+                "trackIndex": 0,
+                "config": {
+                    "trackId": "t1",
+                    "enabled": true,
+                    "language": "eng",
+                    "title": "English",
+                    "default": true,
+                    "forced": false,
+                    "delayMs": 250,
+                    "order": 0
+                }
+            }],
+            "sourceTrackConfigs": [{
+                "originalIndex": 1,
+                "type": "audio",
+                "config": {"trackId": "s1", "enabled": false, "delayMs": 0, "order": 0}
+            }]
+        }));
+
+        assert_eq!(job.tracks[0].delay_ms(), 250);
+        let source = &job.source_track_configs.as_ref().expect("source configs")[0];
+        assert!(!source.is_enabled());
+        assert!(job.structural_issues().is_empty());
+    }
+
+    #[test]
+    fn merge_job_validates_audio_encode_settings() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "tracks": [{
+                "inputPath": "/tmp/audio.flac",
+                "config": {"audioEncode": {"codec": "ac3", "bitrate": "640k", "channelLayout": "5.1"}}
+            }],
+            "sourceTrackConfigs": [{
+                "originalIndex": 1,
+                "config": {"audioEncode": {"codec": "dts", "bitrate": "fast", "channelLayout": "9.1"}}
+            }]
+        }));
+
+        let fields: Vec<String> = job
+            .structural_issues()
+            .into_iter()
+            .map(|issue| issue.field)
+            .collect();
+
+        assert_eq!(
+            fields,
+            vec![
+                "sourceTrackConfigs[0].config.audioEncode.codec",
+                "sourceTrackConfigs[0].config.audioEncode.bitrate",
+                "sourceTrackConfigs[0].config.audioEncode.channelLayout",
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_job_validates_subtitle_retime() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "tracks": [
+                {"inputPath": "/tmp/pal.srt", "config": {"retime": {"sourceFps": 25, "targetFps": 23.976}}},
+                {"inputPath": "/tmp/a.ass", "config": {"retime": {"factor": 1.1, "sourceFps": 25}}},
+                {"inputPath": "/tmp/b.sup", "config": {"retime": {"factor": 3.0}}}
+            ],
+            "sourceTrackConfigs": [{"originalIndex": 2, "config": {"retime": {"factor": 1.0}}}]
+        }));
+
+        let issues = job.structural_issues();
+        let fields: Vec<&str> = issues.iter().map(|issue| issue.field.as_str()).collect();
+
+        assert_eq!(
+            fields,
+            vec![
+                "sourceTrackConfigs[0].config.retime",
+                "tracks[1].config.retime",
+                "tracks[2].config.retime",
+                "tracks[2].config.retime",
+            ]
+        );
+        assert!(issues[2].message.contains("out of range"));
+        assert!(issues[3].message.contains("SRT, ASS and VTT"));
+    }
+
+    #[test]
+    fn dropped_source_indices_match_rules_but_keep_replaced_streams() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "replacements": [{"originalIndex": 3, "inputPath": "/tmp/fixed.ass"}],
+            "dropRules": [
+                {"type": "audio", "titleContains": "commentary"},
+                {"type": "subtitle", "titleContains": "signs"}
+            ]
+        }));
+        let streams = vec![
+            json!({"codec_type": "video"}),
+            json!({"codec_type": "audio", "tags": {"title": "Main"}}),
+            json!({"codec_type": "audio", "tags": {"title": "Director's Commentary"}}),
+            json!({"codec_type": "subtitle", "tags": {"title": "Signs & Songs"}}),
+            json!({"codec_type": "subtitle", "tags": {"title": "Signs"}}),
+            json!({"codec_type": "attachment", "tags": {"title": "signs font"}}),
+        ];
+
+        assert!(job.structural_issues().is_empty());
+        assert_eq!(job.dropped_source_indices(&streams), vec![2, 4]);
+        assert!(job.validate_source_indices(3).is_err());
+        assert!(job.validate_source_indices(6).is_ok());
+    }
+
+    #[test]
+    fn merge_job_reports_duplicate_replacements_and_empty_drop_rules() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "replacements": [
+                {"originalIndex": 2, "inputPath": "/tmp/a.srt"},
+                {"originalIndex": 2, "inputPath": ""}
+            ],
+            "dropRules": [{}]
+        }));
+
+        let fields: Vec<String> = job
+            .structural_issues()
+            .into_iter()
+            .map(|issue| issue.field)
+            .collect();
+
+        assert_eq!(
+            fields,
+            vec![
+                "replacements[1].inputPath",
+                "replacements[1].originalIndex",
+                "dropRules[0]",
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_job_validates_output_order_entries() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "tracks": [{"inputPath": "/tmp/eng.m4a"}],
+            "outputOrder": [{"attached": 0}, {"source": 1}, {"attached": 0}, {"attached": 4}]
+        }));
+
+        let fields: Vec<String> = job
+            .structural_issues()
+            .into_iter()
+            .map(|issue| issue.field)
+            .collect();
+
+        assert_eq!(fields, vec!["outputOrder[2]", "outputOrder[3]"]);
+    }
+
+    #[test]
+    fn merge_job_rejects_misspelled_field() {
+        let error = serde_json::from_value::<MergeJob>(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "tracks": [{"inputPath": "/tmp/sub.srt", "config": {"delayMS": 100}}]
+        }))
+        .expect_err("unknown field should be rejected");
+
+        assert!(error.to_string().contains("unknown field `delayMS`"));
+    }
+
+    #[test]
+    fn structural_issues_report_invalid_language_and_delay_overflow() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "tracks": [{"inputPath": "/tmp/sub.srt", "config": {"language": "english"}}],
+            "sourceTrackConfigs": [
+                {"originalIndex": 0, "config": {"delayMs": 100_000_000_000_i64}},
+                {"originalIndex": 0}
+            ]
+        }));
+
+        let fields: Vec<String> = job
+            .structural_issues()
+            .into_iter()
+            .map(|issue| issue.field)
+            .collect();
+        assert!(fields.contains(&"tracks[0].config.language".to_string()));
+        assert!(fields.contains(&"sourceTrackConfigs[0].config.delayMs".to_string()));
+        assert!(fields.contains(&"sourceTrackConfigs[1].originalIndex".to_string()));
+    }
+
+    #[test]
+    fn validate_source_indices_rejects_missing_stream() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "sourceTrackConfigs": [{"originalIndex": 4}]
+        }));
+
+        assert!(job.validate_source_indices(5).is_ok());
+        assert!(job.validate_source_indices(2).is_err());
+    }
+
+    #[test]
+    fn validate_merge_job_reports_missing_input_files() {
+        let dir = tempfile::tempdir().expect("failed to create tempdir");
+        let video = dir.path().join("video.mkv");
+        std::fs::write(&video, b"video").expect("failed to create video file");
+
+        let validation = validate_merge_job_value(json!({
+            "videoPath": video.to_string_lossy(),
+            "outputPath": dir.path().join("out.mkv").to_string_lossy(),
+            "tracks": [{"inputPath": dir.path().join("missing.srt").to_string_lossy()}]
+        }));
+
+        assert!(!validation.valid);
+        assert_eq!(validation.issues.len(), 1);
+        assert_eq!(validation.issues[0].field, "tracks[0].inputPath");
+    }
+
+    #[test]
+    fn validate_merge_job_reports_existing_output_under_fail_policy() {
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let video = temp.path().join("video.mkv");
+        let output = temp.path().join("out.mkv");
+        std::fs::write(&video, b"video").expect("failed to create video");
+        std::fs::write(&output, b"existing").expect("failed to create output");
+
+        let validation = validate_merge_job_value(json!({
+            "videoPath": video.to_string_lossy(),
+            "outputPath": output.to_string_lossy(),
+            "overwritePolicy": "fail"
+        }));
+
+        assert!(!validation.valid);
+        assert!(validation.issues.iter().any(|i| i.field == "outputPath"));
+    }
+
+    #[test]
+    fn merge_attachment_infers_name_and_mimetype_from_path() {
+        let attachment: MergeAttachment = serde_json::from_value(json!({
+            "inputPath": "/tmp/fonts/Arial Bold.TTF"
+        }))
+        .expect("attachment should deserialize");
+
+        assert_eq!(attachment.file_name(), "Arial Bold.TTF");
+        assert_eq!(
+            attachment.mime_type().as_deref(),
+            Some("application/x-truetype-font")
+        );
+    }
+
+    #[test]
+    fn structural_issues_reject_attachments_for_non_matroska_output() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mp4",
+            "attachments": [
+                {"inputPath": "/tmp/a.ttf"},
+                {"inputPath": "/tmp/other/a.ttf"},
+                {"inputPath": "/tmp/notes.bin"}
+            ]
+        }));
+
+        let fields: Vec<String> = job
+            .structural_issues()
+            .into_iter()
+            .map(|issue| issue.field)
+            .collect();
+
+        assert!(fields.contains(&"attachments".to_string()));
+        assert!(fields.contains(&"attachments[1].fileName".to_string()));
+        assert!(fields.contains(&"attachments[2].mimeType".to_string()));
+    }
+
+    #[test]
+    fn kept_source_attachments_honours_attachment_and_track_configs() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "sourceTrackConfigs": [
+                {"originalIndex": 0},
+                {"originalIndex": 5, "config": {"enabled": false}}
+            ],
+            "sourceAttachments": [{"originalIndex": 3, "enabled": false}]
+        }));
+
+        assert_eq!(job.kept_source_attachments(&[3, 4, 5]), vec![4]);
+
+        let mp4_job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mp4"
+        }));
+        assert!(mp4_job.kept_source_attachments(&[3, 4]).is_empty());
+    }
+
+    #[test]
+    fn merge_job_accepts_chapter_import_variants() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "chapters": {
+                "source": "list",
+                "chapters": [{"startMs": 5000, "endMs": 1000, "title": "Broken"}]
+            },
+            "sourceChapters": "shift",
+            "chapterShiftMs": 100000000
+        }));
+
+        let fields: Vec<String> = job
+            .structural_issues()
+            .into_iter()
+            .map(|issue| issue.field)
+            .collect();
+        assert!(fields.contains(&"chapters.chapters[0].endMs".to_string()));
+        assert!(fields.contains(&"chapterShiftMs".to_string()));
+
+        let xml_job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "chapters": {"source": "matroskaXml", "path": "/tmp/missing-chapters.xml"}
+        }));
+        assert!(
+            xml_job
+                .issues()
+                .iter()
+                .any(|issue| issue.field == "chapters.path")
+        );
+    }
+
+    #[test]
+    fn structural_issues_accept_probed_language_tags() {
+        let job = parse_job(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "tracks": [
+                {"inputPath": "/tmp/sub1.srt", "config": {"language": "en"}},
+                {"inputPath": "/tmp/sub2.srt", "config": {"language": "pt-BR"}}
+            ],
+            "sourceTrackConfigs": [{"originalIndex": 1, "config": {"language": "ENG"}}],
+            "dropRules": [{"language": "fr"}]
+        }));
+
+        assert!(job.structural_issues().is_empty());
+        assert_eq!(
+            job.tracks[0]
+                .config
+                .as_ref()
+                .and_then(|c| c.language_code()),
+            Some("eng".to_string())
+        );
+        assert_eq!(
+            job.tracks[1]
+                .config
+                .as_ref()
+                .and_then(|c| c.language_code()),
+            Some("por".to_string())
+        );
+        let french = json!({"codec_type": "audio", "tags": {"language": "fre"}});
+        assert!(job.drop_rules[0].matches(&french));
+    }
+}
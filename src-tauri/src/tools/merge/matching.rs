@@ -0,0 +1,183 @@
+use crate::shared::episode::{EpisodeInfo, parse_episode};
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Below this gap between the two best videos, a file is flagged for review
+const AMBIGUITY_MARGIN: f64 = 0.1;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EpisodeMatchCandidate {
+    pub(crate) video_path: String,
+    pub(crate) confidence: f64,
+}
+
+/// Best video for one file, with every other plausible video ranked after it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EpisodeMatch {
+    pub(crate) file_path: String,
+    pub(crate) episode: Option<EpisodeInfo>,
+    pub(crate) video_path: Option<String>,
+    pub(crate) confidence: f64,
+    pub(crate) ambiguous: bool,
+    pub(crate) candidates: Vec<EpisodeMatchCandidate>,
+}
+
+fn title_similarity(a: &str, b: &str) -> f64 {
+    let a: HashSet<&str> = a.split_whitespace().collect();
+    let b: HashSet<&str> = b.split_whitespace().collect();
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 0.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
+
+/// Score (0-1) for pairing a file with a video; 0 when their episodes cannot match
+fn pair_confidence(file: &EpisodeInfo, video: &EpisodeInfo) -> f64 {
+    if !file.episodes.iter().any(|e| video.episodes.contains(e)) {
+        return 0.0;
+    }
+
+    let mut score = if file.episodes == video.episodes {
+        0.6
+    } else {
+        0.4
+    };
+    score += match (file.season, video.season) {
+        (Some(a), Some(b)) if a != b => return 0.0,
+        (Some(_), Some(_)) => 0.25,
+        // Both use absolute numbering
+        (None, None) => 0.15,
+        _ => 0.05,
+    };
+    score += 0.15 * title_similarity(&file.title, &video.title);
+
+    (score.min(1.0) * 100.0).round() / 100.0
+}
+
+/// Pair every file with the video of the same episode
+pub(crate) fn match_episodes(video_paths: &[String], file_paths: &[String]) -> Vec<EpisodeMatch> {
+    let videos: Vec<(&String, EpisodeInfo)> = video_paths
+        .iter()
+        .filter_map(|path| Some((path, parse_episode(path)?)))
+        .collect();
+
+    file_paths
+        .iter()
+        .map(|file_path| {
+            let episode = parse_episode(file_path);
+            let mut candidates: Vec<EpisodeMatchCandidate> = episode
+                .as_ref()
+                .map(|file| {
+                    videos
+                        .iter()
+                        .map(|(video_path, video)| EpisodeMatchCandidate {
+                            video_path: video_path.to_string(),
+                            confidence: pair_confidence(file, video),
+                        })
+                        .filter(|candidate| candidate.confidence > 0.0)
+                        .collect()
+                })
+                .unwrap_or_default();
+            candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+            let confidence = candidates.first().map_or(0.0, |c| c.confidence);
+            let ambiguous = candidates
+                .get(1)
+                .is_some_and(|second| confidence - second.confidence < AMBIGUITY_MARGIN);
+
+            EpisodeMatch {
+                file_path: file_path.clone(),
+                episode,
+                video_path: candidates.first().map(|c| c.video_path.clone()),
+                confidence,
+                ambiguous,
+                candidates,
+            }
+        })
+        .collect()
+}
+
+/// Match tracks or other files to videos by the episode numbers in their names
+#[tauri::command]
+pub(crate) async fn match_files_to_videos(
+    video_paths: Vec<String>,
+    file_paths: Vec<String>,
+) -> Result<Vec<EpisodeMatch>, String> {
+    Ok(match_episodes(&video_paths, &file_paths))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::match_episodes;
+
+    fn paths(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn match_episodes_pairs_by_season_and_episode() {
+        let videos = paths(&[
+            "/tv/Show.S01E01.1080p.mkv",
+            "/tv/Show.S01E02.1080p.mkv",
+            "/tv/Show.S02E01.1080p.mkv",
+        ]);
+        let files = paths(&[
+            "/subs/Show - 1x02.srt",
+            "/subs/Show.S02E01.fre.ass",
+            "/subs/notes.txt",
+        ]);
+
+        let matches = match_episodes(&videos, &files);
+
+        assert_eq!(
+            matches[0].video_path.as_deref(),
+            Some("/tv/Show.S01E02.1080p.mkv")
+        );
+        assert_eq!(matches[0].confidence, 1.0);
+        assert!(!matches[0].ambiguous);
+        assert_eq!(
+            matches[1].video_path.as_deref(),
+            Some("/tv/Show.S02E01.1080p.mkv")
+        );
+        assert_eq!(matches[1].candidates.len(), 1);
+        assert_eq!(matches[2].episode, None);
+        assert_eq!(matches[2].video_path, None);
+    }
+
+    #[test]
+    fn match_episodes_flags_files_without_season_as_ambiguous() {
+        let videos = paths(&["/tv/Show.S01E03.mkv", "/tv/Show.S02E03.mkv"]);
+        let files = paths(&["/subs/Show - 03.ass"]);
+
+        let matches = match_episodes(&videos, &files);
+
+        assert!(matches[0].ambiguous);
+        assert_eq!(matches[0].candidates.len(), 2);
+    }
+
+    #[test]
+    fn match_episodes_handles_absolute_and_multi_episode_files() {
+        let videos = paths(&[
+            "/anime/[Group] Frieren - 12 [1080p].mkv",
+            "/anime/[Group] Frieren - 13-14 [1080p].mkv",
+        ]);
+        let files = paths(&["/subs/Frieren - 12.ass", "/subs/Frieren 14.ass"]);
+
+        let matches = match_episodes(&videos, &files);
+
+        assert_eq!(
+            matches[0].video_path.as_deref(),
+            Some("/anime/[Group] Frieren - 12 [1080p].mkv")
+        );
+        assert_eq!(matches[0].confidence, 0.9);
+        assert_eq!(
+            matches[1].video_path.as_deref(),
+            Some("/anime/[Group] Frieren - 13-14 [1080p].mkv")
+        );
+        assert!(matches[1].confidence < matches[0].confidence);
+        assert!(!matches[1].ambiguous);
+    }
+}
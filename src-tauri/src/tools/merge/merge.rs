@@ -1,11 +1,25 @@
+use crate::shared::clip::{ACCURATE_VIDEO_ENCODE_ARGS, ClipCut};
 use crate::shared::ffmpeg_progress::FfmpegProgressTracker;
+use crate::shared::output::StagedOutput;
+use crate::shared::process::terminate_process;
 use crate::shared::sleep_inhibit::SleepInhibitGuard;
 use crate::shared::store::{resolve_ffmpeg_path, resolve_ffprobe_path};
-use crate::shared::validation::{validate_media_path, validate_output_path};
-use crate::tools::ffprobe::FFPROBE_TIMEOUT;
-use serde_json::Value;
+use crate::tools::ffprobe::cache::{cached_probe_json, invalidate_probe_cache};
+use crate::tools::merge::cancel::cancel_video_merge;
+use crate::tools::merge::chapters::{ChapterMapping, ChaptersMetadataFile};
+use crate::tools::merge::compat::{StreamConversion, ensure_compatible};
+use crate::tools::merge::job::{
+    MergeAttachedTrack, MergeJob, MergeReplacement, MergeSourceTrack, MergeTrackConfig,
+    MergeTrackRef,
+};
+use crate::tools::merge::plan::{MergePlan, plan_merge_with_bins};
+use crate::tools::merge::retime::RetimedSubtitleFiles;
+use crate::tools::merge::verify::{MergeVerificationReport, verify_merge_output};
+use serde_json::{Value, json};
 use std::collections::HashMap;
 use std::process::Stdio;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 use tauri::Emitter;
 use tokio::process::Command;
 use tokio::time::{Duration, timeout};
@@ -15,43 +29,167 @@ const FFMPEG_MERGE_TIMEOUT: Duration = Duration::from_secs(600);
 
 #[cfg_attr(not(test), allow(dead_code))]
 fn enabled_source_indices(
-    source_track_configs: Option<&[Value]>,
+    source_track_configs: Option<&[MergeSourceTrack]>,
     original_stream_count: usize,
 ) -> Vec<usize> {
     if let Some(configs) = source_track_configs {
         configs
             .iter()
-            .filter_map(|c| {
-                let enabled = c
-                    .get("config")
-                    .and_then(|cfg| cfg.get("enabled"))
-                    .and_then(|v| v.as_bool())
-                    .unwrap_or(true);
-                if enabled {
-                    c.get("originalIndex")
-                        .and_then(|v| v.as_u64())
-                        .map(|i| i as usize)
-                } else {
-                    None
-                }
-            })
+            .filter(|c| c.is_enabled())
+            .map(|c| c.original_index)
             .collect()
     } else {
         (0..original_stream_count).collect()
     }
 }
 
-struct SourceTrackSelection<'a> {
-    input_idx: usize,
+pub(super) struct SourceTrackSelection<'a> {
+    pub(super) input_idx: usize,
+    pub(super) original_index: usize,
+    pub(super) config: Option<&'a MergeTrackConfig>,
+    /// External file mapped in place of the source stream, with its index in the job
+    pub(super) replacement: Option<(usize, &'a MergeReplacement)>,
+}
+
+impl SourceTrackSelection<'_> {
+    /// Stream index within the input this selection is mapped from
+    pub(super) fn stream_index(&self) -> usize {
+        self.replacement
+            .map_or(self.original_index, |(_, replacement)| {
+                replacement.track_index
+            })
+    }
+}
+
+/// Indices of the attachment streams in an ffprobe stream list
+pub(super) fn attachment_stream_indices(streams: &[Value]) -> Vec<usize> {
+    streams
+        .iter()
+        .enumerate()
+        .filter(|(_, stream)| {
+            stream.get("codec_type").and_then(|v| v.as_str()) == Some("attachment")
+        })
+        .map(|(index, _)| index)
+        .collect()
+}
+
+/// Facts about the inputs that `build_merge_args` cannot read from the job
+#[derive(Debug, Clone, Default)]
+pub(super) struct MergeInputLayout {
+    pub(super) stream_count: usize,
+    pub(super) attachment_indices: Vec<usize>,
+    /// Source tracks removed by the job's drop rules
+    pub(super) dropped_indices: Vec<usize>,
+    pub(super) chapters: ChapterMapping,
+    /// Output streams re-encoded instead of copied
+    pub(super) conversions: Vec<StreamConversion>,
+    /// Resolved cut points when only part of the source is kept
+    pub(super) clip: Option<ClipCut>,
+}
+
+impl MergeInputLayout {
+    pub(super) fn from_streams(streams: &[Value]) -> Self {
+        Self {
+            stream_count: streams.len(),
+            attachment_indices: attachment_stream_indices(streams),
+            dropped_indices: Vec::new(),
+            chapters: ChapterMapping::Source,
+            conversions: Vec::new(),
+            clip: None,
+        }
+    }
+}
+
+/// Where an output track comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum OutputTrackSlot {
+    /// Index into the source track selections
+    Source(usize),
+    /// Index into the job's attached tracks
+    Attached(usize),
+}
+
+/// Output track order: `outputOrder` entries first, then every other track in default order
+pub(super) fn output_track_slots(
+    job: &MergeJob,
+    selections: &[SourceTrackSelection],
+) -> Vec<OutputTrackSlot> {
+    let default_slots = (0..selections.len())
+        .map(OutputTrackSlot::Source)
+        .chain((0..job.tracks.len()).map(OutputTrackSlot::Attached));
+    let Some(order) = job.output_order.as_deref() else {
+        return default_slots.collect();
+    };
+
+    let mut slots = Vec::with_capacity(selections.len() + job.tracks.len());
+    let ordered = order.iter().filter_map(|entry| match *entry {
+        MergeTrackRef::Source(original_index) => selections
+            .iter()
+            .position(|s| s.original_index == original_index)
+            .map(OutputTrackSlot::Source),
+        MergeTrackRef::Attached(track) => {
+            (track < job.tracks.len()).then_some(OutputTrackSlot::Attached(track))
+        }
+    });
+    for slot in ordered.chain(default_slots) {
+        if !slots.contains(&slot) {
+            slots.push(slot);
+        }
+    }
+    slots
+}
+
+/// Source attachments and dropped tracks, which are never mapped as tracks
+pub(super) fn skipped_source_indices(job: &MergeJob, streams: &[Value]) -> Vec<usize> {
+    let mut skipped = attachment_stream_indices(streams);
+    skipped.extend(job.dropped_source_indices(streams));
+    skipped
+}
+
+/// Push `-i` for an input shifted by `delay_ms`, seeking it to the clip start when clipping
+fn push_timed_input(args: &mut Vec<String>, path: &str, delay_ms: i64, clip: Option<&ClipCut>) {
+    let (seek_ms, offset_ms) = clip.map_or((0, delay_ms), |clip| clip.input_timing(delay_ms));
+    if offset_ms != 0 {
+        args.push("-itsoffset".to_string());
+        args.push(format!("{:.3}", offset_ms as f64 / 1000.0));
+    }
+    if seek_ms != 0 {
+        args.push("-ss".to_string());
+        args.push(format!("{:.3}", seek_ms as f64 / 1000.0));
+    }
+    args.push("-i".to_string());
+    args.push(path.to_string());
+}
+
+/// Replacement inputs are added in place of the stream they replace
+fn push_replacement_input<'a>(
+    replacements: &'a [MergeReplacement],
     original_index: usize,
-    config: Option<&'a Value>,
+    args: &mut Vec<String>,
+    next_input_idx: &mut usize,
+    clip: Option<&ClipCut>,
+) -> Option<(usize, (usize, &'a MergeReplacement))> {
+    let (position, replacement) = replacements
+        .iter()
+        .enumerate()
+        .find(|(_, r)| r.original_index == original_index)?;
+
+    push_timed_input(args, &replacement.input_path, replacement.delay_ms(), clip);
+
+    let input_idx = *next_input_idx;
+    *next_input_idx += 1;
+    Some((input_idx, (position, replacement)))
 }
 
-fn build_source_track_selections<'a>(
-    source_track_configs: Option<&'a [Value]>,
+/// Source attachments are skipped here and mapped after all tracks
+pub(super) fn build_source_track_selections<'a>(
+    source_track_configs: Option<&'a [MergeSourceTrack]>,
     original_stream_count: usize,
+    skipped_source_indices: &[usize],
+    replacements: &'a [MergeReplacement],
     args: &mut Vec<String>,
     video_path: &str,
+    clip: Option<&ClipCut>,
 ) -> (Vec<SourceTrackSelection<'a>>, usize) {
     let mut selections = Vec::new();
     let mut delayed_input_indices: HashMap<i64, usize> = HashMap::new();
@@ -59,39 +197,36 @@ fn build_source_track_selections<'a>(
 
     if let Some(configs) = source_track_configs {
         for source_config in configs {
-            let enabled = source_config
-                .get("config")
-                .and_then(|cfg| cfg.get("enabled"))
-                .and_then(|v| v.as_bool())
-                .unwrap_or(true);
-            if !enabled {
+            if !source_config.is_enabled()
+                || skipped_source_indices.contains(&source_config.original_index)
+            {
                 continue;
             }
 
-            let Some(original_index) = source_config
-                .get("originalIndex")
-                .and_then(|v| v.as_u64())
-                .map(|i| i as usize)
-            else {
+            if let Some((input_idx, replacement)) = push_replacement_input(
+                replacements,
+                source_config.original_index,
+                args,
+                &mut next_input_idx,
+                clip,
+            ) {
+                selections.push(SourceTrackSelection {
+                    input_idx,
+                    original_index: source_config.original_index,
+                    config: replacement.1.config.as_ref(),
+                    replacement: Some(replacement),
+                });
                 continue;
-            };
+            }
 
-            let delay_ms = source_config
-                .get("config")
-                .and_then(|cfg| cfg.get("delayMs"))
-                .and_then(|v| v.as_i64())
-                .unwrap_or(0);
+            let delay_ms = source_config.delay_ms();
 
             let input_idx = if delay_ms == 0 {
                 0
             } else if let Some(existing_input_idx) = delayed_input_indices.get(&delay_ms) {
                 *existing_input_idx
             } else {
-                let delay_sec = delay_ms as f64 / 1000.0;
-                args.push("-itsoffset".to_string());
-                args.push(format!("{:.3}", delay_sec));
-                args.push("-i".to_string());
-                args.push(video_path.to_string());
+                push_timed_input(args, video_path, delay_ms, clip);
 
                 let new_input_idx = next_input_idx;
                 delayed_input_indices.insert(delay_ms, new_input_idx);
@@ -101,16 +236,28 @@ fn build_source_track_selections<'a>(
 
             selections.push(SourceTrackSelection {
                 input_idx,
-                original_index,
-                config: source_config.get("config"),
+                original_index: source_config.original_index,
+                config: source_config.config.as_ref(),
+                replacement: None,
             });
         }
     } else {
         for original_index in 0..original_stream_count {
+            if skipped_source_indices.contains(&original_index) {
+                continue;
+            }
+            let replacement = push_replacement_input(
+                replacements,
+                original_index,
+                args,
+                &mut next_input_idx,
+                clip,
+            );
             selections.push(SourceTrackSelection {
-                input_idx: 0,
+                input_idx: replacement.map_or(0, |(input_idx, _)| input_idx),
                 original_index,
-                config: None,
+                config: replacement.and_then(|(_, (_, r))| r.config.as_ref()),
+                replacement: replacement.map(|(_, replacement)| replacement),
             });
         }
     }
@@ -118,151 +265,208 @@ fn build_source_track_selections<'a>(
     (selections, next_input_idx)
 }
 
-fn build_merge_args(
-    video_path: &str,
-    tracks: &[Value],
-    source_track_configs: Option<&[Value]>,
-    original_stream_count: usize,
-    output_path: &str,
-) -> Vec<String> {
-    let mut args = vec!["-y".to_string(), "-i".to_string(), video_path.to_string()];
+fn push_disposition_args(
+    args: &mut Vec<String>,
+    output_stream_idx: usize,
+    config: &MergeTrackConfig,
+) {
+    let mut disposition = Vec::new();
+    if config.default {
+        disposition.push("default");
+    }
+    if config.forced {
+        disposition.push("forced");
+    }
+
+    args.push(format!("-disposition:{}", output_stream_idx));
+    if disposition.is_empty() {
+        args.push("0".to_string());
+    } else {
+        args.push(disposition.join("+"));
+    }
+}
+
+pub(super) fn build_merge_args(job: &MergeJob, layout: &MergeInputLayout) -> Vec<String> {
+    let video_path = job.video_path.as_str();
+    let clip = layout.clip.as_ref();
+    let mut args = vec!["-y".to_string()];
+    push_timed_input(&mut args, video_path, 0, clip);
+    let skipped_indices = [
+        layout.attachment_indices.as_slice(),
+        layout.dropped_indices.as_slice(),
+    ]
+    .concat();
     let (source_track_selections, mut next_input_idx) = build_source_track_selections(
-        source_track_configs,
-        original_stream_count,
+        job.source_track_configs.as_deref(),
+        layout.stream_count,
+        &skipped_indices,
+        &job.replacements,
         &mut args,
         video_path,
+        clip,
     );
-    let mut attached_track_inputs: Vec<(usize, &Value)> = Vec::new();
-
-    for track in tracks {
-        if let Some(input_path) = track.get("inputPath").and_then(|v| v.as_str()) {
-            let delay_ms = track
-                .get("config")
-                .and_then(|c| c.get("delayMs"))
-                .and_then(|v| v.as_i64())
-                .unwrap_or(0);
-
-            if delay_ms != 0 {
-                let delay_sec = delay_ms as f64 / 1000.0;
-                args.push("-itsoffset".to_string());
-                args.push(format!("{:.3}", delay_sec));
-            }
+    let mut attached_track_inputs: Vec<(usize, &MergeAttachedTrack)> = Vec::new();
 
-            args.push("-i".to_string());
-            args.push(input_path.to_string());
-            attached_track_inputs.push((next_input_idx, track));
-            next_input_idx += 1;
-        }
+    for track in &job.tracks {
+        push_timed_input(&mut args, &track.input_path, track.delay_ms(), clip);
+        attached_track_inputs.push((next_input_idx, track));
+        next_input_idx += 1;
+    }
+
+    if let ChapterMapping::Metadata(path) = &layout.chapters {
+        args.push("-f".to_string());
+        args.push("ffmetadata".to_string());
+        args.push("-i".to_string());
+        args.push(path.clone());
     }
+    let chapters_input_idx = next_input_idx;
 
-    for source_track in &source_track_selections {
+    let slots = output_track_slots(job, &source_track_selections);
+    let mut source_output_indices = vec![0; source_track_selections.len()];
+    let mut attached_output_indices = vec![0; attached_track_inputs.len()];
+    for (output_stream_idx, slot) in slots.iter().enumerate() {
         args.push("-map".to_string());
-        args.push(format!(
-            "{}:{}",
-            source_track.input_idx, source_track.original_index
-        ));
+        match *slot {
+            OutputTrackSlot::Source(i) => {
+                let source_track = &source_track_selections[i];
+                args.push(format!(
+                    "{}:{}",
+                    source_track.input_idx,
+                    source_track.stream_index()
+                ));
+                source_output_indices[i] = output_stream_idx;
+            }
+            OutputTrackSlot::Attached(i) => {
+                let (input_idx, track) = attached_track_inputs[i];
+                args.push(format!("{}:{}", input_idx, track.track_index));
+                attached_output_indices[i] = output_stream_idx;
+            }
+        }
     }
 
-    for (input_idx, _) in &attached_track_inputs {
+    let kept_source_attachments = job.kept_source_attachments(&layout.attachment_indices);
+    for original_index in &kept_source_attachments {
         args.push("-map".to_string());
-        args.push(format!("{}:0", input_idx));
+        args.push(format!("0:{}", original_index));
     }
 
-    args.push("-c:v".to_string());
-    args.push("copy".to_string());
+    // -attach streams are created after every mapped stream
+    for attachment in &job.attachments {
+        args.push("-attach".to_string());
+        args.push(attachment.input_path.clone());
+    }
+
+    if clip.is_some_and(ClipCut::reencodes_video) {
+        args.extend(ACCURATE_VIDEO_ENCODE_ARGS.iter().map(|arg| arg.to_string()));
+    } else {
+        args.push("-c:v".to_string());
+        args.push("copy".to_string());
+    }
     args.push("-c:a".to_string());
     args.push("copy".to_string());
     args.push("-c:s".to_string());
     args.push("copy".to_string());
+    if !kept_source_attachments.is_empty() || !job.attachments.is_empty() {
+        args.push("-c:t".to_string());
+        args.push("copy".to_string());
+    }
+    // Per-stream codecs come last so they override the blanket copy
+    for conversion in &layout.conversions {
+        args.push(format!("-c:{}", conversion.specifier));
+        args.push(conversion.encoder.clone());
+        if let Some(bitrate) = conversion.bitrate.as_deref() {
+            args.push(format!("-b:{}", conversion.specifier));
+            args.push(bitrate.to_string());
+        }
+        if let Some(channel_layout) = conversion.channel_layout.as_deref() {
+            args.push(format!("-ch_layout:{}", conversion.specifier));
+            args.push(channel_layout.to_string());
+        }
+    }
+
+    match &layout.chapters {
+        ChapterMapping::Source => {}
+        ChapterMapping::Drop => {
+            args.push("-map_chapters".to_string());
+            args.push("-1".to_string());
+        }
+        ChapterMapping::Metadata(_) => {
+            args.push("-map_chapters".to_string());
+            args.push(chapters_input_idx.to_string());
+        }
+    }
+
+    for (source_track, &output_stream_idx) in
+        source_track_selections.iter().zip(&source_output_indices)
+    {
+        // An unconfigured replacement keeps the language and title of the stream it replaces
+        if source_track.replacement.is_some() && source_track.config.is_none() {
+            args.push(format!("-map_metadata:s:{}", output_stream_idx));
+            args.push(format!("0:s:{}", source_track.original_index));
+        }
 
-    for (output_stream_idx, source_track) in source_track_selections.iter().enumerate() {
         if let Some(cfg) = source_track.config {
-            if let Some(lang) = cfg.get("language").and_then(|v| v.as_str()) {
-                if !lang.is_empty() {
-                    args.push(format!("-metadata:s:{}", output_stream_idx));
-                    args.push(format!("language={}", lang));
-                }
+            if let Some(lang) = cfg.language_code() {
+                args.push(format!("-metadata:s:{}", output_stream_idx));
+                args.push(format!("language={}", lang));
             }
 
-            if let Some(title) = cfg.get("title").and_then(|v| v.as_str()) {
+            if let Some(title) = cfg.title.as_deref() {
                 args.push(format!("-metadata:s:{}", output_stream_idx));
                 args.push(format!("title={}", title));
             }
 
-            let is_default = cfg
-                .get("default")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
-            let is_forced = cfg.get("forced").and_then(|v| v.as_bool()).unwrap_or(false);
-
-            if is_default || is_forced {
-                let mut disposition = Vec::new();
-                if is_default {
-                    disposition.push("default");
-                }
-                if is_forced {
-                    disposition.push("forced");
-                }
-                args.push(format!("-disposition:{}", output_stream_idx));
-                args.push(disposition.join("+"));
-            } else {
-                args.push(format!("-disposition:{}", output_stream_idx));
-                args.push("0".to_string());
-            }
+            push_disposition_args(&mut args, output_stream_idx, cfg);
         }
     }
 
-    let attached_start_idx = source_track_selections.len();
-    for (i, (_, track)) in attached_track_inputs.iter().enumerate() {
-        let output_stream_idx = attached_start_idx + i;
-
-        if let Some(config) = track.get("config") {
-            if let Some(lang) = config.get("language").and_then(|v| v.as_str()) {
-                if !lang.is_empty() && lang != "und" {
+    for ((_, track), &output_stream_idx) in
+        attached_track_inputs.iter().zip(&attached_output_indices)
+    {
+        if let Some(config) = track.config.as_ref() {
+            if let Some(lang) = config.language_code() {
+                if lang != "und" {
                     args.push(format!("-metadata:s:{}", output_stream_idx));
                     args.push(format!("language={}", lang));
                 }
             }
 
-            if let Some(title) = config.get("title").and_then(|v| v.as_str()) {
+            if let Some(title) = config.title.as_deref() {
                 if !title.is_empty() {
                     args.push(format!("-metadata:s:{}", output_stream_idx));
                     args.push(format!("title={}", title));
                 }
             }
 
-            let is_default = config
-                .get("default")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
-            let is_forced = config
-                .get("forced")
-                .and_then(|v| v.as_bool())
-                .unwrap_or(false);
-
-            if is_default || is_forced {
-                let mut disposition = Vec::new();
-                if is_default {
-                    disposition.push("default");
-                }
-                if is_forced {
-                    disposition.push("forced");
-                }
-                args.push(format!("-disposition:{}", output_stream_idx));
-                args.push(disposition.join("+"));
-            } else {
-                args.push(format!("-disposition:{}", output_stream_idx));
-                args.push("0".to_string());
-            }
+            push_disposition_args(&mut args, output_stream_idx, config);
+        }
+    }
+
+    for (i, attachment) in job.attachments.iter().enumerate() {
+        let attachment_idx = kept_source_attachments.len() + i;
+        if let Some(mime_type) = attachment.mime_type() {
+            args.push(format!("-metadata:s:t:{}", attachment_idx));
+            args.push(format!("mimetype={}", mime_type));
         }
+        args.push(format!("-metadata:s:t:{}", attachment_idx));
+        args.push(format!("filename={}", attachment.file_name()));
     }
 
+    if let Some(clip) = clip {
+        args.extend(clip.duration_args());
+    }
     args.push("-progress".to_string());
     args.push("pipe:1".to_string());
-    args.push(output_path.to_string());
+    args.push(job.output_path.clone());
     args
 }
 
+/// Error of a merge stopped by `cancel_merge_file` or `cancel_merge`
+pub(super) const MERGE_CANCELLED: &str = "Merge cancelled";
+
+/// Extra listener for a job's progress and write speed, used by batch merges
+pub(super) type MergeProgressHook = Arc<dyn Fn(i32, Option<f64>) + Send + Sync>;
+
 fn emit_merge_progress(
     app: &tauri::AppHandle,
     video_path: &str,
@@ -272,7 +476,7 @@ fn emit_merge_progress(
 ) {
     let _ = app.emit(
         "merge-progress",
-        serde_json::json!({
+        json!({
             "videoPath": video_path,
             "outputPath": output_path,
             "progress": progress,
@@ -281,72 +485,32 @@ fn emit_merge_progress(
     );
 }
 
-#[cfg_attr(not(test), allow(dead_code))]
-pub(super) async fn merge_tracks_with_bins(
-    ffprobe_path: &str,
-    ffmpeg_path: &str,
-    video_path: &str,
-    tracks: &[Value],
-    source_track_configs: Option<&[Value]>,
-    output_path: &str,
-) -> Result<(), String> {
-    validate_media_path(video_path)?;
-    validate_output_path(output_path)?;
-
-    for track in tracks {
-        if let Some(input_path) = track.get("inputPath").and_then(|v| v.as_str()) {
-            validate_media_path(input_path)?;
-        }
-    }
-
-    let probe_future = async move {
-        Command::new(ffprobe_path)
-            .args([
-                "-v",
-                "quiet",
-                "-print_format",
-                "json",
-                "-show_streams",
-                video_path,
-            ])
-            .output()
-            .await
-    };
-
-    let probe_output = timeout(FFPROBE_TIMEOUT, probe_future)
-        .await
-        .map_err(|_| {
-            format!(
-                "FFprobe timeout after {} seconds",
-                FFPROBE_TIMEOUT.as_secs()
-            )
-        })?
-        .map_err(|e| format!("Failed to probe video: {}", e))?;
-
-    if !probe_output.status.success() {
-        return Err("Failed to probe video file".to_string());
-    }
+/// Probe a merge input and return the ffprobe JSON (streams, format and chapters)
+/// Reuses the probe the UI already ran when the file hasn't changed since
+pub(super) async fn probe_media(ffprobe_path: &str, path: &str) -> Result<Value, String> {
+    cached_probe_json(ffprobe_path, path).await
+}
 
-    let probe_json: Value = serde_json::from_slice(&probe_output.stdout)
-        .map_err(|e| format!("Failed to parse probe output: {}", e))?;
-    let streams = probe_json
+pub(super) fn probe_streams(probe: &Value) -> &[Value] {
+    probe
         .get("streams")
         .and_then(|s| s.as_array())
-        .cloned()
-        .unwrap_or_default();
-    let original_stream_count = streams.len();
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+}
 
-    let args = build_merge_args(
-        video_path,
-        tracks,
-        source_track_configs,
-        original_stream_count,
-        output_path,
-    );
+pub(super) fn probe_duration_secs(probe: &Value) -> Option<f64> {
+    probe
+        .get("format")
+        .and_then(|f| f.get("duration"))
+        .and_then(|v| v.as_str())
+        .and_then(|v| v.parse::<f64>().ok())
+}
 
+async fn run_ffmpeg_merge(ffmpeg_path: &str, args: &[String]) -> Result<(), String> {
     let wait_future = async move {
         Command::new(ffmpeg_path)
-            .args(&args)
+            .args(args)
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
             .output()
@@ -371,89 +535,123 @@ pub(super) async fn merge_tracks_with_bins(
     Ok(())
 }
 
+/// Stage the job's output and return a copy of the job that writes to the temp file
+fn stage_merge_output(job: &MergeJob) -> Result<(StagedOutput, MergeJob), String> {
+    let staged = StagedOutput::prepare(&job.output_path, job.overwrite_policy)?;
+    let staged_job = MergeJob {
+        output_path: staged.temp_path().to_string(),
+        ..job.clone()
+    };
+    Ok((staged, staged_job))
+}
+
+/// Verify the temp output, then move it into place or discard it
+async fn finish_merge_output(
+    ffprobe_path: &str,
+    plan: &MergePlan,
+    staged: &StagedOutput,
+) -> Result<MergeVerificationReport, String> {
+    let mut report = match verify_merge_output(ffprobe_path, plan, staged.temp_path()).await {
+        Ok(report) => report,
+        Err(e) => {
+            staged.discard();
+            return Err(e);
+        }
+    };
+    if report.passed {
+        report.output_path = staged.commit()?;
+        invalidate_probe_cache(&report.output_path);
+    } else {
+        staged.discard();
+        report.output_path = staged.final_path().to_string();
+    }
+    Ok(report)
+}
+
+#[cfg_attr(not(test), allow(dead_code))]
+pub(super) async fn merge_tracks_with_bins(
+    ffprobe_path: &str,
+    ffmpeg_path: &str,
+    job: &MergeJob,
+) -> Result<MergeVerificationReport, String> {
+    job.validate()?;
+    let (staged, staged_job) = stage_merge_output(job)?;
+    let plan = plan_merge_with_bins(ffprobe_path, ffmpeg_path, &staged_job).await?;
+    ensure_compatible(&plan.compatibility_issues)?;
+    let _chapters_file = ChaptersMetadataFile::write(
+        plan.chapters_metadata_path.as_deref(),
+        plan.chapters.as_deref(),
+    )?;
+    let _retimed_files = RetimedSubtitleFiles::write(&plan.retimed_subtitles)?;
+    if let Err(e) = run_ffmpeg_merge(ffmpeg_path, &plan.args).await {
+        staged.discard();
+        return Err(e);
+    }
+
+    finish_merge_output(ffprobe_path, &plan, &staged)
+        .await?
+        .into_result()
+}
+
 /// Merge tracks into a video file
 /// Uses async tokio::process::Command with timeout
 #[tauri::command]
 pub(crate) async fn merge_tracks(
     app: tauri::AppHandle,
-    video_path: String,
-    tracks: Vec<Value>,
-    source_track_configs: Option<Vec<Value>>,
-    output_path: String,
-    duration_us: Option<u64>,
-) -> Result<(), String> {
-    // Validate input paths
-    validate_media_path(&video_path)?;
-    validate_output_path(&output_path)?;
+    job: MergeJob,
+) -> Result<MergeVerificationReport, String> {
+    run_merge_job(&app, job, None, None).await
+}
 
-    let _sleep_guard = SleepInhibitGuard::try_acquire("FFmpeg merge").ok();
+/// Plan, run and verify a merge, emitting progress events keyed by the job's video path
+pub(super) async fn run_merge_job(
+    app: &tauri::AppHandle,
+    job: MergeJob,
+    progress_hook: Option<MergeProgressHook>,
+    cancel_flag: Option<&AtomicBool>,
+) -> Result<MergeVerificationReport, String> {
+    let video_path = job.video_path.clone();
 
-    // Validate all track input paths
-    for track in &tracks {
-        if let Some(input_path) = track.get("inputPath").and_then(|v| v.as_str()) {
-            validate_media_path(input_path)?;
-        }
-    }
+    // Validate paths, languages and delays before spawning anything
+    job.validate()?;
 
-    // First, probe the video to count streams and get their types
-    let ffprobe_path = resolve_ffprobe_path(&app)?;
-    let video_path_for_probe = video_path.clone();
-    let probe_future = async move {
-        Command::new(ffprobe_path)
-            .args([
-                "-v",
-                "quiet",
-                "-print_format",
-                "json",
-                "-show_streams",
-                &video_path_for_probe,
-            ])
-            .output()
-            .await
-    };
+    // ffmpeg writes to a hidden sibling that only replaces the output once verified
+    let (staged, staged_job) = stage_merge_output(&job)?;
+    let output_path = staged.final_path().to_string();
 
-    let probe_output = timeout(FFPROBE_TIMEOUT, probe_future)
-        .await
-        .map_err(|_| {
-            format!(
-                "FFprobe timeout after {} seconds",
-                FFPROBE_TIMEOUT.as_secs()
-            )
-        })?
-        .map_err(|e| format!("Failed to probe video: {}", e))?;
+    let _sleep_guard = SleepInhibitGuard::try_acquire("FFmpeg merge").ok();
 
-    if !probe_output.status.success() {
-        return Err("Failed to probe video file".to_string());
+    // Plan the merge from probed inputs so the executed argv matches plan_merge
+    let ffprobe_path = resolve_ffprobe_path(app)?;
+    let ffmpeg_path = resolve_ffmpeg_path(app)?;
+    let plan = plan_merge_with_bins(&ffprobe_path, &ffmpeg_path, &staged_job).await?;
+    ensure_compatible(&plan.compatibility_issues)?;
+    let _chapters_file = ChaptersMetadataFile::write(
+        plan.chapters_metadata_path.as_deref(),
+        plan.chapters.as_deref(),
+    )?;
+    let _retimed_files = RetimedSubtitleFiles::write(&plan.retimed_subtitles)?;
+
+    // Without a duration from the caller, progress falls back to the planned output length
+    let duration_us = job.duration_us.or_else(|| {
+        plan.expected_duration_secs
+            .map(|secs| (secs * 1_000_000.0).round() as u64)
+    });
+
+    // A batch cancelled while the job was being prepared has no process to kill yet
+    let is_cancelled = || cancel_flag.is_some_and(|flag| flag.load(Ordering::SeqCst));
+    if is_cancelled() {
+        return Err(MERGE_CANCELLED.to_string());
     }
 
-    let probe_json: Value = serde_json::from_slice(&probe_output.stdout)
-        .map_err(|e| format!("Failed to parse probe output: {}", e))?;
-
-    let streams = probe_json
-        .get("streams")
-        .and_then(|s| s.as_array())
-        .cloned()
-        .unwrap_or_default();
-
-    let original_stream_count = streams.len();
-
-    let args = build_merge_args(
-        &video_path,
-        &tracks,
-        source_track_configs.as_deref(),
-        original_stream_count,
-        &output_path,
-    );
-
-    let ffmpeg_path = resolve_ffmpeg_path(&app)?;
-    let mut child = Command::new(ffmpeg_path)
-        .args(&args)
+    let mut child = Command::new(&ffmpeg_path)
+        .args(&plan.args)
         .stdout(Stdio::piped())
         .stderr(Stdio::piped())
         .spawn()
         .map_err(|e| format!("Failed to start ffmpeg: {}", e))?;
 
-    emit_merge_progress(&app, &video_path, &output_path, 0, None);
+    emit_merge_progress(app, &video_path, &output_path, 0, None);
 
     if let Some(pid) = child.id() {
         if let Ok(mut guard) = super::state::MERGE_PROCESS_IDS.lock() {
@@ -462,7 +660,12 @@ pub(crate) async fn merge_tracks(
     }
 
     if let Ok(mut guard) = super::state::MERGE_OUTPUT_PATHS.lock() {
-        guard.insert(video_path.clone(), output_path.clone());
+        guard.insert(video_path.clone(), staged.temp_path().to_string());
+    }
+
+    // Or cancelled after the flag was checked but before the process ID was registered
+    if is_cancelled() {
+        cancel_video_merge(&video_path)?;
     }
 
     if let Some(stdout) = child.stdout.take() {
@@ -491,6 +694,9 @@ pub(crate) async fn merge_tracks(
                         last_progress,
                         update.speed_bytes_per_sec,
                     );
+                    if let Some(hook) = &progress_hook {
+                        hook(last_progress, update.speed_bytes_per_sec);
+                    }
 
                     if update.is_end {
                         last_progress = 100;
@@ -506,12 +712,17 @@ pub(crate) async fn merge_tracks(
     let output = timeout(FFMPEG_MERGE_TIMEOUT, wait_future)
         .await
         .map_err(|_| {
-            if let Ok(mut guard) = super::state::MERGE_PROCESS_IDS.lock() {
-                guard.remove(&video_path);
+            let pid = super::state::MERGE_PROCESS_IDS
+                .lock()
+                .ok()
+                .and_then(|mut guard| guard.remove(&video_path));
+            if let Some(pid) = pid {
+                terminate_process(pid);
             }
             if let Ok(mut guard) = super::state::MERGE_OUTPUT_PATHS.lock() {
                 guard.remove(&video_path);
             }
+            staged.discard();
             format!(
                 "FFmpeg merge timeout after {} seconds",
                 FFMPEG_MERGE_TIMEOUT.as_secs()
@@ -524,24 +735,43 @@ pub(crate) async fn merge_tracks(
             if let Ok(mut guard) = super::state::MERGE_OUTPUT_PATHS.lock() {
                 guard.remove(&video_path);
             }
+            staged.discard();
             format!("Failed to execute ffmpeg: {}", e)
         })?;
 
-    if let Ok(mut guard) = super::state::MERGE_PROCESS_IDS.lock() {
-        guard.remove(&video_path);
-    }
+    // Cancelling removes the process ID before killing ffmpeg
+    let cancelled = super::state::MERGE_PROCESS_IDS
+        .lock()
+        .ok()
+        .and_then(|mut guard| guard.remove(&video_path))
+        .is_none();
     if let Ok(mut guard) = super::state::MERGE_OUTPUT_PATHS.lock() {
         guard.remove(&video_path);
     }
 
     if !output.status.success() {
+        staged.discard();
+        if cancelled {
+            return Err(MERGE_CANCELLED.to_string());
+        }
         let stderr = String::from_utf8_lossy(&output.stderr);
         return Err(format!("FFmpeg merge failed: {}", stderr));
     }
 
-    emit_merge_progress(&app, &video_path, &output_path, 100, None);
+    // Check the written file against the plan before moving it into place
+    let report = finish_merge_output(&ffprobe_path, &plan, &staged).await?;
+    let _ = app.emit(
+        "merge-verification",
+        json!({
+            "inputPath": video_path,
+            "report": report,
+        }),
+    );
+    let report = report.into_result()?;
 
-    Ok(())
+    emit_merge_progress(app, &video_path, &output_path, 100, None);
+
+    Ok(report)
 }
 
 #[cfg(test)]
@@ -549,7 +779,53 @@ mod tests {
     use serde_json::Value;
     use serde_json::json;
 
-    use super::{build_merge_args, enabled_source_indices, merge_tracks_with_bins};
+    use super::{
+        MergeInputLayout, build_merge_args, enabled_source_indices, merge_tracks_with_bins,
+    };
+    use crate::tools::merge::chapters::ChapterMapping;
+    use crate::tools::merge::compat::StreamConversion;
+    use crate::tools::merge::job::{MergeJob, MergeSourceTrack};
+
+    fn merge_job(tracks: Vec<Value>, source_track_configs: Option<Vec<Value>>) -> MergeJob {
+        merge_job_with_paths(
+            "/tmp/video.mkv",
+            "/tmp/out.mkv",
+            tracks,
+            source_track_configs,
+        )
+    }
+
+    fn merge_job_with_paths(
+        video_path: &str,
+        output_path: &str,
+        tracks: Vec<Value>,
+        source_track_configs: Option<Vec<Value>>,
+    ) -> MergeJob {
+        serde_json::from_value(json!({
+            "videoPath": video_path,
+            "outputPath": output_path,
+            "tracks": tracks,
+            "sourceTrackConfigs": source_track_configs
+        }))
+        .expect("merge job should deserialize")
+    }
+
+    fn layout(stream_count: usize, attachment_indices: &[usize]) -> MergeInputLayout {
+        MergeInputLayout {
+            stream_count,
+            attachment_indices: attachment_indices.to_vec(),
+            dropped_indices: Vec::new(),
+            chapters: ChapterMapping::Source,
+            conversions: Vec::new(),
+            clip: None,
+        }
+    }
+
+    fn position(args: &[String], value: &str) -> usize {
+        args.iter()
+            .position(|arg| arg == value)
+            .unwrap_or_else(|| panic!("{} should be in args", value))
+    }
 
     fn has_arg_pair(args: &[String], left: &str, right: &str) -> bool {
         args.windows(2)
@@ -579,11 +855,12 @@ mod tests {
 
     #[test]
     fn enabled_source_indices_filters_disabled_tracks() {
-        let configs = vec![
-            json!({"originalIndex": 0, "config": {"enabled": true}}),
-            json!({"originalIndex": 1, "config": {"enabled": false}}),
-            json!({"originalIndex": 2, "config": {"enabled": true}}),
-        ];
+        let configs: Vec<MergeSourceTrack> = serde_json::from_value(json!([
+            {"originalIndex": 0, "config": {"enabled": true}},
+            {"originalIndex": 1, "config": {"enabled": false}},
+            {"originalIndex": 2, "config": {"enabled": true}}
+        ]))
+        .expect("source configs should deserialize");
         let indices = enabled_source_indices(Some(&configs), 3);
         assert_eq!(indices, vec![0, 2]);
     }
@@ -601,7 +878,7 @@ mod tests {
             }
         })];
 
-        let args = build_merge_args("/tmp/video.mkv", &tracks, None, 2, "/tmp/out.mkv");
+        let args = build_merge_args(&merge_job(tracks, None), &layout(2, &[]));
 
         assert!(args.windows(2).any(|w| w == ["-itsoffset", "1.500"]));
         assert!(args.windows(2).any(|w| w == ["-map", "0:0"]));
@@ -618,7 +895,7 @@ mod tests {
             json!({"inputPath": "/tmp/sub2.srt", "config": {"language": "fra"}}),
         ];
 
-        let args = build_merge_args("/tmp/video.mkv", &tracks, None, 2, "/tmp/out.mkv");
+        let args = build_merge_args(&merge_job(tracks, None), &layout(2, &[]));
 
         assert!(has_arg_pair(&args, "-map", "0:0"));
         assert!(has_arg_pair(&args, "-map", "0:1"));
@@ -633,13 +910,7 @@ mod tests {
             json!({"originalIndex": 1, "config": {"enabled": true, "delayMs": 0}}),
         ];
 
-        let args = build_merge_args(
-            "/tmp/video.mkv",
-            &[],
-            Some(&source_configs),
-            2,
-            "/tmp/out.mkv",
-        );
+        let args = build_merge_args(&merge_job(vec![], Some(source_configs)), &layout(2, &[]));
 
         assert!(has_arg_pair(&args, "-itsoffset", "1.500"));
         assert!(has_arg_pair(&args, "-map", "1:0"));
@@ -654,13 +925,7 @@ mod tests {
             json!({"originalIndex": 2, "config": {"enabled": true, "delayMs": 0}}),
         ];
 
-        let args = build_merge_args(
-            "/tmp/video.mkv",
-            &[],
-            Some(&source_configs),
-            3,
-            "/tmp/out.mkv",
-        );
+        let args = build_merge_args(&merge_job(vec![], Some(source_configs)), &layout(3, &[]));
 
         assert_eq!(count_arg_pair(&args, "-itsoffset", "0.900"), 1);
         assert!(has_arg_pair(&args, "-map", "1:0"));
@@ -679,13 +944,7 @@ mod tests {
             "config": {"enabled": true, "delayMs": 1200, "language": "jpn"}
         })];
 
-        let args = build_merge_args(
-            "/tmp/video.mkv",
-            &tracks,
-            Some(&source_configs),
-            2,
-            "/tmp/out.mkv",
-        );
+        let args = build_merge_args(&merge_job(tracks, Some(source_configs)), &layout(2, &[]));
 
         assert!(has_arg_pair(&args, "-itsoffset", "1.200"));
         assert!(has_arg_pair(&args, "-map", "1:0"));
@@ -717,13 +976,7 @@ mod tests {
             }
         })];
 
-        let args = build_merge_args(
-            "/tmp/video.mkv",
-            &tracks,
-            Some(&source_configs),
-            1,
-            "/tmp/out.mkv",
-        );
+        let args = build_merge_args(&merge_job(tracks, Some(source_configs)), &layout(1, &[]));
 
         assert!(has_arg_pair(&args, "-metadata:s:0", "language=jpn"));
         assert!(has_arg_pair(&args, "-metadata:s:0", "title=Main stream"));
@@ -738,6 +991,98 @@ mod tests {
         assert!(has_arg_pair(&args, "-disposition:1", "default"));
     }
 
+    #[test]
+    fn build_merge_args_attaches_fonts_after_mapped_streams() {
+        let mut job = merge_job(
+            vec![json!({"inputPath": "/tmp/sub.ass", "config": {"language": "eng"}})],
+            None,
+        );
+        job.attachments = serde_json::from_value(json!([
+            {"inputPath": "/tmp/fonts/Main.ttf"},
+            {"inputPath": "/tmp/cover", "fileName": "cover.jpg", "mimeType": "image/jpeg"}
+        ]))
+        .expect("attachments should deserialize");
+
+        let args = build_merge_args(&job, &layout(3, &[2]));
+
+        assert!(position(&args, "0:1") < position(&args, "1:0"));
+        assert!(position(&args, "1:0") < position(&args, "0:2"));
+        assert!(position(&args, "0:2") < position(&args, "/tmp/fonts/Main.ttf"));
+        assert!(has_arg_pair(&args, "-c:t", "copy"));
+        assert!(has_arg_pair(
+            &args,
+            "-metadata:s:t:1",
+            "mimetype=application/x-truetype-font"
+        ));
+        assert!(has_arg_pair(&args, "-metadata:s:t:1", "filename=Main.ttf"));
+        assert!(has_arg_pair(
+            &args,
+            "-metadata:s:t:2",
+            "mimetype=image/jpeg"
+        ));
+        assert!(has_arg_pair(&args, "-metadata:s:t:2", "filename=cover.jpg"));
+    }
+
+    #[test]
+    fn build_merge_args_keeps_source_attachments_unless_disabled() {
+        let mut job = merge_job(
+            vec![],
+            Some(vec![
+                json!({"originalIndex": 0}),
+                json!({"originalIndex": 1}),
+            ]),
+        );
+
+        let args = build_merge_args(&job, &layout(4, &[2, 3]));
+        assert!(has_arg_pair(&args, "-map", "0:2"));
+        assert!(has_arg_pair(&args, "-map", "0:3"));
+        assert!(position(&args, "0:1") < position(&args, "0:2"));
+
+        job.source_attachments = serde_json::from_value(json!([
+            {"originalIndex": 3, "enabled": false}
+        ]))
+        .expect("source attachments should deserialize");
+
+        let args = build_merge_args(&job, &layout(4, &[2, 3]));
+        assert!(has_arg_pair(&args, "-map", "0:2"));
+        assert!(!has_arg_pair(&args, "-map", "0:3"));
+    }
+
+    #[test]
+    fn build_merge_args_overrides_codec_for_converted_streams() {
+        let job = merge_job(vec![json!({"inputPath": "/tmp/sub.srt"})], None);
+        let mut layout = layout(2, &[]);
+        layout.conversions = vec![
+            StreamConversion {
+                output_index: 1,
+                specifier: "a:0".to_string(),
+                from_codec: "truehd".to_string(),
+                to_codec: "aac".to_string(),
+                encoder: "aac".to_string(),
+                bitrate: Some("448k".to_string()),
+                channel_layout: Some("stereo".to_string()),
+            },
+            StreamConversion {
+                output_index: 2,
+                specifier: "s:0".to_string(),
+                from_codec: "subrip".to_string(),
+                to_codec: "mov_text".to_string(),
+                encoder: "mov_text".to_string(),
+                bitrate: None,
+                channel_layout: None,
+            },
+        ];
+
+        let args = build_merge_args(&job, &layout);
+
+        assert!(has_arg_pair(&args, "-c:s", "copy"));
+        assert!(has_arg_pair(&args, "-c:a:0", "aac"));
+        assert!(has_arg_pair(&args, "-b:a:0", "448k"));
+        assert!(has_arg_pair(&args, "-ch_layout:a:0", "stereo"));
+        assert!(has_arg_pair(&args, "-c:s:0", "mov_text"));
+        assert!(position(&args, "-c:s") < position(&args, "-c:s:0"));
+    }
+
     #[tokio::test]
     async fn merge_tracks_adds_external_subtitle_track() {
         let video = crate::test_support::assets::ensure_sample_video()
@@ -762,14 +1107,12 @@ mod tests {
             }
         })];
 
-        let probe_json = crate::tools::ffprobe::probe::probe_file_with_ffprobe(
+        let probe_value = crate::tools::ffprobe::probe::probe_json_with_ffprobe(
             "ffprobe",
             video.to_string_lossy().as_ref(),
         )
         .await
         .expect("probe should succeed");
-        let probe_value: serde_json::Value =
-            serde_json::from_str(&probe_json).expect("valid probe json expected");
         let source_track_configs: Vec<serde_json::Value> = probe_value
             .get("streams")
             .and_then(|v| v.as_array())
@@ -791,27 +1134,28 @@ mod tests {
             })
             .collect();
 
-        merge_tracks_with_bins(
+        let report = merge_tracks_with_bins(
             "ffprobe",
             "ffmpeg",
-            video.to_string_lossy().as_ref(),
-            &tracks,
-            Some(&source_track_configs),
-            output.to_string_lossy().as_ref(),
+            &merge_job_with_paths(
+                video.to_string_lossy().as_ref(),
+                output.to_string_lossy().as_ref(),
+                tracks,
+                Some(source_track_configs),
+            ),
         )
         .await
         .expect("merge should succeed");
 
+        assert!(report.passed);
         assert!(output.exists());
 
-        let merged_probe = crate::tools::ffprobe::probe::probe_file_with_ffprobe(
+        let merged_value = crate::tools::ffprobe::probe::probe_json_with_ffprobe(
             "ffprobe",
             output.to_string_lossy().as_ref(),
         )
         .await
         .expect("probe merged output should succeed");
-        let merged_value: Value =
-            serde_json::from_str(&merged_probe).expect("merged probe json should be valid");
 
         let subtitle_stream = merged_value
             .get("streams")
@@ -857,14 +1201,12 @@ mod tests {
         let temp = tempfile::tempdir().expect("failed to create tempdir");
         let output = temp.path().join("merged-source-delay.mkv");
 
-        let probe_json = crate::tools::ffprobe::probe::probe_file_with_ffprobe(
+        let probe_value = crate::tools::ffprobe::probe::probe_json_with_ffprobe(
             "ffprobe",
             video.to_string_lossy().as_ref(),
         )
         .await
         .expect("probe should succeed");
-        let probe_value: Value =
-            serde_json::from_str(&probe_json).expect("valid probe json expected");
 
         let source_track_configs: Vec<Value> = probe_value
             .get("streams")
@@ -893,22 +1235,22 @@ mod tests {
         merge_tracks_with_bins(
             "ffprobe",
             "ffmpeg",
-            video.to_string_lossy().as_ref(),
-            &[],
-            Some(&source_track_configs),
-            output.to_string_lossy().as_ref(),
+            &merge_job_with_paths(
+                video.to_string_lossy().as_ref(),
+                output.to_string_lossy().as_ref(),
+                vec![],
+                Some(source_track_configs),
+            ),
         )
         .await
         .expect("merge should succeed");
 
-        let merged_probe = crate::tools::ffprobe::probe::probe_file_with_ffprobe(
+        let merged_value = crate::tools::ffprobe::probe::probe_json_with_ffprobe(
             "ffprobe",
             output.to_string_lossy().as_ref(),
         )
         .await
         .expect("probe merged output should succeed");
-        let merged_value: Value =
-            serde_json::from_str(&merged_probe).expect("merged probe json should be valid");
 
         let streams = merged_value
             .get("streams")
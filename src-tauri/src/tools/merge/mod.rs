@@ -1,3 +1,12 @@
+pub(crate) mod batch;
 pub(crate) mod cancel;
+mod chapters;
+pub(crate) mod compat;
+pub(crate) mod job;
+pub(crate) mod matching;
 pub(crate) mod merge;
+pub(crate) mod plan;
+pub(crate) mod remux;
+pub(crate) mod retime;
 mod state;
+pub(crate) mod verify;
@@ -0,0 +1,1038 @@
+use crate::shared::chapters::Chapter;
+use crate::shared::clip::{ClipCut, ClipRange};
+use crate::shared::output::resolve_output_path;
+use crate::shared::store::{resolve_ffmpeg_path, resolve_ffprobe_path};
+use crate::tools::ffprobe::keyframes::resolve_clip;
+use crate::tools::merge::chapters::{
+    ChapterMapping, chapters_metadata_path, clip_chapters, resolve_output_chapters,
+};
+use crate::tools::merge::compat::{
+    MergeCompatibilityIssue, StreamConversion, check_output_compatibility,
+};
+use crate::tools::merge::job::{AudioEncodeConfig, MergeJob, MergeTrackRef};
+use crate::tools::merge::merge::{
+    MergeInputLayout, OutputTrackSlot, attachment_stream_indices, build_merge_args,
+    build_source_track_selections, output_track_slots, probe_duration_secs, probe_media,
+    probe_streams, skipped_source_indices,
+};
+use crate::tools::merge::retime::{RetimedSubtitle, plan_retimed_subtitles};
+use serde::Serialize;
+use serde_json::Value;
+
+/// One stream of the planned output file
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergePlanStream {
+    pub(crate) index: usize,
+    pub(crate) input_index: usize,
+    pub(crate) input_path: String,
+    pub(crate) input_stream_index: usize,
+    pub(crate) codec_type: Option<String>,
+    pub(crate) codec: Option<String>,
+    pub(crate) language: Option<String>,
+    pub(crate) title: Option<String>,
+    pub(crate) default: bool,
+    pub(crate) forced: bool,
+    /// Requested re-encode, None when the stream is copied as-is
+    pub(crate) audio_encode: Option<AudioEncodeConfig>,
+}
+
+/// A file attached with `-attach`
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergePlanAttachment {
+    pub(crate) input_path: String,
+    pub(crate) file_name: String,
+    pub(crate) mime_type: Option<String>,
+}
+
+/// What `merge_tracks` would run for a job, without running it
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergePlan {
+    pub(crate) program: String,
+    pub(crate) args: Vec<String>,
+    pub(crate) command_line: String,
+    pub(crate) streams: Vec<MergePlanStream>,
+    pub(crate) attachments: Vec<MergePlanAttachment>,
+    /// None when the source chapters pass through untouched
+    pub(crate) chapters: Option<Vec<Chapter>>,
+    pub(crate) chapters_metadata_path: Option<String>,
+    pub(crate) conversions: Vec<StreamConversion>,
+    /// Streams the output container cannot hold; merging refuses to run while non-empty
+    pub(crate) compatibility_issues: Vec<MergeCompatibilityIssue>,
+    /// Attached subtitles muxed from retimed temp copies
+    pub(crate) retimed_subtitles: Vec<RetimedSubtitle>,
+    pub(crate) warnings: Vec<String>,
+    pub(crate) expected_duration_secs: Option<f64>,
+    /// Requested and actual cut points when only part of the source is kept
+    pub(crate) clip: Option<ClipCut>,
+}
+
+pub(super) fn stream_str(stream: Option<&Value>, key: &str) -> Option<String> {
+    stream
+        .and_then(|s| s.get(key))
+        .and_then(|v| v.as_str())
+        .map(str::to_string)
+}
+
+pub(super) fn stream_tag(stream: Option<&Value>, key: &str) -> Option<String> {
+    stream
+        .and_then(|s| s.get("tags"))
+        .and_then(|tags| tags.get(key))
+        .and_then(|v| v.as_str())
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+pub(super) fn stream_disposition(stream: Option<&Value>, key: &str) -> bool {
+    stream
+        .and_then(|s| s.get("disposition"))
+        .and_then(|d| d.get(key))
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0)
+        == 1
+}
+
+fn shell_quote(arg: &str) -> String {
+    let is_safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./:=+,@%".contains(c));
+    if is_safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+/// Describe the output stream table in the order `build_merge_args` maps it
+///
+/// `attached_streams` holds the streams of each attached track's file, then of each replacement's
+pub(super) fn plan_output_streams(
+    job: &MergeJob,
+    source_streams: &[Value],
+    attached_streams: &[Vec<Value>],
+) -> Vec<MergePlanStream> {
+    let source_attachment_indices = attachment_stream_indices(source_streams);
+    let mut scratch_args = Vec::new();
+    let (selections, first_attached_input) = build_source_track_selections(
+        job.source_track_configs.as_deref(),
+        source_streams.len(),
+        &skipped_source_indices(job, source_streams),
+        &job.replacements,
+        &mut scratch_args,
+        &job.video_path,
+        None,
+    );
+
+    // Output indices are assigned once the track order is known
+    let mut source_tracks = Vec::new();
+    let mut attached_tracks = Vec::new();
+
+    for selection in &selections {
+        if let Some((position, replacement)) = selection.replacement {
+            let replaced = source_streams.get(selection.original_index);
+            let input = attached_streams
+                .get(job.tracks.len() + position)
+                .and_then(|input_streams| input_streams.get(replacement.track_index));
+            let (language, title, default, forced) = match replacement.config.as_ref() {
+                Some(cfg) => (
+                    cfg.language_code()
+                        .or_else(|| stream_tag(input, "language")),
+                    cfg.title
+                        .clone()
+                        .filter(|title| !title.is_empty())
+                        .or_else(|| stream_tag(input, "title")),
+                    cfg.default,
+                    cfg.forced,
+                ),
+                None => (
+                    stream_tag(replaced, "language"),
+                    stream_tag(replaced, "title"),
+                    stream_disposition(input, "default"),
+                    stream_disposition(input, "forced"),
+                ),
+            };
+
+            source_tracks.push(MergePlanStream {
+                index: 0,
+                input_index: selection.input_idx,
+                input_path: replacement.input_path.clone(),
+                input_stream_index: replacement.track_index,
+                codec_type: stream_str(input, "codec_type"),
+                codec: stream_str(input, "codec_name"),
+                language,
+                title,
+                default,
+                forced,
+                audio_encode: replacement
+                    .config
+                    .as_ref()
+                    .and_then(|cfg| cfg.audio_encode.clone()),
+            });
+            continue;
+        }
+
+        let source = source_streams.get(selection.original_index);
+        let audio_encode = selection.config.and_then(|cfg| cfg.audio_encode.clone());
+        let (language, title, default, forced) = match selection.config {
+            Some(cfg) => (
+                cfg.language_code()
+                    .or_else(|| stream_tag(source, "language")),
+                cfg.title.clone().filter(|title| !title.is_empty()),
+                cfg.default,
+                cfg.forced,
+            ),
+            None => (
+                stream_tag(source, "language"),
+                stream_tag(source, "title"),
+                stream_disposition(source, "default"),
+                stream_disposition(source, "forced"),
+            ),
+        };
+
+        source_tracks.push(MergePlanStream {
+            index: 0,
+            input_index: selection.input_idx,
+            input_path: job.video_path.clone(),
+            input_stream_index: selection.original_index,
+            codec_type: stream_str(source, "codec_type"),
+            codec: stream_str(source, "codec_name"),
+            language,
+            title,
+            default,
+            forced,
+            audio_encode,
+        });
+    }
+
+    for (i, track) in job.tracks.iter().enumerate() {
+        let input = attached_streams
+            .get(i)
+            .and_then(|input_streams| input_streams.get(track.track_index));
+        let audio_encode = track
+            .config
+            .as_ref()
+            .and_then(|cfg| cfg.audio_encode.clone());
+        let (language, title, default, forced) = match track.config.as_ref() {
+            Some(cfg) => (
+                cfg.language_code()
+                    .filter(|lang| lang != "und")
+                    .or_else(|| stream_tag(input, "language")),
+                cfg.title
+                    .clone()
+                    .filter(|title| !title.is_empty())
+                    .or_else(|| stream_tag(input, "title")),
+                cfg.default,
+                cfg.forced,
+            ),
+            None => (
+                stream_tag(input, "language"),
+                stream_tag(input, "title"),
+                stream_disposition(input, "default"),
+                stream_disposition(input, "forced"),
+            ),
+        };
+
+        attached_tracks.push(MergePlanStream {
+            index: 0,
+            input_index: first_attached_input + i,
+            input_path: track.input_path.clone(),
+            input_stream_index: track.track_index,
+            codec_type: stream_str(input, "codec_type"),
+            codec: stream_str(input, "codec_name"),
+            language,
+            title,
+            default,
+            forced,
+            audio_encode,
+        });
+    }
+
+    let mut streams: Vec<MergePlanStream> = output_track_slots(job, &selections)
+        .into_iter()
+        .map(|slot| match slot {
+            OutputTrackSlot::Source(i) => source_tracks[i].clone(),
+            OutputTrackSlot::Attached(i) => attached_tracks[i].clone(),
+        })
+        .collect();
+    for (index, stream) in streams.iter_mut().enumerate() {
+        stream.index = index;
+    }
+
+    for original_index in job.kept_source_attachments(&source_attachment_indices) {
+        let source = source_streams.get(original_index);
+        streams.push(MergePlanStream {
+            index: streams.len(),
+            input_index: 0,
+            input_path: job.video_path.clone(),
+            input_stream_index: original_index,
+            codec_type: stream_str(source, "codec_type"),
+            codec: stream_str(source, "codec_name"),
+            language: None,
+            title: None,
+            default: false,
+            forced: false,
+            audio_encode: None,
+        });
+    }
+
+    streams
+}
+
+/// ffmpeg specifier counting only earlier output streams of the same type, e.g. "a:1"
+pub(crate) fn output_stream_specifier(streams: &[MergePlanStream], index: usize) -> String {
+    let codec_type = streams.get(index).and_then(|s| s.codec_type.as_deref());
+    let type_letter = match codec_type {
+        Some("video") => "v",
+        Some("audio") => "a",
+        Some("subtitle") => "s",
+        Some("attachment") => "t",
+        _ => return index.to_string(),
+    };
+    let type_index = streams[..index]
+        .iter()
+        .filter(|s| s.codec_type.as_deref() == codec_type)
+        .count();
+    format!("{}:{}", type_letter, type_index)
+}
+
+/// Conversions for audio streams whose track config asks for a re-encode
+fn audio_encode_conversions(streams: &[MergePlanStream]) -> Vec<StreamConversion> {
+    streams
+        .iter()
+        .filter(|stream| stream.codec_type.as_deref() == Some("audio"))
+        .filter_map(|stream| {
+            let encode = stream.audio_encode.as_ref()?;
+            Some(StreamConversion {
+                output_index: stream.index,
+                specifier: output_stream_specifier(streams, stream.index),
+                from_codec: stream.codec.clone().unwrap_or_default(),
+                to_codec: encode.codec.clone(),
+                encoder: encode.encoder()?.to_string(),
+                bitrate: encode.bitrate.clone(),
+                channel_layout: encode.channel_layout.clone(),
+            })
+        })
+        .collect()
+}
+
+/// Longest mapped input after its delay is applied, in seconds
+pub(super) fn expected_output_duration(
+    job: &MergeJob,
+    source_probe: &Value,
+    attached_probes: &[Value],
+) -> Option<f64> {
+    let source_streams = probe_streams(source_probe);
+    let mut scratch_args = Vec::new();
+    let (selections, _) = build_source_track_selections(
+        job.source_track_configs.as_deref(),
+        source_streams.len(),
+        &skipped_source_indices(job, source_streams),
+        &job.replacements,
+        &mut scratch_args,
+        &job.video_path,
+        None,
+    );
+
+    let source_duration = probe_duration_secs(source_probe);
+    let source_ends = selections.iter().filter_map(|selection| {
+        let delay_ms = selection.config.map(|cfg| cfg.delay_ms).unwrap_or(0);
+        let duration = match selection.replacement {
+            Some((position, _)) => attached_probes
+                .get(job.tracks.len() + position)
+                .and_then(probe_duration_secs),
+            None => source_duration,
+        };
+        duration.map(|d| d + delay_ms as f64 / 1000.0)
+    });
+    let attached_ends = job.tracks.iter().enumerate().filter_map(|(i, track)| {
+        let stretch = track
+            .config
+            .as_ref()
+            .and_then(|cfg| cfg.retime.as_ref())
+            .and_then(|retime| retime.factor())
+            .unwrap_or(1.0);
+        attached_probes
+            .get(i)
+            .and_then(probe_duration_secs)
+            .map(|d| d * stretch + track.delay_ms() as f64 / 1000.0)
+    });
+
+    source_ends
+        .chain(attached_ends)
+        .map(|end| end.max(0.0))
+        .reduce(f64::max)
+}
+
+/// Flag layouts that players handle badly
+pub(super) fn plan_warnings(streams: &[MergePlanStream]) -> Vec<String> {
+    let mut warnings = Vec::new();
+    let is_type = |stream: &MergePlanStream, codec_type: &str| {
+        stream.codec_type.as_deref() == Some(codec_type)
+    };
+
+    let audio_streams: Vec<&MergePlanStream> =
+        streams.iter().filter(|s| is_type(s, "audio")).collect();
+    if !audio_streams.is_empty() && !audio_streams.iter().any(|s| s.default) {
+        warnings.push("No audio track is marked as default".to_string());
+    }
+
+    let default_subtitles = streams
+        .iter()
+        .filter(|s| is_type(s, "subtitle") && s.default)
+        .count();
+    if default_subtitles > 1 {
+        warnings.push(format!(
+            "{} subtitle tracks are marked as default",
+            default_subtitles
+        ));
+    }
+
+    for stream in streams {
+        if stream.audio_encode.is_some() && !is_type(stream, "audio") {
+            warnings.push(format!(
+                "Stream {}: audio re-encode is ignored for a {} track",
+                stream.index,
+                stream.codec_type.as_deref().unwrap_or("unknown")
+            ));
+        }
+    }
+
+    warnings
+}
+
+/// Reject `outputOrder` entries naming source streams that are not mapped as tracks
+fn check_output_order(job: &MergeJob, source_streams: &[Value]) -> Result<(), String> {
+    let Some(order) = job.output_order.as_deref() else {
+        return Ok(());
+    };
+
+    let mut scratch_args = Vec::new();
+    let (selections, _) = build_source_track_selections(
+        job.source_track_configs.as_deref(),
+        source_streams.len(),
+        &skipped_source_indices(job, source_streams),
+        &job.replacements,
+        &mut scratch_args,
+        &job.video_path,
+        None,
+    );
+    for entry in order {
+        if let MergeTrackRef::Source(original_index) = entry
+            && !selections
+                .iter()
+                .any(|s| s.original_index == *original_index)
+        {
+            return Err(format!(
+                "outputOrder lists source stream {} which is not in the output",
+                original_index
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// `attached_probes` holds one probe per attached track, then one per replacement
+pub(super) fn build_merge_plan(
+    ffmpeg_path: &str,
+    job: &MergeJob,
+    source_probe: &Value,
+    attached_probes: &[Value],
+) -> Result<MergePlan, String> {
+    let source_streams = probe_streams(source_probe);
+    check_output_order(job, source_streams)?;
+    let attached_streams: Vec<Vec<Value>> = attached_probes
+        .iter()
+        .map(|probe| probe_streams(probe).to_vec())
+        .collect();
+
+    // Chapters are resolved on the full timeline, then cut down to the clip
+    let clip = job.clip.as_ref().map(ClipCut::exact);
+    let full_duration_secs = expected_output_duration(job, source_probe, attached_probes);
+    let total_ms = full_duration_secs.map(|secs| (secs * 1000.0).round() as u64);
+    let mut chapters = resolve_output_chapters(job, source_probe, total_ms)?;
+    if let Some(clip) = &clip {
+        chapters = chapters.map(|chapters| clip_chapters(chapters, clip));
+    }
+    let expected_duration_secs = match &clip {
+        Some(clip) => clip.clipped_duration_secs(full_duration_secs),
+        None => full_duration_secs,
+    };
+    let chapters_metadata_path = chapters
+        .as_ref()
+        .filter(|chapters| !chapters.is_empty())
+        .map(|_| chapters_metadata_path());
+
+    let mut layout = MergeInputLayout::from_streams(source_streams);
+    layout.dropped_indices = job.dropped_source_indices(source_streams);
+    layout.chapters = match (&chapters, &chapters_metadata_path) {
+        (None, _) => ChapterMapping::Source,
+        (Some(_), Some(path)) => ChapterMapping::Metadata(path.clone()),
+        (Some(_), None) => ChapterMapping::Drop,
+    };
+    layout.clip = clip.clone();
+
+    let mut streams = plan_output_streams(job, source_streams, &attached_streams);
+    if clip.as_ref().is_some_and(ClipCut::reencodes_video) {
+        for stream in streams
+            .iter_mut()
+            .filter(|s| s.codec_type.as_deref() == Some("video"))
+        {
+            stream.codec = Some("h264".to_string());
+        }
+    }
+    let mut conversions = audio_encode_conversions(&streams);
+    for conversion in &conversions {
+        streams[conversion.output_index].codec = Some(conversion.to_codec.clone());
+    }
+
+    // The container check sees requested codecs, and its conversion wins when both apply
+    let compatibility =
+        check_output_compatibility(&job.output_path, &streams, job.transcode_incompatible_audio);
+    for conversion in compatibility.conversions {
+        streams[conversion.output_index].codec = Some(conversion.to_codec.clone());
+        match conversions
+            .iter_mut()
+            .find(|c| c.output_index == conversion.output_index)
+        {
+            Some(requested) => {
+                *requested = StreamConversion {
+                    from_codec: requested.from_codec.clone(),
+                    ..conversion
+                }
+            }
+            None => conversions.push(conversion),
+        }
+    }
+    conversions.sort_by_key(|c| c.output_index);
+    layout.conversions = conversions.clone();
+
+    // ffmpeg reads retimed subtitles from their temp copies
+    let retimed_subtitles = plan_retimed_subtitles(job);
+    let mut args_job = job.clone();
+    for retimed in &retimed_subtitles {
+        args_job.tracks[retimed.track].input_path = retimed.retimed_path.clone();
+    }
+
+    let args = build_merge_args(&args_job, &layout);
+    let attachments = job
+        .attachments
+        .iter()
+        .map(|attachment| MergePlanAttachment {
+            input_path: attachment.input_path.clone(),
+            file_name: attachment.file_name(),
+            mime_type: attachment.mime_type(),
+        })
+        .collect();
+    let mut warnings = plan_warnings(&streams);
+    warnings.extend(compatibility.warnings);
+    let command_line = std::iter::once(ffmpeg_path)
+        .chain(args.iter().map(String::as_str))
+        .map(shell_quote)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    Ok(MergePlan {
+        program: ffmpeg_path.to_string(),
+        args,
+        command_line,
+        streams,
+        attachments,
+        chapters,
+        chapters_metadata_path,
+        conversions,
+        compatibility_issues: compatibility.issues,
+        retimed_subtitles,
+        warnings,
+        expected_duration_secs,
+        clip,
+    })
+}
+
+pub(super) async fn plan_merge_with_bins(
+    ffprobe_path: &str,
+    ffmpeg_path: &str,
+    job: &MergeJob,
+) -> Result<MergePlan, String> {
+    job.validate()?;
+
+    let source_probe = probe_media(ffprobe_path, &job.video_path).await?;
+    job.validate_source_indices(probe_streams(&source_probe).len())?;
+
+    let mut attached_probes = Vec::with_capacity(job.tracks.len() + job.replacements.len());
+    for track in &job.tracks {
+        attached_probes.push(probe_media(ffprobe_path, &track.input_path).await?);
+    }
+    for replacement in &job.replacements {
+        attached_probes.push(probe_media(ffprobe_path, &replacement.input_path).await?);
+    }
+
+    let Some(range) = job.clip.as_ref() else {
+        return build_merge_plan(ffmpeg_path, job, &source_probe, &attached_probes);
+    };
+
+    // Plan with the snapped range, then report it next to the requested one
+    let cut = resolve_clip(ffprobe_path, &job.video_path, "v:0", range).await?;
+    let mut clipped_job = job.clone();
+    clipped_job.clip = Some(ClipRange {
+        start_ms: cut.start_ms,
+        end_ms: cut.end_ms,
+        mode: cut.mode,
+    });
+    let mut plan = build_merge_plan(ffmpeg_path, &clipped_job, &source_probe, &attached_probes)?;
+    plan.clip = Some(cut);
+    Ok(plan)
+}
+
+/// Build the ffmpeg command and output stream table for a merge without running it
+/// Takes the same job as `merge_tracks`, so the previewed plan is the one that runs
+#[tauri::command]
+pub(crate) async fn plan_merge(
+    app: tauri::AppHandle,
+    mut job: MergeJob,
+) -> Result<MergePlan, String> {
+    // Show where the file would land after the overwrite policy is applied
+    job.output_path = resolve_output_path(&job.output_path, job.overwrite_policy)?;
+
+    let ffprobe_path = resolve_ffprobe_path(&app)?;
+    let ffmpeg_path = resolve_ffmpeg_path(&app)?;
+    plan_merge_with_bins(&ffprobe_path, &ffmpeg_path, &job).await
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::{build_merge_plan, expected_output_duration, plan_merge_with_bins, shell_quote};
+    use crate::tools::merge::job::{MergeJob, SourceChapterMode};
+
+    fn merge_job(tracks: Value, source_track_configs: Value) -> MergeJob {
+        serde_json::from_value(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out put.mkv",
+            "tracks": tracks,
+            "sourceTrackConfigs": source_track_configs
+        }))
+        .expect("merge job should deserialize")
+    }
+
+    fn source_probe() -> Value {
+        json!({
+            "streams": source_streams(),
+            "format": {"duration": "10.000000"}
+        })
+    }
+
+    fn source_streams() -> Vec<Value> {
+        vec![
+            json!({"index": 0, "codec_type": "video", "codec_name": "h264"}),
+            json!({
+                "index": 1,
+                "codec_type": "audio",
+                "codec_name": "aac",
+                "tags": {"language": "jpn", "title": "Stereo"},
+                "disposition": {"default": 1, "forced": 0}
+            }),
+            json!({
+                "index": 2,
+                "codec_type": "subtitle",
+                "codec_name": "ass",
+                "disposition": {"default": 1}
+            }),
+        ]
+    }
+
+    #[test]
+    fn build_merge_plan_lists_source_and_attached_streams_in_output_order() {
+        let job = merge_job(
+            json!([{"inputPath": "/tmp/sub.srt", "config": {"language": "eng", "forced": true}}]),
+            json!([
+                {"originalIndex": 0},
+                {"originalIndex": 1, "config": {"enabled": false}},
+                {"originalIndex": 2, "config": {"title": "Signs", "delayMs": 500}}
+            ]),
+        );
+        let attached = vec![json!({
+            "streams": [{"index": 0, "codec_type": "subtitle", "codec_name": "subrip"}]
+        })];
+
+        let plan = build_merge_plan("ffmpeg", &job, &source_probe(), &attached)
+            .expect("plan should build");
+
+        assert_eq!(plan.streams.len(), 3);
+        assert_eq!(plan.streams[0].codec.as_deref(), Some("h264"));
+        assert_eq!(plan.streams[1].input_index, 1);
+        assert_eq!(plan.streams[1].input_stream_index, 2);
+        assert_eq!(plan.streams[1].title.as_deref(), Some("Signs"));
+        assert!(!plan.streams[1].default);
+        assert_eq!(plan.streams[2].input_index, 2);
+        assert_eq!(plan.streams[2].codec.as_deref(), Some("subrip"));
+        assert_eq!(plan.streams[2].language.as_deref(), Some("eng"));
+        assert!(plan.streams[2].forced);
+        assert_eq!(
+            plan.args.last().map(String::as_str),
+            Some("/tmp/out put.mkv")
+        );
+        assert!(plan.command_line.starts_with("ffmpeg -y -i /tmp/video.mkv"));
+        assert!(plan.command_line.ends_with("'/tmp/out put.mkv'"));
+    }
+
+    #[test]
+    fn build_merge_plan_warns_when_no_audio_track_is_default() {
+        let job = merge_job(
+            json!([]),
+            json!([
+                {"originalIndex": 0},
+                {"originalIndex": 1, "config": {"default": false}}
+            ]),
+        );
+
+        let plan =
+            build_merge_plan("ffmpeg", &job, &source_probe(), &[]).expect("plan should build");
+
+        assert!(
+            plan.warnings
+                .iter()
+                .any(|w| w.contains("No audio track is marked as default"))
+        );
+    }
+
+    #[test]
+    fn build_merge_plan_warns_about_multiple_default_subtitles() {
+        let job = merge_job(
+            json!([{"inputPath": "/tmp/sub.srt", "config": {"default": true}}]),
+            Value::Null,
+        );
+        let attached = vec![json!({
+            "streams": [{"index": 0, "codec_type": "subtitle", "codec_name": "subrip"}]
+        })];
+
+        let plan = build_merge_plan("ffmpeg", &job, &source_probe(), &attached)
+            .expect("plan should build");
+
+        assert!(
+            plan.warnings
+                .iter()
+                .any(|w| w.contains("2 subtitle tracks are marked as default"))
+        );
+        assert!(!plan.warnings.iter().any(|w| w.contains("audio")));
+    }
+
+    #[test]
+    fn build_merge_plan_lists_attachments_after_tracks() {
+        let mut job = merge_job(json!([]), Value::Null);
+        job.attachments = serde_json::from_value(json!([{"inputPath": "/tmp/Font.otf"}]))
+            .expect("attachments should deserialize");
+        let mut probe = source_probe();
+        probe["streams"]
+            .as_array_mut()
+            .expect("streams array")
+            .insert(
+                1,
+                json!({"index": 1, "codec_type": "attachment", "codec_name": "ttf"}),
+            );
+
+        let plan = build_merge_plan("ffmpeg", &job, &probe, &[]).expect("plan should build");
+
+        assert_eq!(plan.streams.len(), 4);
+        assert_eq!(plan.streams[3].input_stream_index, 1);
+        assert_eq!(plan.streams[3].codec.as_deref(), Some("ttf"));
+        assert_eq!(plan.attachments.len(), 1);
+        assert_eq!(plan.attachments[0].file_name, "Font.otf");
+        assert_eq!(
+            plan.attachments[0].mime_type.as_deref(),
+            Some("application/vnd.ms-opentype")
+        );
+    }
+
+    #[test]
+    fn build_merge_plan_writes_chapters_through_ffmetadata_input() {
+        let mut job = merge_job(json!([]), Value::Null);
+        job.chapters = serde_json::from_value(json!({
+            "source": "list",
+            "chapters": [{"startMs": 0, "title": "Intro"}, {"startMs": 4000, "title": "Main"}]
+        }))
+        .expect("chapters should deserialize");
+
+        let plan =
+            build_merge_plan("ffmpeg", &job, &source_probe(), &[]).expect("plan should build");
+
+        let metadata_path = plan
+            .chapters_metadata_path
+            .clone()
+            .expect("chapters should use a metadata file");
+        let chapters = plan.chapters.expect("chapters should be planned");
+        assert_eq!(chapters[1].end_ms, Some(10_000));
+        let path_idx = plan
+            .args
+            .iter()
+            .position(|arg| *arg == metadata_path)
+            .expect("metadata input should be in args");
+        assert_eq!(plan.args[path_idx - 3], "-f");
+        assert_eq!(plan.args[path_idx - 2], "ffmetadata");
+        assert!(plan.args.windows(2).any(|w| w == ["-map_chapters", "1"]));
+    }
+
+    #[test]
+    fn build_merge_plan_drops_source_chapters_on_request() {
+        let mut job = merge_job(json!([]), Value::Null);
+        job.source_chapters = SourceChapterMode::Drop;
+
+        let plan =
+            build_merge_plan("ffmpeg", &job, &source_probe(), &[]).expect("plan should build");
+
+        assert_eq!(plan.chapters, Some(vec![]));
+        assert!(plan.chapters_metadata_path.is_none());
+        assert!(plan.args.windows(2).any(|w| w == ["-map_chapters", "-1"]));
+    }
+
+    #[test]
+    fn build_merge_plan_converts_subtitles_and_reports_truehd_for_mp4() {
+        let mut job = merge_job(json!([]), Value::Null);
+        job.output_path = "/tmp/out.mp4".to_string();
+        let mut probe = source_probe();
+        probe["streams"][1]["codec_name"] = json!("truehd");
+
+        let plan = build_merge_plan("ffmpeg", &job, &probe, &[]).expect("plan should build");
+
+        assert_eq!(plan.streams[2].codec.as_deref(), Some("mov_text"));
+        assert!(plan.args.windows(2).any(|w| w == ["-c:s:0", "mov_text"]));
+        assert_eq!(plan.compatibility_issues.len(), 1);
+        assert_eq!(plan.compatibility_issues[0].output_index, 1);
+
+        job.transcode_incompatible_audio = true;
+        let plan = build_merge_plan("ffmpeg", &job, &probe, &[]).expect("plan should build");
+
+        assert!(plan.compatibility_issues.is_empty());
+        assert_eq!(plan.streams[1].codec.as_deref(), Some("aac"));
+        assert!(plan.args.windows(2).any(|w| w == ["-c:a:0", "aac"]));
+    }
+
+    #[test]
+    fn build_merge_plan_reencodes_audio_tracks_on_request() {
+        let job = merge_job(
+            json!([{
+                "inputPath": "/tmp/audio.flac",
+                "config": {"audioEncode": {"codec": "ac3", "bitrate": "640k", "channelLayout": "5.1"}}
+            }]),
+            json!([
+                {"originalIndex": 0},
+                {"originalIndex": 1},
+                {"originalIndex": 2, "config": {"audioEncode": {"codec": "aac"}}}
+            ]),
+        );
+        let attached = vec![json!({
+            "streams": [{"index": 0, "codec_type": "audio", "codec_name": "flac"}]
+        })];
+
+        let plan = build_merge_plan("ffmpeg", &job, &source_probe(), &attached)
+            .expect("plan should build");
+
+        assert_eq!(plan.conversions.len(), 1);
+        assert_eq!(plan.conversions[0].output_index, 3);
+        assert_eq!(plan.conversions[0].specifier, "a:1");
+        assert_eq!(plan.conversions[0].from_codec, "flac");
+        assert_eq!(plan.streams[3].codec.as_deref(), Some("ac3"));
+        assert!(plan.args.windows(2).any(|w| w == ["-c:a:1", "ac3"]));
+        assert!(plan.args.windows(2).any(|w| w == ["-b:a:1", "640k"]));
+        assert!(plan.args.windows(2).any(|w| w == ["-ch_layout:a:1", "5.1"]));
+        assert!(
+            plan.warnings
+                .iter()
+                .any(|w| w.contains("Stream 2: audio re-encode is ignored for a subtitle track"))
+        );
+    }
+
+    #[test]
+    fn build_merge_plan_follows_explicit_output_order() {
+        let mut job = merge_job(
+            json!([
+                {"inputPath": "/tmp/eng.m4a", "config": {"language": "eng", "default": true}},
+                {"inputPath": "/tmp/sub.srt"}
+            ]),
+            Value::Null,
+        );
+        job.output_order = serde_json::from_value(json!([
+            {"source": 0},
+            {"attached": 0},
+            {"source": 1}
+        ]))
+        .expect("output order should deserialize");
+        let attached = vec![
+            json!({"streams": [{"index": 0, "codec_type": "audio", "codec_name": "aac"}]}),
+            json!({"streams": [{"index": 0, "codec_type": "subtitle", "codec_name": "subrip"}]}),
+        ];
+
+        let plan = build_merge_plan("ffmpeg", &job, &source_probe(), &attached)
+            .expect("plan should build");
+
+        let maps: Vec<&str> = plan
+            .args
+            .windows(2)
+            .filter(|w| w[0] == "-map")
+            .map(|w| w[1].as_str())
+            .collect();
+        assert_eq!(maps, vec!["0:0", "1:0", "0:1", "0:2", "2:0"]);
+        let inputs: Vec<&str> = plan.streams.iter().map(|s| s.input_path.as_str()).collect();
+        assert_eq!(
+            inputs,
+            vec![
+                "/tmp/video.mkv",
+                "/tmp/eng.m4a",
+                "/tmp/video.mkv",
+                "/tmp/video.mkv",
+                "/tmp/sub.srt"
+            ]
+        );
+        assert_eq!(plan.streams[1].index, 1);
+        assert!(
+            plan.args
+                .windows(2)
+                .any(|w| w == ["-metadata:s:1", "language=eng"])
+        );
+        assert!(
+            plan.args
+                .windows(2)
+                .any(|w| w == ["-disposition:1", "default"])
+        );
+
+        job.output_order = serde_json::from_value(json!([{"source": 7}]))
+            .expect("output order should deserialize");
+        assert!(build_merge_plan("ffmpeg", &job, &source_probe(), &attached).is_err());
+    }
+
+    #[test]
+    fn build_merge_plan_clips_inputs_chapters_and_duration() {
+        let mut job = merge_job(
+            json!([{"inputPath": "/tmp/sub.srt", "config": {"delayMs": 2500}}]),
+            Value::Null,
+        );
+        job.chapters = serde_json::from_value(json!({
+            "source": "list",
+            "chapters": [{"startMs": 0, "title": "Intro"}, {"startMs": 4000, "title": "Main"}]
+        }))
+        .expect("chapters should deserialize");
+        job.clip =
+            serde_json::from_value(json!({"startMs": 2000, "endMs": 6000, "mode": "accurate"}))
+                .expect("clip should deserialize");
+        let attached = vec![json!({
+            "streams": [{"index": 0, "codec_type": "subtitle", "codec_name": "subrip"}]
+        })];
+
+        let plan = build_merge_plan("ffmpeg", &job, &source_probe(), &attached)
+            .expect("plan should build");
+
+        assert_eq!(
+            plan.args[..5],
+            ["-y", "-ss", "2.000", "-i", "/tmp/video.mkv"]
+        );
+        // The subtitle starts 2.5 s into the source, so 0.5 s into the clip
+        assert!(
+            plan.args
+                .windows(4)
+                .any(|w| w == ["-itsoffset", "0.500", "-i", "/tmp/sub.srt"])
+        );
+        assert!(plan.args.windows(2).any(|w| w == ["-c:v", "libx264"]));
+        assert!(plan.args.windows(2).any(|w| w == ["-t", "4.000"]));
+        assert_eq!(plan.expected_duration_secs, Some(4.0));
+        let chapters: Vec<(u64, Option<u64>)> = plan
+            .chapters
+            .expect("chapters should be planned")
+            .iter()
+            .map(|c| (c.start_ms, c.end_ms))
+            .collect();
+        assert_eq!(chapters, vec![(0, Some(2_000)), (2_000, Some(4_000))]);
+        assert_eq!(plan.clip.map(|c| c.start_ms), Some(2_000));
+    }
+
+    #[test]
+    fn expected_output_duration_accounts_for_track_delays() {
+        let job = merge_job(
+            json!([
+                {"inputPath": "/tmp/sub.srt", "config": {"delayMs": 2500}},
+                {"inputPath": "/tmp/audio.mka", "config": {"delayMs": -1000}}
+            ]),
+            json!([
+                {"originalIndex": 0},
+                {"originalIndex": 1, "config": {"delayMs": 500}}
+            ]),
+        );
+        let attached = vec![
+            json!({"format": {"duration": "9.000000"}}),
+            json!({"format": {"duration": "12.000000"}}),
+        ];
+
+        let duration = expected_output_duration(&job, &source_probe(), &attached);
+
+        assert_eq!(duration, Some(11.5));
+    }
+
+    #[test]
+    fn build_merge_plan_reads_retimed_subtitles_from_temp_copies() {
+        let job = merge_job(
+            json!([{"inputPath": "/tmp/pal.srt", "config": {"retime": {"factor": 1.5}}}]),
+            Value::Null,
+        );
+        let attached = vec![json!({
+            "streams": [{"index": 0, "codec_type": "subtitle", "codec_name": "subrip"}],
+            "format": {"duration": "12.000000"}
+        })];
+
+        let plan = build_merge_plan("ffmpeg", &job, &source_probe(), &attached)
+            .expect("plan should build");
+
+        assert_eq!(plan.retimed_subtitles.len(), 1);
+        let retimed_path = &plan.retimed_subtitles[0].retimed_path;
+        assert!(retimed_path.ends_with(".srt"));
+        assert!(
+            plan.args
+                .windows(2)
+                .any(|w| w[0] == "-i" && w[1] == *retimed_path)
+        );
+        assert!(!plan.args.iter().any(|arg| arg == "/tmp/pal.srt"));
+        assert_eq!(plan.streams[3].input_path, "/tmp/pal.srt");
+        assert_eq!(plan.expected_duration_secs, Some(18.0));
+    }
+
+    #[test]
+    fn expected_output_duration_is_none_without_format_durations() {
+        let job = merge_job(json!([]), Value::Null);
+        let probe = json!({"streams": source_streams()});
+
+        assert_eq!(expected_output_duration(&job, &probe, &[]), None);
+    }
+
+    #[test]
+    fn shell_quote_wraps_unsafe_arguments() {
+        assert_eq!(shell_quote("-map"), "-map");
+        assert_eq!(shell_quote("title=My Show"), "'title=My Show'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+
+    #[tokio::test]
+    async fn plan_merge_probes_sample_video_without_writing_output() {
+        let video = crate::test_support::assets::ensure_sample_video()
+            .await
+            .expect("failed to load local sample video");
+        let temp = tempfile::tempdir().expect("failed to create tempdir");
+        let output = temp.path().join("planned.mkv");
+
+        let job: MergeJob = serde_json::from_value(json!({
+            "videoPath": video.to_string_lossy(),
+            "outputPath": output.to_string_lossy()
+        }))
+        .expect("merge job should deserialize");
+
+        let plan = plan_merge_with_bins("ffprobe", "ffmpeg", &job)
+            .await
+            .expect("plan should succeed");
+
+        assert!(!plan.streams.is_empty());
+        assert!(plan.streams.iter().all(|s| s.codec.is_some()));
+        assert!(plan.expected_duration_secs.is_some());
+        assert!(!output.exists());
+    }
+}
@@ -0,0 +1,140 @@
+use crate::shared::clip::ClipRange;
+use crate::shared::output::OverwritePolicy;
+use crate::tools::merge::job::{MergeJob, MergeReplacement, SourceChapterMode, TrackDropRule};
+use crate::tools::merge::merge::run_merge_job;
+use crate::tools::merge::verify::MergeVerificationReport;
+use serde::Deserialize;
+
+/// Streams to replace or drop in a video, every other source stream is kept in order
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub(crate) struct RemuxRequest {
+    pub(crate) video_path: String,
+    pub(crate) output_path: String,
+    #[serde(default)]
+    pub(crate) replacements: Vec<MergeReplacement>,
+    #[serde(default)]
+    pub(crate) drop_rules: Vec<TrackDropRule>,
+    #[serde(default)]
+    pub(crate) overwrite_policy: OverwritePolicy,
+    #[serde(default)]
+    pub(crate) duration_us: Option<u64>,
+    #[serde(default)]
+    pub(crate) clip: Option<ClipRange>,
+}
+
+/// Job that keeps every source stream in order, except the replaced and dropped ones
+fn remux_job(request: RemuxRequest) -> Result<MergeJob, String> {
+    if request.replacements.is_empty() && request.drop_rules.is_empty() && request.clip.is_none() {
+        return Err("Nothing to remux: add a replacement, a drop rule or a clip".to_string());
+    }
+
+    Ok(MergeJob {
+        video_path: request.video_path,
+        tracks: Vec::new(),
+        source_track_configs: None,
+        output_path: request.output_path,
+        duration_us: request.duration_us,
+        overwrite_policy: request.overwrite_policy,
+        attachments: Vec::new(),
+        source_attachments: None,
+        chapters: None,
+        source_chapters: SourceChapterMode::Keep,
+        chapter_shift_ms: None,
+        transcode_incompatible_audio: false,
+        replacements: request.replacements,
+        drop_rules: request.drop_rules,
+        output_order: None,
+        clip: request.clip,
+    })
+}
+
+/// Remux a video, replacing streams at their position and dropping tracks by rule
+/// A clip keeps only its time range; the report carries the cut points actually used
+#[tauri::command]
+pub(crate) async fn remux_tracks(
+    app: tauri::AppHandle,
+    request: RemuxRequest,
+) -> Result<MergeVerificationReport, String> {
+    let job = remux_job(request)?;
+    run_merge_job(&app, job, None, None).await
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{RemuxRequest, remux_job};
+    use crate::tools::merge::plan::build_merge_plan;
+
+    fn request(fields: serde_json::Value) -> RemuxRequest {
+        let mut value = json!({"videoPath": "/tmp/video.mkv", "outputPath": "/tmp/out.mkv"});
+        if let (Some(target), Some(fields)) = (value.as_object_mut(), fields.as_object()) {
+            target.extend(fields.clone());
+        }
+        serde_json::from_value(value).expect("remux request should deserialize")
+    }
+
+    fn source_probe() -> serde_json::Value {
+        json!({
+            "streams": [
+                {"index": 0, "codec_type": "video", "codec_name": "h264"},
+                {"index": 1, "codec_type": "audio", "codec_name": "aac", "tags": {"title": "Main"}},
+                {"index": 2, "codec_type": "audio", "codec_name": "aac", "tags": {"title": "Commentary"}},
+                {"index": 3, "codec_type": "subtitle", "codec_name": "ass", "tags": {"language": "eng", "title": "Full"}},
+                {"index": 4, "codec_type": "subtitle", "codec_name": "ass", "tags": {"title": "Signs"}}
+            ],
+            "format": {"duration": "10.000000"}
+        })
+    }
+
+    #[test]
+    fn remux_job_replaces_in_place_and_drops_by_rule() {
+        let job = remux_job(request(json!({
+            "replacements": [{"originalIndex": 3, "inputPath": "/tmp/fixed.ass"}],
+            "dropRules": [{"titleContains": "commentary"}, {"titleContains": "signs"}]
+        })))
+        .expect("remux job should build");
+        let replacement_probe = json!({
+            "streams": [{"index": 0, "codec_type": "subtitle", "codec_name": "ass"}]
+        });
+
+        let plan = build_merge_plan("ffmpeg", &job, &source_probe(), &[replacement_probe])
+            .expect("plan should build");
+
+        let maps: Vec<&str> = plan
+            .args
+            .windows(2)
+            .filter(|w| w[0] == "-map")
+            .map(|w| w[1].as_str())
+            .collect();
+        assert_eq!(maps, vec!["0:0", "0:1", "1:0"]);
+        assert!(
+            plan.args
+                .windows(2)
+                .any(|w| w == ["-map_metadata:s:2", "0:s:3"])
+        );
+        assert_eq!(plan.streams.len(), 3);
+        assert_eq!(plan.streams[2].input_path, "/tmp/fixed.ass");
+        assert_eq!(plan.streams[2].language.as_deref(), Some("eng"));
+        assert_eq!(plan.streams[2].title.as_deref(), Some("Full"));
+    }
+
+    #[test]
+    fn remux_job_requires_a_replacement_rule_or_clip() {
+        assert!(remux_job(request(json!({}))).is_err());
+
+        let job = remux_job(request(
+            json!({"clip": {"startMs": 60_000, "endMs": 90_000}}),
+        ))
+        .expect("a clip alone is enough to remux");
+        let plan =
+            build_merge_plan("ffmpeg", &job, &source_probe(), &[]).expect("plan should build");
+
+        assert_eq!(
+            plan.args[..5],
+            ["-y", "-ss", "60.000", "-i", "/tmp/video.mkv"]
+        );
+        assert!(plan.args.windows(2).any(|w| w == ["-c:v", "copy"]));
+    }
+}
@@ -0,0 +1,357 @@
+use crate::tools::merge::job::MergeJob;
+use serde::Serialize;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+static RETIME_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Text subtitle formats whose timings can be rewritten before muxing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SubtitleFormat {
+    Srt,
+    Ass,
+    Vtt,
+}
+
+impl SubtitleFormat {
+    pub(crate) fn from_path(path: &str) -> Option<Self> {
+        let ext = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+        match ext.as_str() {
+            "srt" => Some(Self::Srt),
+            "ass" | "ssa" => Some(Self::Ass),
+            "vtt" => Some(Self::Vtt),
+            _ => None,
+        }
+    }
+}
+
+/// Attached subtitle muxed from a retimed temp copy
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct RetimedSubtitle {
+    /// Index into the job's attached tracks
+    pub(crate) track: usize,
+    pub(crate) source_path: String,
+    pub(crate) retimed_path: String,
+    pub(crate) factor: f64,
+}
+
+fn scale_ms(ms: u64, factor: f64) -> u64 {
+    (ms as f64 * factor).round().max(0.0) as u64
+}
+
+/// "[H:]MM:SS" plus a fraction after `separator`, returned in milliseconds
+fn parse_clock(value: &str, separator: char) -> Option<u64> {
+    let (clock, fraction) = value.trim().split_once(separator)?;
+    if fraction.is_empty() || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    let parts: Vec<&str> = clock.split(':').collect();
+    let (hours, minutes, seconds) = match parts.as_slice() {
+        [h, m, s] => (
+            h.parse::<u64>().ok()?,
+            m.parse::<u64>().ok()?,
+            s.parse::<u64>().ok()?,
+        ),
+        [m, s] => (0, m.parse::<u64>().ok()?, s.parse::<u64>().ok()?),
+        _ => return None,
+    };
+    if minutes >= 60 || seconds >= 60 {
+        return None;
+    }
+
+    let millis = format!("{:0<3}", &fraction[..fraction.len().min(3)])
+        .parse::<u64>()
+        .ok()?;
+    Some(((hours * 60 + minutes) * 60 + seconds) * 1000 + millis)
+}
+
+fn format_cue_time(ms: u64, separator: char) -> String {
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        ms / 3_600_000,
+        (ms % 3_600_000) / 60_000,
+        (ms % 60_000) / 1000,
+        separator,
+        ms % 1000
+    )
+}
+
+/// ASS times use a single-digit hour and centiseconds
+fn format_ass_time(ms: u64) -> String {
+    let centis = (ms + 5) / 10;
+    format!(
+        "{}:{:02}:{:02}.{:02}",
+        centis / 360_000,
+        (centis % 360_000) / 6_000,
+        (centis % 6_000) / 100,
+        centis % 100
+    )
+}
+
+/// Rewrite an SRT or VTT "start --> end [settings]" line
+fn retime_cue_line(line: &str, separator: char, factor: f64) -> Option<String> {
+    let (start, rest) = line.split_once("-->")?;
+    let rest = rest.trim_start();
+    let (end, settings) = match rest.find(char::is_whitespace) {
+        Some(pos) => rest.split_at(pos),
+        None => (rest, ""),
+    };
+
+    let start_ms = parse_clock(start, separator)?;
+    let end_ms = parse_clock(end, separator)?;
+    Some(format!(
+        "{} --> {}{}",
+        format_cue_time(scale_ms(start_ms, factor), separator),
+        format_cue_time(scale_ms(end_ms, factor), separator),
+        settings
+    ))
+}
+
+/// Positions of Start and End in the [Events] Format line
+fn ass_time_fields(format_line: &str) -> Option<(usize, usize)> {
+    let fields: Vec<String> = format_line
+        .split_once(':')?
+        .1
+        .split(',')
+        .map(|field| field.trim().to_lowercase())
+        .collect();
+    let start = fields.iter().position(|f| f == "start")?;
+    let end = fields.iter().position(|f| f == "end")?;
+    Some((start, end))
+}
+
+/// Rewrite the Start and End fields of a Dialogue or Comment line, leaving the text bytes untouched
+fn retime_ass_event(line: &[u8], fields: (usize, usize), factor: f64) -> Option<Vec<u8>> {
+    let colon = line.iter().position(|b| *b == b':')?;
+    let (prefix, body) = line.split_at(colon + 1);
+    let last_field = fields.0.max(fields.1);
+    let mut parts: Vec<&[u8]> = body.splitn(last_field + 2, |b| *b == b',').collect();
+    if parts.len() <= last_field {
+        return None;
+    }
+
+    let mut retimed = Vec::with_capacity(2);
+    for index in [fields.0, fields.1] {
+        let value = std::str::from_utf8(parts[index]).ok()?;
+        let ms = parse_clock(value, '.')?;
+        retimed.push((index, format_ass_time(scale_ms(ms, factor))));
+    }
+    for (index, value) in &retimed {
+        parts[*index] = value.as_bytes();
+    }
+
+    let mut output = prefix.to_vec();
+    for (i, part) in parts.iter().enumerate() {
+        if i > 0 {
+            output.push(b',');
+        }
+        output.extend_from_slice(part);
+    }
+    Some(output)
+}
+
+/// Scale every cue timing by `factor`, keeping all other bytes as they are
+pub(crate) fn retime_subtitle(
+    content: &[u8],
+    format: SubtitleFormat,
+    factor: f64,
+) -> Result<Vec<u8>, String> {
+    let mut output = Vec::with_capacity(content.len());
+    let mut retimed_cues = 0usize;
+    let mut in_events = false;
+    let mut ass_fields = (1, 2);
+
+    for (i, raw_line) in content.split(|b| *b == b'\n').enumerate() {
+        if i > 0 {
+            output.push(b'\n');
+        }
+        let (line, line_ending) = match raw_line.strip_suffix(b"\r") {
+            Some(line) => (line, &b"\r"[..]),
+            None => (raw_line, &b""[..]),
+        };
+
+        let retimed = match format {
+            SubtitleFormat::Srt | SubtitleFormat::Vtt => {
+                let separator = if format == SubtitleFormat::Srt {
+                    ','
+                } else {
+                    '.'
+                };
+                std::str::from_utf8(line)
+                    .ok()
+                    .filter(|text| text.contains("-->"))
+                    .and_then(|text| retime_cue_line(text, separator, factor))
+                    .map(String::into_bytes)
+            }
+            SubtitleFormat::Ass => {
+                let text = String::from_utf8_lossy(line);
+                let trimmed = text.trim_start();
+                if trimmed.starts_with('[') {
+                    in_events = trimmed.trim_end().eq_ignore_ascii_case("[events]");
+                    None
+                } else if in_events && trimmed.starts_with("Format:") {
+                    ass_fields = ass_time_fields(trimmed).unwrap_or((1, 2));
+                    None
+                } else if in_events
+                    && (trimmed.starts_with("Dialogue:") || trimmed.starts_with("Comment:"))
+                {
+                    retime_ass_event(line, ass_fields, factor)
+                } else {
+                    None
+                }
+            }
+        };
+
+        match retimed {
+            Some(retimed) => {
+                retimed_cues += 1;
+                output.extend_from_slice(&retimed);
+            }
+            None => output.extend_from_slice(line),
+        }
+        output.extend_from_slice(line_ending);
+    }
+
+    if retimed_cues == 0 {
+        return Err("No subtitle timings found to retime".to_string());
+    }
+    Ok(output)
+}
+
+fn retimed_subtitle_path(source_path: &str) -> String {
+    let ext = Path::new(source_path)
+        .extension()
+        .map(|e| e.to_string_lossy().to_string())
+        .unwrap_or_default();
+    std::env::temp_dir()
+        .join(format!(
+            "mediaflow-retime-{}-{}.{}",
+            std::process::id(),
+            RETIME_FILE_COUNTER.fetch_add(1, Ordering::Relaxed),
+            ext
+        ))
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Attached tracks that need a retimed copy, each with a fresh temp path
+pub(crate) fn plan_retimed_subtitles(job: &MergeJob) -> Vec<RetimedSubtitle> {
+    job.tracks
+        .iter()
+        .enumerate()
+        .filter_map(|(track, attached)| {
+            let factor = attached.config.as_ref()?.retime.as_ref()?.factor()?;
+            Some(RetimedSubtitle {
+                track,
+                source_path: attached.input_path.clone(),
+                retimed_path: retimed_subtitle_path(&attached.input_path),
+                factor,
+            })
+        })
+        .collect()
+}
+
+/// Retimed subtitle copies that live as long as the merge using them
+pub(super) struct RetimedSubtitleFiles {
+    paths: Vec<String>,
+}
+
+impl RetimedSubtitleFiles {
+    pub(super) fn write(subtitles: &[RetimedSubtitle]) -> Result<Self, String> {
+        let mut files = Self { paths: Vec::new() };
+        for subtitle in subtitles {
+            let format = SubtitleFormat::from_path(&subtitle.source_path).ok_or_else(|| {
+                format!("Cannot retime subtitle format: {}", subtitle.source_path)
+            })?;
+            let content = std::fs::read(&subtitle.source_path)
+                .map_err(|e| format!("Failed to read subtitle {}: {}", subtitle.source_path, e))?;
+            let retimed = retime_subtitle(&content, format, subtitle.factor)
+                .map_err(|e| format!("{}: {}", subtitle.source_path, e))?;
+
+            std::fs::write(&subtitle.retimed_path, retimed)
+                .map_err(|e| format!("Failed to write retimed subtitle: {}", e))?;
+            files.paths.push(subtitle.retimed_path.clone());
+        }
+        Ok(files)
+    }
+}
+
+impl Drop for RetimedSubtitleFiles {
+    fn drop(&mut self) {
+        for path in &self.paths {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SubtitleFormat, format_ass_time, parse_clock, retime_subtitle};
+
+    const PAL_TO_FILM: f64 = 25.0 / 23.976;
+
+    #[test]
+    fn parse_clock_accepts_srt_vtt_and_ass_times() {
+        assert_eq!(parse_clock("00:01:02,500", ','), Some(62_500));
+        assert_eq!(parse_clock("01:02.250", '.'), Some(62_250));
+        assert_eq!(parse_clock("0:00:05.20", '.'), Some(5_200));
+        assert_eq!(parse_clock("00:61:00,000", ','), None);
+        assert_eq!(format_ass_time(5_204), "0:00:05.20");
+    }
+
+    #[test]
+    fn retime_subtitle_scales_srt_cues_and_keeps_text() {
+        let srt = "1\r\n00:00:10,000 --> 00:00:12,000\r\nCafé\r\n\r\n2\r\n00:10:00,000 --> 00:10:01,000\r\nBye\r\n";
+
+        let retimed = retime_subtitle(srt.as_bytes(), SubtitleFormat::Srt, PAL_TO_FILM)
+            .expect("srt should retime");
+        let retimed = String::from_utf8(retimed).expect("output should stay utf-8");
+
+        assert!(retimed.contains("00:00:10,427 --> 00:00:12,513\r\nCafé\r\n"));
+        assert!(retimed.contains("00:10:25,626 --> 00:10:26,668"));
+    }
+
+    #[test]
+    fn retime_subtitle_keeps_vtt_cue_settings() {
+        let vtt = "WEBVTT\n\n00:01.000 --> 00:02.000 align:start line:0\nHi\n";
+
+        let retimed =
+            retime_subtitle(vtt.as_bytes(), SubtitleFormat::Vtt, 2.0).expect("vtt should retime");
+
+        assert_eq!(
+            String::from_utf8(retimed).expect("utf-8"),
+            "WEBVTT\n\n00:00:02.000 --> 00:00:04.000 align:start line:0\nHi\n"
+        );
+    }
+
+    #[test]
+    fn retime_subtitle_rewrites_ass_events_only() {
+        let mut ass = b"[V4+ Styles]\nFormat: Name, Fontname\nStyle: Default,Arial\n\n[Events]\nFormat: Layer, Start, End, Style, Text\nDialogue: 0,0:00:01.00,0:00:02.50,Default,".to_vec();
+        ass.extend_from_slice(&[0xe9, b',', b'x']);
+        ass.push(b'\n');
+
+        let retimed = retime_subtitle(&ass, SubtitleFormat::Ass, 2.0).expect("ass should retime");
+
+        let expected_prefix = b"Dialogue: 0,0:00:02.00,0:00:05.00,Default,";
+        let dialogue_start = retimed
+            .windows(expected_prefix.len())
+            .position(|w| w == expected_prefix)
+            .expect("dialogue should be retimed");
+        assert_eq!(
+            &retimed[dialogue_start + expected_prefix.len()..],
+            &[0xe9, b',', b'x', b'\n']
+        );
+        assert!(retimed.starts_with(b"[V4+ Styles]\nFormat: Name, Fontname\n"));
+    }
+
+    #[test]
+    fn retime_subtitle_fails_without_timings() {
+        assert!(retime_subtitle(b"hello", SubtitleFormat::Srt, 1.5).is_err());
+    }
+}
@@ -0,0 +1,363 @@
+use crate::shared::clip::ClipCut;
+use crate::shared::language::same_language;
+use crate::tools::ffprobe::probe::probe_container_json;
+use crate::tools::merge::merge::{probe_duration_secs, probe_streams};
+use crate::tools::merge::plan::{MergePlan, stream_disposition, stream_str, stream_tag};
+use serde::Serialize;
+use serde_json::Value;
+
+/// Smallest duration drift tolerated between the plan and the output, in seconds
+const MIN_DURATION_TOLERANCE_SECS: f64 = 1.0;
+
+/// Relative duration drift tolerated for long outputs
+const DURATION_TOLERANCE_RATIO: f64 = 0.01;
+
+/// One expectation checked against the merged file
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeVerificationCheck {
+    pub(crate) name: String,
+    pub(crate) expected: String,
+    pub(crate) actual: String,
+    pub(crate) passed: bool,
+}
+
+/// Result of probing a merged file and comparing it with its plan
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct MergeVerificationReport {
+    pub(crate) output_path: String,
+    pub(crate) passed: bool,
+    pub(crate) checks: Vec<MergeVerificationCheck>,
+    pub(crate) expected_duration_secs: Option<f64>,
+    pub(crate) actual_duration_secs: Option<f64>,
+    pub(crate) clip: Option<ClipCut>,
+}
+
+impl MergeVerificationReport {
+    /// Turn a failed report into an error listing every failed check
+    pub(crate) fn into_result(self) -> Result<Self, String> {
+        if self.passed {
+            return Ok(self);
+        }
+
+        let failures = self
+            .checks
+            .iter()
+            .filter(|check| !check.passed)
+            .map(|check| {
+                format!(
+                    "{}: expected {}, got {}",
+                    check.name, check.expected, check.actual
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        Err(format!("Merge verification failed: {}", failures))
+    }
+}
+
+fn push_check(
+    checks: &mut Vec<MergeVerificationCheck>,
+    name: String,
+    expected: impl ToString,
+    actual: impl ToString,
+    passed: bool,
+) {
+    checks.push(MergeVerificationCheck {
+        name,
+        expected: expected.to_string(),
+        actual: actual.to_string(),
+        passed,
+    });
+}
+
+fn describe(value: Option<&str>) -> String {
+    value.unwrap_or("none").to_string()
+}
+
+/// Compare an ffprobe result of the merged file with the plan it was built from
+pub(super) fn build_verification_report(
+    plan: &MergePlan,
+    output_path: &str,
+    output_probe: &Value,
+) -> MergeVerificationReport {
+    let mut checks = Vec::new();
+    let actual_streams = probe_streams(output_probe);
+
+    let expected_count = plan.streams.len() + plan.attachments.len();
+    push_check(
+        &mut checks,
+        "stream count".to_string(),
+        expected_count,
+        actual_streams.len(),
+        expected_count == actual_streams.len(),
+    );
+
+    for planned in &plan.streams {
+        let actual = actual_streams.get(planned.index);
+        let prefix = format!("stream {}", planned.index);
+
+        if let Some(codec) = planned.codec.as_deref() {
+            let actual_codec = stream_str(actual, "codec_name");
+            push_check(
+                &mut checks,
+                format!("{} codec", prefix),
+                codec,
+                describe(actual_codec.as_deref()),
+                actual_codec.as_deref() == Some(codec),
+            );
+        }
+
+        if let Some(language) = planned.language.as_deref() {
+            let actual_language = stream_tag(actual, "language");
+            push_check(
+                &mut checks,
+                format!("{} language", prefix),
+                language,
+                describe(actual_language.as_deref()),
+                actual_language
+                    .as_deref()
+                    .is_some_and(|actual| same_language(actual, language)),
+            );
+        }
+
+        // ffmpeg marks the first stream of a type as default when none is,
+        // so only a requested default flag is enforced
+        if planned.default {
+            let actual_default = stream_disposition(actual, "default");
+            push_check(
+                &mut checks,
+                format!("{} default", prefix),
+                true,
+                actual_default,
+                actual_default,
+            );
+        }
+
+        let actual_forced = stream_disposition(actual, "forced");
+        push_check(
+            &mut checks,
+            format!("{} forced", prefix),
+            planned.forced,
+            actual_forced,
+            actual_forced == planned.forced,
+        );
+    }
+
+    if let Some(chapters) = &plan.chapters {
+        let actual_chapters = output_probe
+            .get("chapters")
+            .and_then(|c| c.as_array())
+            .map_or(0, Vec::len);
+        push_check(
+            &mut checks,
+            "chapter count".to_string(),
+            chapters.len(),
+            actual_chapters,
+            chapters.len() == actual_chapters,
+        );
+    }
+
+    let actual_duration_secs = probe_duration_secs(output_probe);
+    if let Some(expected) = plan.expected_duration_secs {
+        let tolerance = (expected * DURATION_TOLERANCE_RATIO).max(MIN_DURATION_TOLERANCE_SECS);
+        let passed =
+            actual_duration_secs.is_some_and(|actual| (actual - expected).abs() <= tolerance);
+        push_check(
+            &mut checks,
+            "duration".to_string(),
+            format!("{:.3}s (±{:.3}s)", expected, tolerance),
+            actual_duration_secs.map_or("none".to_string(), |actual| format!("{:.3}s", actual)),
+            passed,
+        );
+    }
+
+    MergeVerificationReport {
+        output_path: output_path.to_string(),
+        passed: checks.iter().all(|check| check.passed),
+        checks,
+        expected_duration_secs: plan.expected_duration_secs,
+        actual_duration_secs,
+        clip: plan.clip.clone(),
+    }
+}
+
+/// Probe the merged file and check it against the plan
+pub(super) async fn verify_merge_output(
+    ffprobe_path: &str,
+    plan: &MergePlan,
+    output_path: &str,
+) -> Result<MergeVerificationReport, String> {
+    // The output was just written, so it is always probed afresh; the report only compares
+    // streams, so frame-level HDR metadata is not probed
+    let output_probe = probe_container_json(ffprobe_path, output_path).await?;
+    Ok(build_verification_report(plan, output_path, &output_probe))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{Value, json};
+
+    use super::build_verification_report;
+    use crate::tools::merge::job::MergeJob;
+    use crate::tools::merge::plan::{MergePlan, MergePlanStream, build_merge_plan};
+
+    fn planned_stream(index: usize, codec_type: &str, codec: &str) -> MergePlanStream {
+        MergePlanStream {
+            index,
+            input_index: 0,
+            input_path: "/tmp/video.mkv".to_string(),
+            input_stream_index: index,
+            codec_type: Some(codec_type.to_string()),
+            codec: Some(codec.to_string()),
+            language: None,
+            title: None,
+            default: false,
+            forced: false,
+            audio_encode: None,
+        }
+    }
+
+    fn plan(streams: Vec<MergePlanStream>, expected_duration_secs: Option<f64>) -> MergePlan {
+        MergePlan {
+            program: "ffmpeg".to_string(),
+            args: Vec::new(),
+            command_line: String::new(),
+            streams,
+            attachments: Vec::new(),
+            chapters: None,
+            chapters_metadata_path: None,
+            conversions: Vec::new(),
+            compatibility_issues: Vec::new(),
+            retimed_subtitles: Vec::new(),
+            warnings: Vec::new(),
+            expected_duration_secs,
+            clip: None,
+        }
+    }
+
+    fn output_probe(streams: Value, duration: &str) -> Value {
+        json!({"streams": streams, "format": {"duration": duration}})
+    }
+
+    #[test]
+    fn verification_passes_when_output_matches_plan() {
+        let mut audio = planned_stream(1, "audio", "aac");
+        audio.language = Some("jpn".to_string());
+        audio.default = true;
+        let plan = plan(vec![planned_stream(0, "video", "h264"), audio], Some(60.0));
+        let probe = output_probe(
+            json!([
+                {"codec_name": "h264", "disposition": {"default": 1, "forced": 0}},
+                {
+                    "codec_name": "aac",
+                    "tags": {"language": "jpn"},
+                    "disposition": {"default": 1, "forced": 0}
+                }
+            ]),
+            "60.040000",
+        );
+
+        let report = build_verification_report(&plan, "/tmp/out.mkv", &probe);
+
+        assert!(report.passed);
+        assert_eq!(report.actual_duration_secs, Some(60.04));
+        assert!(report.into_result().is_ok());
+    }
+
+    #[test]
+    fn verification_accepts_attached_track_language_in_another_form() {
+        let job: MergeJob = serde_json::from_value(json!({
+            "videoPath": "/tmp/video.mkv",
+            "outputPath": "/tmp/out.mkv",
+            "tracks": [{"inputPath": "/tmp/sub.srt", "config": {"language": "en"}}]
+        }))
+        .expect("merge job should deserialize");
+        let source = json!({
+            "streams": [{"index": 0, "codec_type": "video", "codec_name": "h264"}],
+            "format": {"duration": "10.000000"}
+        });
+        let attached = vec![json!({
+            "streams": [{"index": 0, "codec_type": "subtitle", "codec_name": "subrip"}]
+        })];
+        let plan = build_merge_plan("ffmpeg", &job, &source, &attached).expect("plan should build");
+        assert_eq!(plan.streams[1].language.as_deref(), Some("eng"));
+
+        // ffmpeg writes the normalised code, and containers may report either 639-2 form
+        for written in ["eng", "en"] {
+            let probe = output_probe(
+                json!([
+                    {"codec_name": "h264"},
+                    {"codec_name": "subrip", "tags": {"language": written}}
+                ]),
+                "10.0",
+            );
+            let report = build_verification_report(&plan, "/tmp/out.mkv", &probe);
+            assert!(report.passed, "{:?}", report.checks);
+        }
+    }
+
+    #[test]
+    fn verification_reports_missing_stream_and_wrong_language() {
+        let mut subtitle = planned_stream(1, "subtitle", "subrip");
+        subtitle.language = Some("eng".to_string());
+        subtitle.forced = true;
+        let plan = plan(
+            vec![planned_stream(0, "video", "h264"), subtitle.clone(), {
+                let mut extra = subtitle;
+                extra.index = 2;
+                extra
+            }],
+            None,
+        );
+        let probe = output_probe(
+            json!([
+                {"codec_name": "h264"},
+                {"codec_name": "subrip", "tags": {"language": "fre"}, "disposition": {"forced": 1}}
+            ]),
+            "10.0",
+        );
+
+        let report = build_verification_report(&plan, "/tmp/out.mkv", &probe);
+        let failed: Vec<&str> = report
+            .checks
+            .iter()
+            .filter(|c| !c.passed)
+            .map(|c| c.name.as_str())
+            .collect();
+
+        assert!(!report.passed);
+        assert!(failed.contains(&"stream count"));
+        assert!(failed.contains(&"stream 1 language"));
+        assert!(failed.contains(&"stream 2 codec"));
+        let error = report.into_result().expect_err("report should fail");
+        assert!(error.contains("stream count: expected 3, got 2"));
+    }
+
+    #[test]
+    fn verification_checks_chapter_count_only_when_planned() {
+        let mut plan = plan(vec![planned_stream(0, "video", "h264")], None);
+        let probe = json!({
+            "streams": [{"codec_name": "h264"}],
+            "chapters": [{"start_time": "0.000000"}]
+        });
+
+        assert!(build_verification_report(&plan, "/tmp/out.mkv", &probe).passed);
+
+        plan.chapters = Some(vec![]);
+        let report = build_verification_report(&plan, "/tmp/out.mkv", &probe);
+        assert!(!report.passed);
+        assert!(report.checks.iter().any(|c| c.name == "chapter count"));
+    }
+
+    #[test]
+    fn verification_fails_when_duration_drifts_beyond_tolerance() {
+        let plan = plan(vec![planned_stream(0, "video", "h264")], Some(120.0));
+        let within = output_probe(json!([{"codec_name": "h264"}]), "121.100000");
+        let beyond = output_probe(json!([{"codec_name": "h264"}]), "118.500000");
+
+        assert!(build_verification_report(&plan, "/tmp/out.mkv", &within).passed);
+        assert!(!build_verification_report(&plan, "/tmp/out.mkv", &beyond).passed);
+    }
+}
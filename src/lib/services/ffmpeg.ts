@@ -38,10 +38,12 @@ export async function extractTrack(params: ExtractTrackParams): Promise<Extracti
   try {
     await invoke('extract_track', {
       inputPath: params.inputPath,
-      outputPath: params.outputPath,
-      trackIndex: params.trackIndex,
-      trackType: params.trackType,
-      codec: params.codec,
+      track: {
+        trackIndex: params.trackIndex,
+        trackType: params.trackType,
+        codec: params.codec,
+        outputPath: params.outputPath,
+      },
       durationUs: params.durationUs,
     });
 
@@ -1,5 +1,6 @@
 import { invoke } from '@tauri-apps/api/core';
-import type { VideoFile, Track, FFprobeOutput, FFprobeStream } from '$lib/types';
+import { MEDIA_INFO_SCHEMA_VERSION } from '$lib/types';
+import type { VideoFile, Track, MediaInfo, MediaStream, BitrateAnalysis } from '$lib/types';
 import { getFileName } from '$lib/utils/format';
 import { log } from '$lib/utils/log-toast';
 
@@ -9,10 +10,10 @@ import { log } from '$lib/utils/log-toast';
 const DEFAULT_SCAN_CONCURRENCY = 3;
 
 /**
- * Parse FFprobe output and convert to our Track format
+ * Convert a MediaInfo stream to our Track format
  */
-function parseStream(stream: FFprobeStream): Track | null {
-  const type = stream.codec_type as Track['type'];
+function parseStream(stream: MediaStream): Track | null {
+  const type = stream.kind as Track['type'];
 
   // Skip unsupported types
   if (!['video', 'audio', 'subtitle', 'data'].includes(type)) {
@@ -23,17 +24,15 @@ function parseStream(stream: FFprobeStream): Track | null {
     id: stream.index,
     index: stream.index,
     type,
-    codec: stream.codec_name,
-    codecLong: stream.codec_long_name,
-    language: stream.tags?.language,
-    title: stream.tags?.title,
-    bitrate: stream.bit_rate ? parseInt(stream.bit_rate) : 
-             stream.tags?.BPS ? parseInt(stream.tags.BPS) :
-             stream.tags?.["BPS-eng"] ? parseInt(stream.tags["BPS-eng"]) : undefined,
-    size: stream.tags?.NUMBER_OF_BYTES ? parseInt(stream.tags.NUMBER_OF_BYTES) : undefined,
-    numberOfFrames: stream.tags?.NUMBER_OF_FRAMES ? parseInt(stream.tags.NUMBER_OF_FRAMES) : undefined,
-    default: stream.disposition?.default === 1,
-    forced: stream.disposition?.forced === 1,
+    codec: stream.codecName ?? 'unknown',
+    codecLong: stream.codecLongName ?? undefined,
+    language: stream.language ?? undefined,
+    title: stream.title ?? undefined,
+    bitrate: stream.bitRate ?? undefined,
+    size: stream.sizeBytes ?? undefined,
+    numberOfFrames: stream.frameCount ?? undefined,
+    default: stream.disposition.default,
+    forced: stream.disposition.forced,
   };
 
   // Video specific
@@ -43,17 +42,21 @@ function parseStream(stream: FFprobeStream): Track | null {
       track.height = stream.height;
       track.resolution = `${stream.width}×${stream.height}`;
     }
-    track.frameRate = stream.r_frame_rate;
-    track.pixelFormat = stream.pix_fmt;
-    track.colorRange = stream.color_range;
-    track.colorSpace = stream.color_space;
-    track.aspectRatio = stream.display_aspect_ratio;
+    track.frameRate = stream.frameRate ?? undefined;
+    track.pixelFormat = stream.pixelFormat ?? undefined;
+    track.colorRange = stream.colorRange ?? undefined;
+    track.colorSpace = stream.colorSpace ?? undefined;
+    track.colorPrimaries = stream.colorPrimaries ?? undefined;
+    track.colorTransfer = stream.colorTransfer ?? undefined;
+    track.bitDepth = stream.bitDepth ?? undefined;
+    track.hdr = stream.hdr ?? undefined;
+    track.aspectRatio = stream.displayAspectRatio ?? undefined;
   }
 
   // Audio specific
   if (type === 'audio') {
-    track.channels = stream.channels;
-    track.sampleRate = stream.sample_rate ? parseInt(stream.sample_rate) : undefined;
+    track.channels = stream.channels ?? undefined;
+    track.sampleRate = stream.sampleRate ?? undefined;
   }
 
   return track;
@@ -67,26 +70,19 @@ export async function scanFile(filePath: string): Promise<ScannedFile> {
 
   try {
     // Call Rust command to execute ffprobe
-    const result = await invoke<string>('probe_file', { path: filePath });
-    const ffprobeOutput: FFprobeOutput = JSON.parse(result);
+    const mediaInfo = await invoke<MediaInfo>('probe_file', { path: filePath });
+    if (mediaInfo.schemaVersion !== MEDIA_INFO_SCHEMA_VERSION) {
+      throw new Error(`Unsupported media info schema version ${mediaInfo.schemaVersion}`);
+    }
 
-    const tracks: Track[] = ffprobeOutput.streams
+    const tracks: Track[] = mediaInfo.streams
       .map(parseStream)
       .filter((t): t is Track => t !== null);
 
-    const duration = ffprobeOutput.format.duration
-      ? parseFloat(ffprobeOutput.format.duration)
-      : undefined;
-
-    const size = ffprobeOutput.format.size
-      ? parseInt(ffprobeOutput.format.size)
-      : 0;
-
-    const bitrate = ffprobeOutput.format.bit_rate
-      ? parseInt(ffprobeOutput.format.bit_rate)
-      : undefined;
-
-    const format = ffprobeOutput.format.format_long_name || ffprobeOutput.format.format_name;
+    const duration = mediaInfo.format.durationSecs ?? undefined;
+    const size = mediaInfo.format.sizeBytes ?? 0;
+    const bitrate = mediaInfo.format.bitRate ?? undefined;
+    const format = mediaInfo.format.formatLongName || mediaInfo.format.formatName || undefined;
 
     return {
       path: filePath,
@@ -95,7 +91,7 @@ export async function scanFile(filePath: string): Promise<ScannedFile> {
       duration,
       tracks,
       status: 'ready',
-      rawData: ffprobeOutput,
+      rawData: mediaInfo,
       format,
       bitrate
     };
@@ -107,7 +103,7 @@ export async function scanFile(filePath: string): Promise<ScannedFile> {
       `FFprobe failed to analyze file ${name}: ${errorMessage}`,
       {
         filePath,
-        command: `ffprobe -v quiet -print_format json -show_format -show_streams "${filePath}"`
+        command: `ffprobe -v quiet -print_format json -show_streams -show_format -show_chapters "${filePath}"`
       }
     );
 
@@ -126,7 +122,7 @@ export async function scanFile(filePath: string): Promise<ScannedFile> {
  * Extended VideoFile with additional metadata from ffprobe
  */
 export type ScannedFile = VideoFile & { 
-  rawData?: any; 
+  rawData?: MediaInfo; 
   format?: string; 
   bitrate?: number;
 };
@@ -176,3 +172,35 @@ export async function scanFiles(
   return results;
 }
 
+
+/**
+ * Drop every cached probe result in the backend, forcing the next scan to re-run ffprobe
+ * @returns Number of cached files that were dropped
+ */
+export async function clearProbeCache(): Promise<number> {
+  return invoke<number>('clear_probe_cache');
+}
+
+/**
+ * Read every packet of one stream and aggregate bitrate buckets, peak and keyframes
+ * Progress is emitted as `bitrate-progress` events when durationUs is given
+ */
+export async function analyzeBitrate(
+  inputPath: string,
+  streamIndex: number,
+  options: { bucketSecs?: number; durationUs?: number } = {}
+): Promise<BitrateAnalysis> {
+  return invoke<BitrateAnalysis>('analyze_bitrate', {
+    inputPath,
+    streamIndex,
+    bucketSecs: options.bucketSecs,
+    durationUs: options.durationUs,
+  });
+}
+
+/**
+ * Stop the bitrate analysis running on a file
+ */
+export async function cancelAnalyzeBitrate(inputPath: string): Promise<void> {
+  return invoke('cancel_analyze_bitrate', { inputPath });
+}
@@ -21,6 +21,10 @@ export interface Track {
   pixelFormat?: string;
   colorRange?: string;
   colorSpace?: string;
+  colorPrimaries?: string;
+  colorTransfer?: string;
+  bitDepth?: number;
+  hdr?: MediaHdr;
   aspectRatio?: string;
   // Audio specific
   channels?: number;
@@ -67,54 +71,168 @@ export interface ExtractionResult {
   error?: string;
 }
 
-// FFprobe JSON output types
-export interface FFprobeStream {
+// MediaInfo returned by the probe_file command (schemaVersion 1)
+export const MEDIA_INFO_SCHEMA_VERSION = 1;
+
+export type MediaStreamKind = 'video' | 'audio' | 'subtitle' | 'data' | 'attachment' | 'unknown';
+
+export interface MediaFormat {
+  formatName: string | null;
+  formatLongName: string | null;
+  durationSecs: number | null;
+  startTimeSecs: number | null;
+  sizeBytes: number | null;
+  bitRate: number | null;
+  tags: Record<string, string>;
+}
+
+export interface MediaDisposition {
+  default: boolean;
+  dub: boolean;
+  original: boolean;
+  comment: boolean;
+  lyrics: boolean;
+  karaoke: boolean;
+  forced: boolean;
+  hearingImpaired: boolean;
+  visualImpaired: boolean;
+  cleanEffects: boolean;
+  attachedPic: boolean;
+  captions: boolean;
+  descriptions: boolean;
+  metadata: boolean;
+  dependent: boolean;
+  stillImage: boolean;
+}
+
+export type HdrFormat = 'hdr10' | 'hdr10Plus' | 'hlg' | 'dolbyVision';
+
+export interface MasteringDisplay {
+  redX: number;
+  redY: number;
+  greenX: number;
+  greenY: number;
+  blueX: number;
+  blueY: number;
+  whitePointX: number;
+  whitePointY: number;
+  minLuminance: number;
+  maxLuminance: number;
+}
+
+export interface DolbyVisionConfig {
+  versionMajor: number;
+  versionMinor: number;
+  profile: number;
+  level: number;
+  rpuPresent: boolean;
+  elPresent: boolean;
+  blPresent: boolean;
+  blSignalCompatibilityId: number | null;
+}
+
+export interface MediaHdr {
+  formats: HdrFormat[];
+  masteringDisplay: MasteringDisplay | null;
+  contentLightLevel: { maxCll: number; maxFall: number } | null;
+  dolbyVision: DolbyVisionConfig | null;
+}
+
+export interface MediaSideData {
+  sideDataType: string;
+  fields: Record<string, unknown>;
+}
+
+export interface MediaStream {
   index: number;
-  codec_name: string;
-  codec_long_name?: string;
-  codec_type: string;
-  width?: number;
-  height?: number;
-  sample_rate?: string;
-  channels?: number;
-  bit_rate?: string;
-  r_frame_rate?: string;
-  pix_fmt?: string;
-  level?: number;
-  color_range?: string;
-  color_space?: string;
-  color_transfer?: string;
-  color_primaries?: string;
-  sample_aspect_ratio?: string;
-  display_aspect_ratio?: string;
-  disposition?: {
-    default?: number;
-    forced?: number;
-  };
-  tags?: {
-    language?: string;
-    title?: string;
-    // bitrate in tags can sometimes be present
-    "BPS-eng"?: string;
-    BPS?: string;
-    DURATION?: string;
-    NUMBER_OF_FRAMES?: string;
-    NUMBER_OF_BYTES?: string;
-  };
-}
-
-export interface FFprobeFormat {
-  filename: string;
-  format_name?: string;
-  format_long_name?: string;
-  duration?: string;
-  size?: string;
-  bit_rate?: string;
-}
-
-export interface FFprobeOutput {
-  streams: FFprobeStream[];
-  format: FFprobeFormat;
+  kind: MediaStreamKind;
+  codecName: string | null;
+  codecLongName: string | null;
+  profile: string | null;
+  language: string | null;
+  title: string | null;
+  durationSecs: number | null;
+  bitRate: number | null;
+  sizeBytes: number | null;
+  frameCount: number | null;
+  width: number | null;
+  height: number | null;
+  frameRate: string | null;
+  avgFrameRate: string | null;
+  pixelFormat: string | null;
+  fieldOrder: string | null;
+  sampleAspectRatio: string | null;
+  displayAspectRatio: string | null;
+  colorRange: string | null;
+  colorSpace: string | null;
+  colorTransfer: string | null;
+  colorPrimaries: string | null;
+  chromaLocation: string | null;
+  bitDepth: number | null;
+  hdr: MediaHdr | null;
+  channels: number | null;
+  channelLayout: string | null;
+  sampleRate: number | null;
+  closedCaptions: boolean;
+  disposition: MediaDisposition;
+  tags: Record<string, string>;
+  sideData: MediaSideData[];
+}
+
+export interface MediaChapter {
+  id: number;
+  startSecs: number;
+  endSecs: number;
+  title: string | null;
+  tags: Record<string, string>;
+}
+
+export interface MediaAttachment {
+  index: number;
+  filename: string | null;
+  mimetype: string | null;
+  sizeBytes: number | null;
+}
+
+export interface MediaClosedCaptions {
+  streamIndex: number;
+  videoCodec: string | null;
+}
+
+export interface MediaInfo {
+  schemaVersion: number;
+  path: string;
+  format: MediaFormat;
+  streams: MediaStream[];
+  chapters: MediaChapter[];
+  attachments: MediaAttachment[];
+  closedCaptions: MediaClosedCaptions[];
+}
+
+export interface GopSummary {
+  count: number;
+  minSecs: number;
+  maxSecs: number;
+  averageSecs: number;
+  maxFrames: number;
+}
+
+/** Bitrate timeline of one stream; bucket `i` starts at `startSecs + i * bucketSecs` */
+export interface BitrateAnalysis {
+  streamIndex: number;
+  bucketSecs: number;
+  startSecs: number;
+  durationSecs: number;
+  packetCount: number;
+  totalBytes: number;
+  /** Bits per second of each bucket */
+  buckets: number[];
+  averageBitrate: number | null;
+  peakBitrate: number;
+  peakAtSecs: number;
+  keyframes: number[];
+  gopFrames: number[];
+  gop: GopSummary | null;
 }
 
 // ============================================================================